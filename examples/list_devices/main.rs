@@ -72,20 +72,25 @@ fn print_device(device: &soundio::Device, is_default: bool, short_output: bool)
 			if device.is_raw() { " (raw)" } else { "" }
 		);
 
+	if let Some(e) = device.probe_error() {
+		println!("    Probe error: {}", e);
+		return;
+	}
+
 	if short_output {
 		return;
 	}
 
-	println!("    Channel layouts: {:?}", device.layouts());
-	println!("    Current layout: {:?}", device.current_layout());
+	println!("    Channel layouts: {:?}", device.layouts().unwrap());
+	println!("    Current layout: {:?}", device.current_layout().unwrap());
 
-	println!("    Sample rates: {:?}", device.sample_rates());
-	println!("    Current sample rate: {}", device.current_sample_rate());
+	println!("    Sample rates: {:?}", device.sample_rates().unwrap());
+	println!("    Current sample rate: {}", device.current_sample_rate().unwrap());
 
-	println!("    Formats: {:?}", device.formats());
-	println!("    Current format: {}", device.current_format());
+	println!("    Formats: {:?}", device.formats().unwrap());
+	println!("    Current format: {}", device.current_format().unwrap());
 
-	println!("    Software latency: {:?}", device.software_latency());
+	println!("    Software latency: {:?}", device.software_latency().unwrap());
 }
 
 fn main() {
@@ -50,7 +50,10 @@ impl WavPlayer {
 				break;
 			}
 
-			stream.end_write();
+			if let Err(e) = stream.end_write() {
+				println!("Error writing to stream: {}", e);
+				return;
+			}
 		}
 		if self.finished != was_finished {
 	//		stream.wakeup();
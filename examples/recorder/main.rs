@@ -1,45 +1,12 @@
 extern crate soundio;
-extern crate crossbeam;
 extern crate hound;
 
 use std::io;
-use std::io::BufWriter;
-use std::fs::File;
 use std::env;
-
-struct WavRecorder {
-	writer: hound::WavWriter<BufWriter<File>>,
-}
-
-impl WavRecorder {
-	fn read_callback(&mut self, stream: &mut soundio::InStreamReader) {
-		let mut frames_left = stream.frame_count_max();
-
-		// libsoundio reads samples in chunks, so we need to loop until there's nothing to read.
-		loop {
-			if let Err(e) = stream.begin_read(frames_left) {
-				println!("Error reading from stream: {}", e);
-				return;
-			}
-			for f in 0..stream.frame_count() {
-				for c in 0..stream.channel_count() {
-					// In reality you shouldn't write to disk in the callback, but have some buffer instead.
-					match self.writer.write_sample(stream.sample::<i16>(c, f)) {
-						Ok(_) => {},
-						Err(e) => println!("Error: {}", e),
-					}
-				}
-			}
-
-			frames_left -= stream.frame_count();
-			if frames_left <= 0 {
-				break;
-			}
-
-			stream.end_read();
-		}
-	}
-}
+use std::thread;
+use std::time::Duration;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 // Print sound soundio debug info and record a sound.
 fn record(filename: &str) -> Result<(), String> {
@@ -56,7 +23,7 @@ fn record(filename: &str) -> Result<(), String> {
 	};
 
 	// Try to open the output file.
-	let writer = hound::WavWriter::create(filename, spec).map_err(|x| x.to_string())?;
+	let mut writer = hound::WavWriter::create(filename, spec).map_err(|x| x.to_string())?;
 
 	println!("Soundio version: {}", soundio::version_string());
 
@@ -78,29 +45,44 @@ fn record(filename: &str) -> Result<(), String> {
 
 	println!("Default input device: {} {}", input_dev.name(), if input_dev.is_raw() { "raw" } else { "cooked" } );
 
-	let mut recorder = WavRecorder {
-		writer: writer,
-	};
-
 	println!("Opening default input stream");
-	let mut input_stream = input_dev.open_instream(
+	// The stream buffers one second of audio, which we read and write to disk on this thread.
+	// That way we never do anything slow in the real-time read callback.
+	let mut input_stream = input_dev.open_blocking_instream::<i16>(
 		sample_rate as _,
 		soundio_format,
 		default_layout,
 		0.1,
-		|x| recorder.read_callback(x),
-		None::<fn()>,
-		None::<fn(soundio::Error)>,
+		sample_rate as _,
 	)?;
 
+	// Wait for the user to press a key on another thread.
+	println!("Press enter to stop recording");
+	let stop = Arc::new(AtomicBool::new(false));
+	let stop_setter = stop.clone();
+	thread::spawn(move || {
+		let stdin = io::stdin();
+		let input = &mut String::new();
+		let _ = stdin.read_line(input);
+		stop_setter.store(true, Ordering::SeqCst);
+	});
+
+	let mut buffer = vec![0i16; 4096 * channels as usize];
+
 	println!("Starting stream");
-	input_stream.start()?;
+	while !stop.load(Ordering::SeqCst) {
+		match input_stream.read(&mut buffer, Some(Duration::from_millis(100))) {
+			Ok(frames) => {
+				for &sample in &buffer[..frames * channels as usize] {
+					writer.write_sample(sample).map_err(|x| x.to_string())?;
+				}
+			},
+			Err(soundio::Error::Overflow) => println!("Overflow! Some audio was lost."),
+			Err(e) => return Err(e.into()),
+		}
+	}
 
-	// Wait for the user to press a key.
-	println!("Press enter to stop recording");
-	let stdin = io::stdin();
-	let input = &mut String::new();
-	let _ = stdin.read_line(input);
+	writer.finalize().map_err(|x| x.to_string())?;
 
 	Ok(())
 }
@@ -33,7 +33,10 @@ impl SineWavePlayer {
 				break;
 			}
 
-			stream.end_write();
+			if let Err(e) = stream.end_write() {
+				println!("Error writing to stream: {}", e);
+				return;
+			}
 		}
 	}
 }
@@ -1014,6 +1014,12 @@ extern {
 	pub fn soundio_outstream_get_latency(outstream: *mut SoundIoOutStream,
 			out_latency: *mut c_double) -> c_int;
 
+	// Set the volume of the stream, from 0.0 to 1.0. This is only supported by some backends.
+	//
+	// Possible errors:
+	// * #SoundIoErrorIncompatibleBackend
+	pub fn soundio_outstream_set_volume(outstream: *mut SoundIoOutStream, volume: c_double) -> c_int;
+
 
 
 	// Input Streams
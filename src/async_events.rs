@@ -0,0 +1,384 @@
+use super::error::*;
+use super::context::*;
+use super::context_lock::*;
+use super::stream_event::*;
+
+use futures::Stream;
+use futures::task::AtomicWaker;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{self, Poll};
+use std::thread;
+use std::time::Duration;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Sender, Receiver};
+
+// How often a `StreamEvents` checks whether the audio thread has sent an event.
+const STREAM_EVENT_POLL_MS: u64 = 10;
+
+/// An event from a `Context` or one of its streams, produced by the `Stream`s returned by
+/// `Context::events()`, `OutStream::events()` and `InStream::events()`. They all have the same
+/// item type so that they can be merged.
+///
+/// This requires the `async` feature.
+#[derive(Debug, Copy, Clone)]
+pub enum Event {
+	/// The list of devices changed. See `Context::new_with_callbacks()`.
+	DevicesChanged,
+	/// The backend disconnected. See `Context::new_with_callbacks()`.
+	BackendDisconnected(Error),
+	/// A diagnostic event from an output or input stream, such as an underflow.
+	Stream(StreamEvent),
+}
+
+// The sending end of a `ContextEvents`. These are called on the thread that is waiting for
+// events, not an audio thread.
+pub(crate) struct EventSubscriber {
+	sender: Sender<Event>,
+	waker: Arc<AtomicWaker>,
+}
+
+impl EventSubscriber {
+	// Send an event and wake the task. Returns false if the `ContextEvents` has been dropped.
+	pub fn send(&self, event: Event) -> bool {
+		if self.sender.send(event).is_err() {
+			return false;
+		}
+		self.waker.wake();
+		true
+	}
+}
+
+// A pointer to the context's lock that can be moved to the helper thread. The `Context` stops the
+// thread before it lets go of the lock.
+struct LockPtr(*const ContextLock);
+
+unsafe impl Send for LockPtr {}
+
+// A request for the helper thread to call `soundio_wait_events()` once.
+struct WaitState {
+	done: AtomicBool,
+	waker: AtomicWaker,
+}
+
+// The helper thread that calls `soundio_wait_events()` for `WaitEvents`, so that it works with any
+// executor. A `Context` starts it the first time it is needed and it lives as long as the context,
+// waiting for the next request in between.
+pub(crate) struct WaitThread {
+	// Dropping this stops the thread.
+	requests: Option<Sender<Arc<WaitState>>>,
+	thread: Option<thread::JoinHandle<()>>,
+}
+
+impl WaitThread {
+	pub fn spawn(context: &ContextInner) -> WaitThread {
+		let lock = LockPtr(context.context_lock());
+		let (sender, receiver) = channel::<Arc<WaitState>>();
+		let thread = thread::Builder::new()
+			.name("soundio wait_events".to_string())
+			.spawn(move || {
+				let lock = lock;
+				for state in receiver.iter() {
+					// Panics in the callbacks are caught and stored in the context, and resumed by
+					// `WaitEvents::poll()`.
+					unsafe { (*lock.0).wait_events(); }
+					state.done.store(true, Ordering::Release);
+					state.waker.wake();
+				}
+			})
+			.expect("Couldn't spawn the wait_events thread");
+
+		WaitThread {
+			requests: Some(sender),
+			thread: Some(thread),
+		}
+	}
+
+	// Ask the thread to wait for events once. It sets `done` when it has finished.
+	fn wait(&self) -> Arc<WaitState> {
+		let state = Arc::new(WaitState {
+			done: AtomicBool::new(false),
+			waker: AtomicWaker::new(),
+		});
+		if let Some(ref requests) = self.requests {
+			let _ = requests.send(state.clone());
+		}
+		state
+	}
+}
+
+impl Drop for WaitThread {
+	fn drop(&mut self) {
+		// The thread isn't waiting, because a `WaitEvents` borrows the context until it has
+		// finished, so it stops as soon as the channel is closed.
+		self.requests = None;
+		if let Some(thread) = self.thread.take() {
+			let _ = thread.join();
+		}
+	}
+}
+
+/// A future that completes when `Context::wait_events()` would return, obtained using
+/// `Context::wait_events_async()`.
+///
+/// The first time it is polled it asks a helper thread to call `wait_events()`, so it works with
+/// any executor. The context starts the thread the first time it is needed, and keeps it until it
+/// is dropped. The device change and backend disconnect callbacks are called on that thread. The
+/// context is borrowed mutably so that nothing else can use it in the meantime. If the future is
+/// dropped before it completes it wakes the helper thread up and waits for it to finish waiting.
+///
+/// If one of the callbacks panics, the panic is resumed when the future is polled.
+///
+/// This requires the `async` feature.
+pub struct WaitEvents<'c, 'a: 'c> {
+	context: &'c mut Context<'a>,
+	// The request to the helper thread, once it has been made.
+	state: Option<Arc<WaitState>>,
+}
+
+impl<'c, 'a> WaitEvents<'c, 'a> {
+	// Use `Context::wait_events_async()` rather than calling this directly.
+	pub(crate) fn new(context: &'c mut Context<'a>) -> WaitEvents<'c, 'a> {
+		WaitEvents {
+			context: context,
+			state: None,
+		}
+	}
+
+	// Stop the helper thread waiting, if it is.
+	fn stop(&mut self) {
+		if let Some(state) = self.state.take() {
+			// A wakeup is lost if the thread isn't waiting yet, so keep trying until it has
+			// finished.
+			while !state.done.load(Ordering::Acquire) {
+				self.context.wakeup();
+				thread::sleep(Duration::from_millis(1));
+			}
+		}
+	}
+}
+
+impl<'c, 'a> Future for WaitEvents<'c, 'a> {
+	type Output = ();
+
+	fn poll(self: Pin<&mut Self>, cx: &mut task::Context) -> Poll<()> {
+		let this = self.get_mut();
+		if this.state.is_none() {
+			this.state = Some(this.context.wait_thread().wait());
+		}
+
+		let done = match this.state {
+			Some(ref state) => {
+				state.waker.register(cx.waker());
+				state.done.load(Ordering::Acquire)
+			},
+			None => unreachable!(),
+		};
+		if !done {
+			return Poll::Pending;
+		}
+
+		this.state = None;
+		this.context.resume_panic();
+		Poll::Ready(())
+	}
+}
+
+impl<'c, 'a> Drop for WaitEvents<'c, 'a> {
+	fn drop(&mut self) {
+		self.stop();
+	}
+}
+
+/// A `Stream` of device changes and backend disconnects, obtained using `Context::events()`.
+///
+/// It waits for events using `WaitEvents`, so the callbacks are called on the context's helper
+/// thread while it is waiting. The stream never ends. The context is borrowed mutably, but you can get at it
+/// between events with `context()`, for example to list the devices after
+/// `Event::DevicesChanged`.
+///
+/// While a `ContextEvents` exists the default backend disconnect callback reports the disconnect
+/// as `Event::BackendDisconnected` instead of panicking.
+///
+/// This requires the `async` feature.
+///
+/// # Examples
+///
+/// ```
+/// extern crate futures;
+/// extern crate soundio;
+///
+/// use futures::StreamExt;
+///
+/// # fn main() {
+/// let mut ctx = soundio::Context::new();
+/// ctx.connect_backend(soundio::Backend::Dummy).expect("Couldn't connect to backend");
+///
+/// let mut events = ctx.events();
+/// if let Some(soundio::Event::DevicesChanged) = futures::executor::block_on(events.next()) {
+///     println!("{} output devices", events.context().output_device_count());
+/// }
+/// # }
+/// ```
+pub struct ContextEvents<'c, 'a: 'c> {
+	receiver: Receiver<Event>,
+	waker: Arc<AtomicWaker>,
+	wait: WaitEvents<'c, 'a>,
+}
+
+impl<'c, 'a> ContextEvents<'c, 'a> {
+	// Use `Context::events()` rather than calling this directly.
+	pub(crate) fn new(context: &'c mut Context<'a>) -> ContextEvents<'c, 'a> {
+		let (sender, receiver) = channel();
+		let waker = Arc::new(AtomicWaker::new());
+		context.inner.add_event_subscriber(EventSubscriber {
+			sender: sender,
+			waker: waker.clone(),
+		});
+		ContextEvents {
+			receiver: receiver,
+			waker: waker,
+			wait: WaitEvents::new(context),
+		}
+	}
+
+	/// The `Context` that the events are from. This stops waiting for events until the stream is
+	/// polled again.
+	pub fn context(&mut self) -> &Context<'a> {
+		self.wait.stop();
+		self.wait.context
+	}
+}
+
+impl<'c, 'a> Stream for ContextEvents<'c, 'a> {
+	type Item = Event;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context) -> Poll<Option<Event>> {
+		let this = self.get_mut();
+		this.waker.register(cx.waker());
+		if let Ok(event) = this.receiver.try_recv() {
+			return Poll::Ready(Some(event));
+		}
+		match Pin::new(&mut this.wait).poll(cx) {
+			Poll::Ready(()) => {
+				// Events sent during the wait have woken the task already, and a spurious wakeup
+				// starts waiting again next time.
+				cx.waker().wake_by_ref();
+				Poll::Pending
+			},
+			Poll::Pending => Poll::Pending,
+		}
+	}
+}
+
+/// A `Stream` of an output or input stream's diagnostic events, obtained using
+/// `OutStream::events()` or `InStream::events()`. Every item is an `Event::Stream`, and the
+/// stream never ends.
+///
+/// It takes events from the same queue as `OutStream::poll_event()`. The audio thread only sets a
+/// flag when it queues an event, so it never calls the executor's waker. Instead a helper thread,
+/// which lives as long as the `StreamEvents`, checks the flag every 10 ms and wakes the task.
+///
+/// This requires the `async` feature.
+pub struct StreamEvents<'s> {
+	events: &'s mut EventReceiver,
+	watcher: EventWatcher,
+}
+
+impl<'s> StreamEvents<'s> {
+	// Use `OutStream::events()` or `InStream::events()` rather than calling this directly.
+	pub(crate) fn new(events: &'s mut EventReceiver) -> StreamEvents<'s> {
+		let watcher = EventWatcher::spawn(events.pending());
+		StreamEvents {
+			events: events,
+			watcher: watcher,
+		}
+	}
+}
+
+impl<'s> Stream for StreamEvents<'s> {
+	type Item = Event;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context) -> Poll<Option<Event>> {
+		let this = self.get_mut();
+		// Register before polling so that an event queued in between isn't missed.
+		this.watcher.waker.register(cx.waker());
+		match this.events.poll() {
+			Some(event) => Poll::Ready(Some(Event::Stream(event))),
+			None => Poll::Pending,
+		}
+	}
+}
+
+// The helper thread of a `StreamEvents`. It wakes the task when the audio thread has set the
+// event queue's pending flag.
+struct EventWatcher {
+	waker: Arc<AtomicWaker>,
+	stop: Arc<AtomicBool>,
+	thread: Option<thread::JoinHandle<()>>,
+}
+
+impl EventWatcher {
+	fn spawn(pending: Arc<AtomicBool>) -> EventWatcher {
+		let waker = Arc::new(AtomicWaker::new());
+		let stop = Arc::new(AtomicBool::new(false));
+		let thread_waker = waker.clone();
+		let thread_stop = stop.clone();
+		let thread = thread::Builder::new()
+			.name("soundio stream events".to_string())
+			.spawn(move || {
+				while !thread_stop.load(Ordering::Acquire) {
+					if pending.swap(false, Ordering::AcqRel) {
+						thread_waker.wake();
+					}
+					thread::park_timeout(Duration::from_millis(STREAM_EVENT_POLL_MS));
+				}
+			})
+			.expect("Couldn't spawn the stream events thread");
+
+		EventWatcher {
+			waker: waker,
+			stop: stop,
+			thread: Some(thread),
+		}
+	}
+}
+
+impl Drop for EventWatcher {
+	fn drop(&mut self) {
+		self.stop.store(true, Ordering::Release);
+		if let Some(thread) = self.thread.take() {
+			thread.thread().unpark();
+			let _ = thread.join();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::backend::*;
+	use futures::StreamExt;
+	use futures::executor::block_on;
+
+	#[test]
+	fn devices_changed_event() {
+		let mut ctx = Context::new();
+		ctx.connect_backend(Backend::Dummy).expect("Couldn't connect to backend");
+
+		{
+			// The first wait reports the initial devices, then keeps waiting until the stream
+			// stops it.
+			let mut events = ctx.events();
+			match block_on(events.next()) {
+				Some(Event::DevicesChanged) => {},
+				event => panic!("Unexpected event: {:?}", event),
+			}
+			assert!(events.context().output_device_count() > 0);
+		}
+
+		assert!(ctx.output_device_count() > 0);
+	}
+}
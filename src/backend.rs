@@ -22,6 +22,7 @@ use std::fmt;
 /// println!("The name of PulseAudio is {}", soundio::Backend::PulseAudio);
 /// ```
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Backend {
 	None,
 	Jack,
@@ -0,0 +1,350 @@
+use super::error::*;
+use super::format::*;
+use super::layout::*;
+use super::sample::*;
+use super::device::*;
+use super::instream::*;
+use super::ring_buffer::*;
+use super::blocking_outstream::WAIT_TIMEOUT_MS;
+
+use std::cmp;
+use std::time::{Duration, Instant};
+use std::os::raw::c_int;
+use std::sync::{Arc, Mutex, Condvar};
+use std::sync::atomic::{AtomicBool, AtomicUsize, AtomicIsize, Ordering};
+
+// The maximum number of overflows that can be waiting to be read. The ring buffer they are
+// queued in is rounded up to a page, so this is checked when they are written.
+const MAX_PENDING_OVERFLOWS: usize = 64;
+
+/// `BlockingInStream` is an input stream that you pull audio from on a normal thread, like reading
+/// from a file, instead of reading it in a real-time callback.
+///
+/// It is obtained using `Device::open_blocking_instream()`. Samples are interleaved and of type
+/// `T`; they are converted from the stream's format in the read callback and queued in a
+/// `RingBuffer` until you call `read()`.
+///
+/// If input is lost, either because the backend reported an overflow or because the buffer was full
+/// because you didn't call `read()` often enough, `read()` returns `Err(Error::Overflow)` at that
+/// point in the stream. The frames either side of it are never returned by the same `read()`.
+/// The total number of overflows is returned by `overflow_count()`. At most 64 overflows that
+/// `read()` hasn't reached yet are marked in the stream; any more are only counted.
+///
+/// The stream is started by the first call to `read()`, or by `start()`.
+///
+/// # Examples
+///
+/// ```no_run
+/// let mut ctx = soundio::Context::new();
+/// ctx.connect().unwrap();
+/// ctx.flush_events();
+/// let dev = ctx.default_input_device().unwrap();
+///
+/// let mut stream = dev.open_blocking_instream::<i16>(
+///     44100,
+///     soundio::native::S16NE,
+///     soundio::ChannelLayout::get_default(2),
+///     0.1,
+///     44100,
+/// ).unwrap();
+///
+/// let mut buffer = vec![0i16; 2 * 1024];
+/// loop {
+///     match stream.read(&mut buffer, None) {
+///         Ok(frames) => println!("Read {} frames", frames),
+///         Err(soundio::Error::Overflow) => println!("Some input was lost"),
+///         Err(e) => panic!("Error reading: {}", e),
+///     }
+/// }
+/// ```
+pub struct BlockingInStream<'a, T: Sample + Copy + Send + 'a> {
+	stream: InStream<'a>,
+	consumer: RingBufferConsumer<T>,
+	// The positions (in frames) of overflows in the stream.
+	overflows: RingBufferConsumer<usize>,
+	// The position of the next overflow, if it has been taken from `overflows`.
+	next_overflow: Option<usize>,
+	// The number of frames returned by `read()` so far. This wraps around, as do the positions.
+	frames_read: usize,
+	shared: Arc<BlockingInStreamShared>,
+	channel_count: usize,
+	started: bool,
+}
+
+// State shared between the `BlockingInStream` and its callbacks.
+struct BlockingInStreamShared {
+	// Used with `data` to wait for the read callback to add frames to the ring buffer.
+	lock: Mutex<()>,
+	data: Condvar,
+	overflow_count: AtomicUsize,
+	// Set by the overflow callback so that the read callback records an overflow.
+	overflowed: AtomicBool,
+	// The last error passed to the error callback, or 0.
+	error: AtomicIsize,
+}
+
+impl<'a, T: Sample + Copy + Send + 'a> BlockingInStream<'a, T> {
+	// Open the stream. Use `Device::open_blocking_instream()` rather than calling this directly.
+	pub(crate) fn open(
+				device: &Device<'a>,
+				sample_rate: i32,
+				format: Format,
+				layout: ChannelLayout,
+				latency: f64,
+				buffer_frames: usize,
+				) -> Result<BlockingInStream<'a, T>> {
+
+		let channel_count = layout.channels.len();
+		if channel_count == 0 || buffer_frames == 0 {
+			return Err(Error::Invalid);
+		}
+
+		let (mut producer, consumer) = device.context.ring_buffer::<T>(buffer_frames * channel_count).split();
+		let (mut overflow_producer, overflow_consumer) = device.context.ring_buffer::<usize>(MAX_PENDING_OVERFLOWS).split();
+
+		let shared = Arc::new(BlockingInStreamShared {
+			lock: Mutex::new(()),
+			data: Condvar::new(),
+			overflow_count: AtomicUsize::new(0),
+			overflowed: AtomicBool::new(false),
+			error: AtomicIsize::new(0),
+		});
+
+		// The read callback converts whole frames into this, so it never allocates.
+		let mut scratch = vec![T::from_f32(0.0); producer.capacity() / channel_count * channel_count];
+		// The number of frames written to the ring buffer so far.
+		let mut frames_written: usize = 0;
+		// Whether an overflow at `frames_written` has already been recorded.
+		let mut overflow_recorded = false;
+
+		let read_shared = shared.clone();
+		let read_callback = move |reader: &mut InStreamReader| {
+			let channel_count = reader.channel_count();
+
+			let mut record_overflow = |frames_written: usize, overflow_recorded: &mut bool| {
+				// Adjacent overflows are merged.
+				if *overflow_recorded {
+					return;
+				}
+				read_shared.overflow_count.fetch_add(1, Ordering::Relaxed);
+				// If there are too many pending overflows this one isn't marked in the stream, but
+				// it is still counted.
+				if overflow_producer.fill_count() < MAX_PENDING_OVERFLOWS {
+					overflow_producer.write(&[frames_written]);
+				}
+				*overflow_recorded = true;
+			};
+
+			if read_shared.overflowed.swap(false, Ordering::Relaxed) {
+				record_overflow(frames_written, &mut overflow_recorded);
+			}
+
+			let mut frames_left = reader.frame_count_max();
+			while frames_left > 0 {
+				let frame_count = match reader.begin_read(frames_left) {
+					Ok(0) | Err(_) => break,
+					Ok(x) => x,
+				};
+
+				let free = cmp::min(producer.free_count(), scratch.len()) / channel_count;
+				let frames = cmp::min(frame_count, free);
+				if frames > 0 {
+					let read = reader.read_interleaved(&mut scratch[..frames * channel_count]);
+					producer.write(&scratch[..read * channel_count]);
+					frames_written = frames_written.wrapping_add(read);
+					overflow_recorded = false;
+				}
+				if frames < frame_count {
+					// The buffer is full so the rest of the frames are lost.
+					record_overflow(frames_written, &mut overflow_recorded);
+				}
+
+				if reader.end_read().is_err() {
+					break;
+				}
+				frames_left -= frame_count;
+			}
+
+			read_shared.data.notify_all();
+		};
+
+		let overflow_shared = shared.clone();
+		let overflow_callback = move || {
+			overflow_shared.overflowed.store(true, Ordering::Relaxed);
+		};
+
+		let error_shared = shared.clone();
+		let error_callback = move |error: Error| {
+			error_shared.error.store(c_int::from(error) as isize, Ordering::Release);
+			error_shared.data.notify_all();
+		};
+
+		let stream = device.open_instream(
+			sample_rate,
+			format,
+			layout,
+			latency,
+			read_callback,
+			Some(overflow_callback),
+			Some(error_callback),
+		)?;
+
+		Ok(BlockingInStream {
+			stream: stream,
+			consumer: consumer,
+			overflows: overflow_consumer,
+			next_overflow: None,
+			frames_read: 0,
+			shared: shared,
+			channel_count: channel_count,
+			started: false,
+		})
+	}
+
+	/// Read interleaved samples into `samples`, blocking until at least one frame is available, or
+	/// `timeout` elapses. It returns the number of frames read, which is 0 if it timed out.
+	///
+	/// Only whole frames are read, so `samples` should be at least `channel_count()` long.
+	///
+	/// # Errors
+	///
+	/// * `Error::Overflow` - some frames are missing at this point in the stream. The next call
+	///   continues with the frames after the gap.
+	/// * Any error passed to the stream's error callback, e.g. `Error::Streaming`. After that
+	///   the stream can't be used.
+	/// * Any error from `InStream::start()`.
+	pub fn read(&mut self, samples: &mut [T], timeout: Option<Duration>) -> Result<usize> {
+		self.start()?;
+
+		let deadline = timeout.map(|t| Instant::now() + t);
+		let max_frames = samples.len() / self.channel_count;
+
+		loop {
+			self.check_error()?;
+
+			// Check for overflows before the data, because the read callback records an overflow
+			// before writing the frames that come after it.
+			if self.next_overflow.is_none() {
+				let mut position = [0usize];
+				if self.overflows.read(&mut position) == 1 {
+					self.next_overflow = Some(position[0]);
+				}
+			}
+			if self.next_overflow == Some(self.frames_read) {
+				self.next_overflow = None;
+				return Err(Error::Overflow);
+			}
+
+			let mut frames = cmp::min(self.consumer.fill_count() / self.channel_count, max_frames);
+			if let Some(position) = self.next_overflow {
+				frames = cmp::min(frames, position.wrapping_sub(self.frames_read));
+			}
+
+			if frames > 0 || max_frames == 0 {
+				let read = self.consumer.read(&mut samples[..frames * self.channel_count]) / self.channel_count;
+				self.frames_read = self.frames_read.wrapping_add(read);
+				return Ok(read);
+			}
+
+			let wait = match deadline {
+				Some(deadline) => {
+					let now = Instant::now();
+					if now >= deadline {
+						return Ok(0);
+					}
+					cmp::min(deadline - now, Duration::from_millis(WAIT_TIMEOUT_MS))
+				},
+				None => Duration::from_millis(WAIT_TIMEOUT_MS),
+			};
+			self.wait(wait);
+		}
+	}
+
+	/// Start the stream if it hasn't been started yet. `read()` does this automatically, but you may
+	/// want to start recording before you start reading.
+	///
+	/// # Errors
+	///
+	/// Any error from `InStream::start()`.
+	pub fn start(&mut self) -> Result<()> {
+		if !self.started {
+			self.stream.start()?;
+			self.started = true;
+		}
+		Ok(())
+	}
+
+	/// The number of times input has been lost, either because the backend reported an overflow or
+	/// because `read()` wasn't called often enough and the buffer filled up.
+	pub fn overflow_count(&self) -> usize {
+		self.shared.overflow_count.load(Ordering::Relaxed)
+	}
+
+	/// The number of frames that have been recorded but not yet read.
+	pub fn buffered_frames(&self) -> usize {
+		self.consumer.fill_count() / self.channel_count
+	}
+
+	/// The number of frames that can be buffered. This may be more than was requested.
+	pub fn capacity_frames(&self) -> usize {
+		self.consumer.capacity() / self.channel_count
+	}
+
+	/// Return the number of channels in this stream.
+	pub fn channel_count(&self) -> usize {
+		self.channel_count
+	}
+
+	/// The underlying `InStream`, which can be used to query the stream's format, latency, etc.
+	pub fn stream(&self) -> &InStream<'a> {
+		&self.stream
+	}
+
+	// Wait until the read callback or error callback is called, or the timeout elapses.
+	fn wait(&self, timeout: Duration) {
+		let guard = match self.shared.lock.lock() {
+			Ok(guard) => guard,
+			Err(poisoned) => poisoned.into_inner(),
+		};
+		let _ = self.shared.data.wait_timeout(guard, timeout);
+	}
+
+	fn check_error(&self) -> Result<()> {
+		if self.stream.has_panicked() {
+			return Err(Error::Streaming);
+		}
+		match self.shared.error.load(Ordering::Acquire) {
+			0 => Ok(()),
+			x => Err((x as c_int).into()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::context::*;
+	use super::super::backend::*;
+
+	#[test]
+	fn read_with_timeout() {
+		let mut ctx = Context::new();
+		ctx.connect_backend(Backend::Dummy).expect("Couldn't connect to backend");
+		ctx.flush_events();
+
+		let dev = ctx.default_input_device().expect("No input device");
+		let mut stream = dev.open_blocking_instream::<f32>(48000, Format::Float32LE, ChannelLayout::get_default(2), 0.01, 48000)
+			.expect("Couldn't open stream");
+
+		let mut samples = vec![0.0f32; 2 * 256];
+		let mut frames = 0;
+		while frames < 4800 {
+			match stream.read(&mut samples, Some(Duration::from_secs(1))) {
+				Ok(0) => panic!("Timed out"),
+				Ok(x) => frames += x,
+				Err(Error::Overflow) => {},
+				Err(e) => panic!("Error reading: {}", e),
+			}
+		}
+	}
+}
@@ -0,0 +1,322 @@
+use super::error::*;
+use super::format::*;
+use super::layout::*;
+use super::sample::*;
+use super::device::*;
+use super::outstream::*;
+use super::ring_buffer::*;
+
+use std::cmp;
+use std::thread;
+use std::time::Duration;
+use std::os::raw::c_int;
+use std::sync::{Arc, Mutex, Condvar};
+use std::sync::atomic::{AtomicUsize, AtomicIsize, Ordering};
+
+// How long `write()` and `drain()` (and `BlockingInStream::read()`) wait before checking the buffer again. The write callback
+// (or read callback) notifies the waiting thread without taking the lock (so that it never blocks), which means a
+// notification can occasionally be missed. This bounds how long that can delay things.
+pub(crate) const WAIT_TIMEOUT_MS: u64 = 10;
+
+/// `BlockingOutStream` is an output stream that you push audio to from a normal thread, like
+/// writing to a file, instead of writing it from a real-time callback.
+///
+/// It is obtained using `Device::open_blocking_outstream()`. Samples are interleaved and of type
+/// `T`; they are converted to the stream's format in the write callback. Internally they are
+/// queued in a `RingBuffer` which the write callback reads from.
+///
+/// The stream starts playing automatically once the buffer is full, or when `flush()` or `drain()`
+/// is called. If the buffer runs empty while playing, silence is played and counted by
+/// `starved_frames()`.
+///
+/// Dropping the stream stops playback immediately. Call `drain()` first to play all of the
+/// queued audio.
+///
+/// # Examples
+///
+/// ```no_run
+/// let mut ctx = soundio::Context::new();
+/// ctx.connect().unwrap();
+/// ctx.flush_events();
+/// let dev = ctx.default_output_device().unwrap();
+///
+/// let mut stream = dev.open_blocking_outstream::<f32>(
+///     44100,
+///     soundio::native::Float32NE,
+///     soundio::ChannelLayout::get_default(2),
+///     0.1,
+///     44100,
+/// ).unwrap();
+///
+/// let silence = vec![0.0f32; 2 * 44100];
+/// stream.write(&silence).unwrap();
+/// stream.drain().unwrap();
+/// ```
+pub struct BlockingOutStream<'a, T: Sample + Copy + Send + 'a> {
+	stream: OutStream<'a>,
+	producer: RingBufferProducer<T>,
+	shared: Arc<BlockingOutStreamShared>,
+	channel_count: usize,
+	started: bool,
+}
+
+// State shared between the `BlockingOutStream` and its callbacks.
+struct BlockingOutStreamShared {
+	// Used with `space` to wait for the write callback to free space in the ring buffer.
+	lock: Mutex<()>,
+	space: Condvar,
+	underflow_count: AtomicUsize,
+	starved_frames: AtomicUsize,
+	// The last error passed to the error callback, or 0.
+	error: AtomicIsize,
+}
+
+impl<'a, T: Sample + Copy + Send + 'a> BlockingOutStream<'a, T> {
+	// Open the stream. Use `Device::open_blocking_outstream()` rather than calling this directly.
+	pub(crate) fn open(
+				device: &Device<'a>,
+				sample_rate: i32,
+				format: Format,
+				layout: ChannelLayout,
+				latency: f64,
+				buffer_frames: usize,
+				) -> Result<BlockingOutStream<'a, T>> {
+
+		let channel_count = layout.channels.len();
+		if channel_count == 0 || buffer_frames == 0 {
+			return Err(Error::Invalid);
+		}
+
+		let (producer, mut consumer) = device.context.ring_buffer::<T>(buffer_frames * channel_count).split();
+
+		let shared = Arc::new(BlockingOutStreamShared {
+			lock: Mutex::new(()),
+			space: Condvar::new(),
+			underflow_count: AtomicUsize::new(0),
+			starved_frames: AtomicUsize::new(0),
+			error: AtomicIsize::new(0),
+		});
+
+		// The write callback reads whole frames into this, so it never allocates.
+		let mut scratch = vec![T::from_f32(0.0); consumer.capacity() / channel_count * channel_count];
+
+		let write_shared = shared.clone();
+		let write_callback = move |writer: &mut OutStreamWriter| {
+			let channel_count = writer.channel_count();
+			let available = consumer.fill_count() / channel_count;
+			let mut frames_left = cmp::max(writer.frame_count_min(), cmp::min(available, writer.frame_count_max()));
+
+			while frames_left > 0 {
+				let frame_count = match writer.begin_write(frames_left) {
+					Ok(0) | Err(_) => break,
+					Ok(x) => x,
+				};
+
+				let wanted = cmp::min(frame_count * channel_count, scratch.len());
+				let read = consumer.read(&mut scratch[..wanted]);
+				let written = writer.write_interleaved(&scratch[..read]);
+
+				// If there isn't enough audio queued, fill the rest with silence.
+				if written < frame_count {
+					for c in 0..channel_count {
+						for f in written..frame_count {
+							writer.set_sample::<f32>(c, f, 0.0);
+						}
+					}
+					write_shared.starved_frames.fetch_add(frame_count - written, Ordering::Relaxed);
+				}
+
+				if writer.end_write().is_err() {
+					break;
+				}
+				frames_left -= frame_count;
+			}
+
+			write_shared.space.notify_all();
+		};
+
+		let underflow_shared = shared.clone();
+		let underflow_callback = move || {
+			underflow_shared.underflow_count.fetch_add(1, Ordering::Relaxed);
+		};
+
+		let error_shared = shared.clone();
+		let error_callback = move |error: Error| {
+			error_shared.error.store(c_int::from(error) as isize, Ordering::Release);
+			error_shared.space.notify_all();
+		};
+
+		let stream = device.open_outstream(
+			sample_rate,
+			format,
+			layout,
+			latency,
+			write_callback,
+			Some(underflow_callback),
+			Some(error_callback),
+		)?;
+
+		Ok(BlockingOutStream {
+			stream: stream,
+			producer: producer,
+			shared: shared,
+			channel_count: channel_count,
+			started: false,
+		})
+	}
+
+	/// Queue interleaved samples for playback, blocking until there is space for all of them.
+	///
+	/// If the buffer fills up and the stream hasn't been started yet, it is started.
+	///
+	/// # Errors
+	///
+	/// * `Error::Invalid` - `samples` doesn't contain a whole number of frames.
+	/// * Any error passed to the stream's error callback, e.g. `Error::Streaming`. After that
+	///   the stream can't be used.
+	/// * Any error from `OutStream::start()`.
+	pub fn write(&mut self, samples: &[T]) -> Result<()> {
+		if samples.len() % self.channel_count != 0 {
+			return Err(Error::Invalid);
+		}
+
+		let mut remaining = samples;
+		while !remaining.is_empty() {
+			self.check_error()?;
+
+			// Only write whole frames.
+			let free = self.producer.free_count() / self.channel_count * self.channel_count;
+			let written = self.producer.write(&remaining[..cmp::min(free, remaining.len())]);
+			remaining = &remaining[written..];
+
+			if !remaining.is_empty() {
+				// The buffer is full so it's time to start playing if we aren't already.
+				self.start()?;
+				self.wait();
+			}
+		}
+		Ok(())
+	}
+
+	/// Start playing the queued audio if the stream hasn't been started yet. This doesn't block.
+	///
+	/// # Errors
+	///
+	/// The same as `write()`.
+	pub fn flush(&mut self) -> Result<()> {
+		self.check_error()?;
+		self.start()
+	}
+
+	/// Start the stream if necessary, then block until all of the queued audio has been played.
+	/// This includes waiting for the stream's software latency, so that the last samples written
+	/// have left the device's buffer.
+	///
+	/// # Errors
+	///
+	/// The same as `write()`.
+	pub fn drain(&mut self) -> Result<()> {
+		self.flush()?;
+		while self.producer.fill_count() > 0 {
+			self.wait();
+			self.check_error()?;
+		}
+		thread::sleep(duration_from_secs(self.stream.software_latency()));
+		Ok(())
+	}
+
+	/// The number of times the backend reported an underflow, i.e. the write callback wasn't called
+	/// in time.
+	pub fn underflow_count(&self) -> usize {
+		self.shared.underflow_count.load(Ordering::Relaxed)
+	}
+
+	/// The number of frames of silence that were played because not enough audio had been
+	/// written.
+	pub fn starved_frames(&self) -> usize {
+		self.shared.starved_frames.load(Ordering::Relaxed)
+	}
+
+	/// The number of frames that have been written but not yet passed to the device.
+	pub fn buffered_frames(&self) -> usize {
+		self.producer.fill_count() / self.channel_count
+	}
+
+	/// The number of frames that can be buffered. This may be more than was requested.
+	pub fn capacity_frames(&self) -> usize {
+		self.producer.capacity() / self.channel_count
+	}
+
+	/// Return the number of channels in this stream.
+	pub fn channel_count(&self) -> usize {
+		self.channel_count
+	}
+
+	/// The underlying `OutStream`, which can be used to query the stream's format, latency, etc.
+	pub fn stream(&self) -> &OutStream<'a> {
+		&self.stream
+	}
+
+	fn start(&mut self) -> Result<()> {
+		if !self.started {
+			self.stream.start()?;
+			self.started = true;
+		}
+		Ok(())
+	}
+
+	// Wait until the write callback or error callback is called, or the timeout elapses.
+	fn wait(&self) {
+		let guard = match self.shared.lock.lock() {
+			Ok(guard) => guard,
+			Err(poisoned) => poisoned.into_inner(),
+		};
+		let _ = self.shared.space.wait_timeout(guard, Duration::from_millis(WAIT_TIMEOUT_MS));
+	}
+
+	fn check_error(&self) -> Result<()> {
+		if self.stream.has_panicked() {
+			return Err(Error::Streaming);
+		}
+		match self.shared.error.load(Ordering::Acquire) {
+			0 => Ok(()),
+			x => Err((x as c_int).into()),
+		}
+	}
+}
+
+fn duration_from_secs(secs: f64) -> Duration {
+	if secs <= 0.0 {
+		return Duration::from_secs(0);
+	}
+	Duration::new(secs.trunc() as u64, (secs.fract() * 1e9) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::context::*;
+	use super::super::backend::*;
+
+	#[test]
+	fn write_and_drain() {
+		let mut ctx = Context::new();
+		ctx.connect_backend(Backend::Dummy).expect("Couldn't connect to backend");
+		ctx.flush_events();
+
+		let dev = ctx.default_output_device().expect("No output device");
+		let mut stream = dev.open_blocking_outstream::<f32>(48000, Format::Float32LE, ChannelLayout::get_default(2), 0.01, 1024)
+			.expect("Couldn't open stream");
+
+		// More than fits in the buffer, so this has to wait for the stream to play some of it.
+		let samples = vec![0.25f32; 2 * 4096];
+		stream.write(&samples).expect("Couldn't write");
+		stream.drain().expect("Couldn't drain");
+		assert_eq!(stream.buffered_frames(), 0);
+
+		match stream.write(&samples[..3]) {
+			Err(Error::Invalid) => {},
+			x => panic!("Expected Error::Invalid, got {:?}", x),
+		}
+	}
+}
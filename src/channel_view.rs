@@ -0,0 +1,305 @@
+use super::sample::*;
+
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::{Index, IndexMut};
+use std::slice;
+
+/// A read-only view of the samples of one channel in an `InStreamReader`, returned by
+/// `InStreamReader::channel()`.
+///
+/// The samples are accessed in place, without any conversion, so `T` is always the stream's
+/// native sample type. The samples of a channel are not necessarily contiguous (they usually
+/// are interleaved with the other channels), so this is not a slice, but it can be indexed and
+/// iterated like one.
+pub struct Channel<'b, T: 'b> {
+	ptr: *const u8,
+	step: usize,
+	len: usize,
+	phantom: PhantomData<&'b [T]>,
+}
+
+/// A mutable view of the samples of one channel in an `OutStreamWriter`, returned by
+/// `OutStreamWriter::channel_mut()`.
+///
+/// See `Channel` for details.
+pub struct ChannelMut<'b, T: 'b> {
+	ptr: *mut u8,
+	step: usize,
+	len: usize,
+	phantom: PhantomData<&'b mut [T]>,
+}
+
+/// An iterator over the samples of a `Channel`.
+pub struct ChannelIter<'b, T: 'b> {
+	ptr: *const u8,
+	step: usize,
+	remaining: usize,
+	phantom: PhantomData<&'b [T]>,
+}
+
+/// A mutable iterator over the samples of a `ChannelMut`.
+pub struct ChannelIterMut<'b, T: 'b> {
+	ptr: *mut u8,
+	step: usize,
+	remaining: usize,
+	phantom: PhantomData<&'b mut [T]>,
+}
+
+// Whether `ptr` and `step` are suitable for accessing `T`s in place.
+pub(crate) fn is_aligned_for<T>(ptr: *const u8, step: usize) -> bool {
+	let align = mem::align_of::<T>();
+	step >= mem::size_of::<T>() && (ptr as usize) % align == 0 && step % align == 0
+}
+
+impl<'b, T: NativeSample> Channel<'b, T> {
+	// The caller must ensure that `ptr` points to `len` samples of type `T`, `step` bytes apart
+	// and correctly aligned, which are valid for `'b`.
+	pub(crate) unsafe fn new(ptr: *const u8, step: usize, len: usize) -> Channel<'b, T> {
+		Channel {
+			ptr: ptr,
+			step: step,
+			len: len,
+			phantom: PhantomData,
+		}
+	}
+
+	/// The number of samples (frames) in the channel.
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	/// Whether there are no samples in the channel.
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	/// Get a sample, or `None` if `frame` is out of range.
+	pub fn get(&self, frame: usize) -> Option<&T> {
+		if frame < self.len {
+			unsafe { Some(&*(self.ptr.offset((frame * self.step) as isize) as *const T)) }
+		} else {
+			None
+		}
+	}
+
+	/// Iterate over the samples.
+	pub fn iter(&self) -> ChannelIter<'_, T> {
+		ChannelIter {
+			ptr: self.ptr,
+			step: self.step,
+			remaining: self.len,
+			phantom: PhantomData,
+		}
+	}
+
+	/// Get the samples as a slice. This is only possible if the samples are contiguous, which
+	/// is the case for mono streams and some backends' non-interleaved layouts. Otherwise it
+	/// returns `None`.
+	pub fn as_slice(&self) -> Option<&[T]> {
+		if self.step == mem::size_of::<T>() {
+			unsafe { Some(slice::from_raw_parts(self.ptr as *const T, self.len)) }
+		} else {
+			None
+		}
+	}
+}
+
+impl<'b, T: NativeSample> ChannelMut<'b, T> {
+	// The same requirements as `Channel::new()`, and the samples must not be aliased.
+	pub(crate) unsafe fn new(ptr: *mut u8, step: usize, len: usize) -> ChannelMut<'b, T> {
+		ChannelMut {
+			ptr: ptr,
+			step: step,
+			len: len,
+			phantom: PhantomData,
+		}
+	}
+
+	/// The number of samples (frames) in the channel.
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	/// Whether there are no samples in the channel.
+	pub fn is_empty(&self) -> bool {
+		self.len == 0
+	}
+
+	/// Get a sample, or `None` if `frame` is out of range.
+	pub fn get(&self, frame: usize) -> Option<&T> {
+		if frame < self.len {
+			unsafe { Some(&*(self.ptr.offset((frame * self.step) as isize) as *const T)) }
+		} else {
+			None
+		}
+	}
+
+	/// Get a mutable reference to a sample, or `None` if `frame` is out of range.
+	pub fn get_mut(&mut self, frame: usize) -> Option<&mut T> {
+		if frame < self.len {
+			unsafe { Some(&mut *(self.ptr.offset((frame * self.step) as isize) as *mut T)) }
+		} else {
+			None
+		}
+	}
+
+	/// Set a sample. Panics if `frame` is out of range.
+	pub fn set(&mut self, frame: usize, sample: T) {
+		*self.get_mut(frame).expect("Frame out of range") = sample;
+	}
+
+	/// Iterate over the samples.
+	pub fn iter(&self) -> ChannelIter<'_, T> {
+		ChannelIter {
+			ptr: self.ptr,
+			step: self.step,
+			remaining: self.len,
+			phantom: PhantomData,
+		}
+	}
+
+	/// Iterate mutably over the samples.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// fn write_callback(stream: &mut soundio::OutStreamWriter) {
+	///     let frame_count_max = stream.frame_count_max();
+	///     stream.begin_write(frame_count_max).unwrap();
+	///     for c in 0..stream.channel_count() {
+	///         let mut channel = stream.channel_mut::<f32>(c).unwrap();
+	///         for sample in channel.iter_mut() {
+	///             *sample = 0.0;
+	///         }
+	///     }
+	/// }
+	/// ```
+	pub fn iter_mut(&mut self) -> ChannelIterMut<'_, T> {
+		ChannelIterMut {
+			ptr: self.ptr,
+			step: self.step,
+			remaining: self.len,
+			phantom: PhantomData,
+		}
+	}
+
+	/// Get the samples as a slice if they are contiguous. See `Channel::as_slice()`.
+	pub fn as_slice(&self) -> Option<&[T]> {
+		if self.step == mem::size_of::<T>() {
+			unsafe { Some(slice::from_raw_parts(self.ptr as *const T, self.len)) }
+		} else {
+			None
+		}
+	}
+
+	/// Get the samples as a mutable slice if they are contiguous. See `Channel::as_slice()`.
+	pub fn as_mut_slice(&mut self) -> Option<&mut [T]> {
+		if self.step == mem::size_of::<T>() {
+			unsafe { Some(slice::from_raw_parts_mut(self.ptr as *mut T, self.len)) }
+		} else {
+			None
+		}
+	}
+}
+
+impl<'b, T: NativeSample> Index<usize> for Channel<'b, T> {
+	type Output = T;
+
+	fn index(&self, frame: usize) -> &T {
+		self.get(frame).expect("Frame out of range")
+	}
+}
+
+impl<'b, T: NativeSample> Index<usize> for ChannelMut<'b, T> {
+	type Output = T;
+
+	fn index(&self, frame: usize) -> &T {
+		self.get(frame).expect("Frame out of range")
+	}
+}
+
+impl<'b, T: NativeSample> IndexMut<usize> for ChannelMut<'b, T> {
+	fn index_mut(&mut self, frame: usize) -> &mut T {
+		self.get_mut(frame).expect("Frame out of range")
+	}
+}
+
+impl<'b, T: 'b> Iterator for ChannelIter<'b, T> {
+	type Item = &'b T;
+
+	fn next(&mut self) -> Option<&'b T> {
+		if self.remaining == 0 {
+			return None;
+		}
+		let item = unsafe { &*(self.ptr as *const T) };
+		self.remaining -= 1;
+		if self.remaining > 0 {
+			self.ptr = unsafe { self.ptr.offset(self.step as isize) };
+		}
+		Some(item)
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		(self.remaining, Some(self.remaining))
+	}
+}
+
+impl<'b, T: 'b> ExactSizeIterator for ChannelIter<'b, T> {}
+
+impl<'b, T: 'b> Iterator for ChannelIterMut<'b, T> {
+	type Item = &'b mut T;
+
+	fn next(&mut self) -> Option<&'b mut T> {
+		if self.remaining == 0 {
+			return None;
+		}
+		let item = unsafe { &mut *(self.ptr as *mut T) };
+		self.remaining -= 1;
+		if self.remaining > 0 {
+			self.ptr = unsafe { self.ptr.offset(self.step as isize) };
+		}
+		Some(item)
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		(self.remaining, Some(self.remaining))
+	}
+}
+
+impl<'b, T: 'b> ExactSizeIterator for ChannelIterMut<'b, T> {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn interleaved_view() {
+		// Two interleaved channels.
+		let mut buffer: [f32; 6] = [0.0, 1.0, 2.0, 3.0, 4.0, 5.0];
+		let step = 2 * mem::size_of::<f32>();
+		let ptr = buffer.as_mut_ptr() as *mut u8;
+
+		{
+			let mut right = unsafe { ChannelMut::<f32>::new(ptr.offset(4), step, 3) };
+			assert_eq!(right.len(), 3);
+			assert!(right.as_slice().is_none());
+			for s in right.iter_mut() {
+				*s *= 10.0;
+			}
+			right[0] = -1.0;
+			assert!(right.get(3).is_none());
+		}
+
+		let left = unsafe { Channel::<f32>::new(ptr, step, 3) };
+		assert_eq!(left.iter().cloned().collect::<Vec<f32>>(), vec![0.0, 2.0, 4.0]);
+		assert_eq!(buffer, [0.0, -1.0, 2.0, 30.0, 4.0, 50.0]);
+	}
+
+	#[test]
+	fn contiguous_view() {
+		let buffer: [i16; 4] = [1, 2, 3, 4];
+		let channel = unsafe { Channel::<i16>::new(buffer.as_ptr() as *const u8, 2, 4) };
+		assert_eq!(channel.as_slice(), Some(&buffer[..]));
+	}
+}
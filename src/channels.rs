@@ -17,6 +17,7 @@ use std::fmt;
 /// assert_eq!(format!("{}", ChannelId::MsMid), "Mid/Side Mid");
 /// ```
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ChannelId {
 	Invalid,
 
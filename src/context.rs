@@ -1,21 +1,47 @@
 extern crate libsoundio_sys as raw;
 
+use super::types::*;
 use super::device::*;
+use super::device_key::*;
+use super::device_query::*;
 use super::error::*;
 use super::backend::*;
+use super::ring_buffer::*;
+use super::messages::*;
+use super::context_lock::*;
+use super::device_watcher::*;
+use super::event_loop::*;
+#[cfg(feature = "async")]
+use super::async_events::*;
 
 use std::ptr;
 use std::os::raw::{c_int, c_char};
-use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicIsize, AtomicUsize, Ordering};
+use std::any::Any;
+use std::panic;
 
 /// `Context` represents the libsoundio library context.
 ///
 /// It must be created using `Context::new()` before most operations can be done and you
 /// generally will only have one context object per app.
 ///
-/// The underlying C struct is destroyed (and the backend is disconnected) when this object
-/// is dropped, which means that it must outlive all the Devices it creates. This is
-/// enforced via the lifetime system.
+/// The underlying C struct is reference counted. Each `Device` (and each stream opened on
+/// a `Device`) holds a reference to it, so it is only destroyed (and the backend disconnected)
+/// when this object and all of the `Device`s it created have been dropped. The lifetime `'a`
+/// is the lifetime of the callbacks, which must outlive all of those objects.
+///
+/// # Thread safety
+///
+/// `Context` is `Send` but not `Sync`: it can be moved to another thread, but only used from one
+/// thread at a time. Calls that change its state, such as `flush_events()` and `wait_events()`,
+/// take `&mut self`. The callbacks are called on whichever thread calls those functions, so they
+/// must be `Send`. Use a `ContextWaker` to wake it up from another thread.
+///
+/// `Device`s and streams can be used on other threads at the same time as the context, because
+/// libsoundio's reference counts, which they share, are only changed while holding a lock. A
+/// thread that needs the lock while another thread is blocked in `wait_events()` wakes it up, so
+/// expect spurious wakeups when devices or streams are dropped on other threads.
 ///
 /// # Examples
 ///
@@ -27,16 +53,151 @@ pub struct Context<'a> {
 	soundio: *mut raw::SoundIo,
 	/// The app name, used by some backends.
 	app_name: String,
+	/// The helper thread for `Context::wait_events_async()`, started the first time it is needed.
+	/// It must be dropped before `inner`, because it uses the lock.
+	#[cfg(feature = "async")]
+	wait_thread: Option<WaitThread>,
+	/// The shared library instance, which owns `soundio` and the callbacks.
+	pub(crate) inner: Arc<ContextInner<'a>>,
+}
+
+// This owns the soundio library instance and the callbacks that it calls. It is shared
+// between the `Context` and every `Device` so that the library instance is not destroyed
+// while any of them are alive.
+pub(crate) struct ContextInner<'a> {
+	soundio: *mut raw::SoundIo,
+	/// Held while changing reference counts. See `ContextLock`.
+	lock: ContextLock,
 	/// The optional callbacks. They are boxed so that we can take a raw pointer
 	/// to the heap object and give use it as `void* userdata`.
 	userdata: Box<ContextUserData<'a>>,
 }
 
+impl<'a> ContextInner<'a> {
+	/// Create a ring buffer. This is used by streams that only have a `Device`, not the `Context`.
+	pub(crate) fn ring_buffer<T: Copy>(&self, capacity: usize) -> RingBuffer<T> {
+		RingBuffer::new(self.soundio, capacity)
+	}
+
+	/// The backend that is connected, or `Backend::None`.
+	pub(crate) fn current_backend(&self) -> Backend {
+		unsafe { (*self.soundio).current_backend.into() }
+	}
+
+	/// Wake up `Context::wait_events()`. This can be called from any thread.
+	pub(crate) fn wakeup(&self) {
+		self.lock.wakeup();
+	}
+
+	/// Take the lock that must be held while changing libsoundio's reference counts, for example
+	/// when getting or dropping a device.
+	pub(crate) fn lock(&self) -> ContextGuard<'_> {
+		self.lock.lock()
+	}
+
+	/// Take the error from the last backend disconnect, if there has been one since this was
+	/// last called.
+	pub(crate) fn take_backend_disconnect(&self) -> Option<Error> {
+		match self.userdata.backend_disconnect_error.swap(0, Ordering::AcqRel) {
+			0 => None,
+			x => Some((x as c_int).into()),
+		}
+	}
+
+	/// Register a supervised stream, which recovers from backend disconnects itself. While there
+	/// are any the default backend disconnect callback doesn't panic.
+	pub(crate) fn add_supervised_stream(&self) {
+		self.userdata.supervised_streams.fetch_add(1, Ordering::Relaxed);
+	}
+
+	pub(crate) fn remove_supervised_stream(&self) {
+		self.userdata.supervised_streams.fetch_sub(1, Ordering::Relaxed);
+	}
+
+	/// The number of times the list of devices has changed. Streams that follow the default
+	/// device compare this to find out when to check it.
+	pub(crate) fn devices_generation(&self) -> usize {
+		self.userdata.devices_generation.load(Ordering::Acquire)
+	}
+
+	/// The lock, for `WaitEvents` to wait for events on another thread.
+	#[cfg(feature = "async")]
+	pub(crate) fn context_lock(&self) -> &ContextLock {
+		&self.lock
+	}
+
+	/// Send device changes and backend disconnects to a `ContextEvents`.
+	#[cfg(feature = "async")]
+	pub(crate) fn add_event_subscriber(&self, subscriber: EventSubscriber) {
+		if let Ok(mut subscribers) = self.userdata.event_subscribers.lock() {
+			subscribers.push(subscriber);
+		}
+	}
+}
+
+// libsoundio's state is only changed by the thread that holds `lock`, apart from `soundio_wakeup()`
+// which is thread-safe. The callbacks are `Send`, and are only called by the thread that holds the
+// lock, in `flush_events()` or `wait_events()`.
+unsafe impl<'a> Send for ContextInner<'a> {}
+unsafe impl<'a> Sync for ContextInner<'a> {}
+
+impl<'a> Drop for ContextInner<'a> {
+	fn drop(&mut self) {
+		unsafe {
+			// This also disconnects if necessary.
+			raw::soundio_destroy(self.soundio);
+		}
+	}
+}
+
 // The callbacks required for a context are stored in this object.
 pub struct ContextUserData<'a> {
-	backend_disconnect_callback: Option<Box<FnMut(Error) + 'a>>,
-	devices_change_callback: Option<Box<FnMut() + 'a>>,
-	events_signal_callback: Option<Box<FnMut() + 'a>>,
+	backend_disconnect_callback: Option<Box<FnMut(Error) + Send + 'a>>,
+	devices_change_callback: Option<Box<FnMut() + Send + 'a>>,
+	events_signal_callback: Option<Box<FnMut() + Send + 'a>>,
+
+	// The error from the last backend disconnect that hasn't been handled by a supervised
+	// stream, or 0. See `SupervisedOutStream`.
+	backend_disconnect_error: AtomicIsize,
+	// The number of supervised streams using this context.
+	supervised_streams: AtomicUsize,
+	// The number of times `on_devices_change` has been called.
+	devices_generation: AtomicUsize,
+	// Every `ContextEvents` using this context.
+	#[cfg(feature = "async")]
+	event_subscribers: Mutex<Vec<EventSubscriber>>,
+
+	// If a callback panics the payload is stored here, and the panic is resumed when the
+	// libsoundio function that called it returns. See `Context::resume_panic()`.
+	panic: Mutex<Option<Box<Any + Send>>>,
+}
+
+impl<'a> ContextUserData<'a> {
+	// Run a callback, catching any panic so that it doesn't unwind into libsoundio. This takes
+	// the `panic` field rather than `&self` so that the callback fields can be borrowed mutably.
+	fn call_with<F: FnOnce()>(panic_slot: &Mutex<Option<Box<Any + Send>>>, f: F) {
+		if let Err(payload) = panic::catch_unwind(panic::AssertUnwindSafe(f)) {
+			if let Ok(mut stored) = panic_slot.lock() {
+				// Only the first panic is kept.
+				if stored.is_none() {
+					*stored = Some(payload);
+				}
+			}
+		}
+	}
+
+	// Send an event to every `ContextEvents`, forgetting the ones that have been dropped. Returns
+	// false if there aren't any.
+	#[cfg(feature = "async")]
+	fn send_event(&self, event: Event) -> bool {
+		match self.event_subscribers.lock() {
+			Ok(mut subscribers) => {
+				subscribers.retain(|subscriber| subscriber.send(event));
+				!subscribers.is_empty()
+			},
+			Err(_) => false,
+		}
+	}
 }
 
 // See `Context::new_with_callbacks()`.
@@ -47,12 +208,30 @@ extern fn on_backend_disconnect(sio: *mut raw::SoundIo, err: c_int) {
 	let raw_userdata_pointer = unsafe { (*sio).userdata as *mut ContextUserData };
 	let userdata = unsafe { &mut (*raw_userdata_pointer) };
 
-	if let Some(ref mut cb) = userdata.backend_disconnect_callback {
-		cb(err);
-	} else {
-		// Hmm I decided to replicate the libsoundio behaviour.
-		panic!("Backend disconnected: {}", err);
-	}
+	// Supervised streams check this to find out that they need to reconnect.
+	userdata.backend_disconnect_error.store(c_int::from(err) as isize, Ordering::Release);
+	#[cfg(feature = "async")]
+	let subscribed = userdata.send_event(Event::BackendDisconnected(err));
+	#[cfg(not(feature = "async"))]
+	let subscribed = false;
+	let supervised = userdata.supervised_streams.load(Ordering::Relaxed) > 0 || subscribed;
+
+	let panic_slot = &userdata.panic;
+	let callback = &mut userdata.backend_disconnect_callback;
+	ContextUserData::call_with(panic_slot, || {
+		if let Some(ref mut cb) = *callback {
+			cb(err);
+		} else if supervised {
+			// The supervised streams reconnect the next time they are supervised, and a
+			// `ContextEvents` has reported it.
+			#[cfg(feature = "log")]
+			warn!("soundio: backend disconnected: {}", err);
+		} else {
+			// Hmm I decided to replicate the libsoundio behaviour. The panic is
+			// resumed in `Context::flush_events()` or `Context::wait_events()`.
+			panic!("Backend disconnected: {}", err);
+		}
+	});
 }
 
 // See `Context::new_with_callbacks()`.
@@ -61,11 +240,21 @@ extern fn on_devices_change(sio: *mut raw::SoundIo) {
 	let raw_userdata_pointer = unsafe { (*sio).userdata as *mut ContextUserData };
 	let userdata = unsafe { &mut (*raw_userdata_pointer) };
 
-	if let Some(ref mut cb) = userdata.devices_change_callback {
-		cb();
-	} else {
-		println!("Devices changed");
-	}
+	userdata.devices_generation.fetch_add(1, Ordering::AcqRel);
+	#[cfg(feature = "async")]
+	userdata.send_event(Event::DevicesChanged);
+
+	let panic_slot = &userdata.panic;
+	let callback = &mut userdata.devices_change_callback;
+	ContextUserData::call_with(panic_slot, || {
+		if let Some(ref mut cb) = *callback {
+			cb();
+		} else {
+			// This is called from `flush_events()` or `wait_events()`, not the audio thread.
+			#[cfg(feature = "log")]
+			debug!("soundio: devices changed");
+		}
+	});
 }
 
 // See `Context::new_with_callbacks()`.
@@ -74,25 +263,12 @@ extern fn on_events_signal(sio: *mut raw::SoundIo) {
 	let raw_userdata_pointer = unsafe { (*sio).userdata as *mut ContextUserData };
 	let userdata = unsafe { &mut (*raw_userdata_pointer) };
 
+	let panic_slot = &userdata.panic;
 	if let Some(ref mut cb) = userdata.events_signal_callback {
-		cb();
+		ContextUserData::call_with(panic_slot, || cb());
 	}
 }
 
-/// Optional real time priority warning callback.
-///
-/// This callback is fired when making thread real-time priority failed. The default
-/// callback action prints a message instructing the user how to configure their
-/// system to allow real-time priority threads. The message is printed to stderr
-/// and is only printed once.
-///
-/// Because the callback doesn't take any `void* userdata` parameters
-/// I can't see an easy way to integrate it into the Rust API, so I have chosen
-/// to just use the default function. In otherwords the callback here is not used.
-#[allow(dead_code)]
-extern fn emit_rtprio_warning() {
-}
-
 impl<'a> Context<'a> {
 
 	/// Create a new libsoundio context.
@@ -117,11 +293,23 @@ impl<'a> Context<'a> {
 			soundio: soundio,
 			// The default name in libsoundio is "SoundIo". We replicate that here for `Context::app_name()`.
 			app_name: "SoundIo".to_string(),
-			userdata: Box::new( ContextUserData {
-				backend_disconnect_callback: None,
-				devices_change_callback: None,
-				events_signal_callback: None,
-			})
+			#[cfg(feature = "async")]
+			wait_thread: None,
+			inner: Arc::new( ContextInner {
+				soundio: soundio,
+				lock: ContextLock::new(soundio),
+				userdata: Box::new( ContextUserData {
+					backend_disconnect_callback: None,
+					devices_change_callback: None,
+					events_signal_callback: None,
+					backend_disconnect_error: AtomicIsize::new(0),
+					supervised_streams: AtomicUsize::new(0),
+					devices_generation: AtomicUsize::new(0),
+					#[cfg(feature = "async")]
+					event_subscribers: Mutex::new(Vec::new()),
+					panic: Mutex::new(None),
+				}),
+			}),
 		};
 
 		// Note that libsoundio's default on_backend_disconnect() handler panics!
@@ -133,11 +321,13 @@ impl<'a> Context<'a> {
 			(*context.soundio).on_events_signal = Some(on_events_signal);
 			// (*context.soundio).app_name is already set by default to point to a static C string "SoundIo".
 
-			// This callback is not used - see its documentation for more information.
-			// (*context.soundio).emit_rtprio_warning = emit_rtprio_warning as *mut _;
+			// The real-time priority warning and JACK messages go to the process-wide message
+			// handler. See `set_message_handler()`.
+			install_message_callbacks(context.soundio);
 
 			// Save a reference here so that we can have user-defined callbacks.
-			(*context.soundio).userdata = context.userdata.as_mut() as *mut ContextUserData as *mut _;
+			let inner = Arc::get_mut(&mut context.inner).unwrap();
+			(*context.soundio).userdata = inner.userdata.as_mut() as *mut ContextUserData as *mut _;
 		}
 		context
 	}
@@ -154,7 +344,10 @@ impl<'a> Context<'a> {
 	/// `Error::BackendDisconnected`. This callback is only called during a
 	/// call to `Context::flush_events()` or `Context::wait_events()`.
 	/// If you do not supply a callback, the default will panic
-	/// with an error message. This callback is also called when the thread
+	/// with an error message (the panic is caught and resumed when
+	/// `flush_events()` or `wait_events()` returns), unless a `SupervisedOutStream`
+	/// or `SupervisedInStream` is using the context, in which case it reconnects
+	/// instead. This callback is also called when the thread
 	/// that retrieves device information runs into an unrecoverable condition
 	/// such as running out of memory.
 	///
@@ -168,8 +361,8 @@ impl<'a> Context<'a> {
 	/// ## `devices_change_callback`
 	///
 	/// This is called when the list of devices change. It is only called during a call
-	/// to `Context::flush_events()` or `Context::`wait_events()`. The default behaviour is
-	/// to print "Devices changed" to the console, which you can disable by using an empty callback.
+	/// to `Context::flush_events()` or `Context::wait_events()`. By default nothing is done, except
+	/// that the change is logged at debug level if the `log` feature is enabled.
 	///
 	/// ## `events_signal_callback`
 	///
@@ -194,21 +387,26 @@ impl<'a> Context<'a> {
 				events_signal_callback: Option<EventsSignalCB>,
 			) -> Context<'a>
 		where
-			BackendDisconnectCB: 'a + FnMut(Error),
-			DevicesChangeCB: 'a + FnMut(),
-			EventsSignalCB: 'a + FnMut() {
+			BackendDisconnectCB: 'a + FnMut(Error) + Send,
+			DevicesChangeCB: 'a + FnMut() + Send,
+			EventsSignalCB: 'a + FnMut() + Send {
 		// A set of function like `fn set_XX_callback(&mut self, ...` might be a nicer interface
 		// but I am unsure about the safety implications.
 		let mut context = Context::new();
 
-		if let Some(cb) = backend_disconnect_callback {
-			context.userdata.backend_disconnect_callback = Some(Box::new(cb));
-		}
-		if let Some(cb) = devices_change_callback {
-			context.userdata.devices_change_callback = Some(Box::new(cb));
-		}
-		if let Some(cb) = events_signal_callback {
-			context.userdata.events_signal_callback = Some(Box::new(cb));
+		{
+			// Nothing else can have a reference to the new context yet.
+			let userdata = &mut Arc::get_mut(&mut context.inner).unwrap().userdata;
+
+			if let Some(cb) = backend_disconnect_callback {
+				userdata.backend_disconnect_callback = Some(Box::new(cb));
+			}
+			if let Some(cb) = devices_change_callback {
+				userdata.devices_change_callback = Some(Box::new(cb));
+			}
+			if let Some(cb) = events_signal_callback {
+				userdata.events_signal_callback = Some(Box::new(cb));
+			}
 		}
 
 		context
@@ -260,6 +458,7 @@ impl<'a> Context<'a> {
 	/// }
 	/// ```
 	pub fn connect(&mut self) -> Result<()> {
+		let _guard = self.inner.lock();
 		let ret = unsafe { raw::soundio_connect(self.soundio) };
 		match ret {
 			0 => Ok(()),
@@ -290,6 +489,7 @@ impl<'a> Context<'a> {
 	/// }
 	/// ```
 	pub fn connect_backend(&mut self, backend: Backend) -> Result<()> {
+		let _guard = self.inner.lock();
 		let ret = unsafe { raw::soundio_connect_backend(self.soundio, backend.into()) };
 		match ret {
 			0 => Ok(()),
@@ -312,6 +512,7 @@ impl<'a> Context<'a> {
 	/// ctx.disconnect();
 	/// ```
 	pub fn disconnect(&mut self) {
+		let _guard = self.inner.lock();
 		unsafe {
 			raw::soundio_disconnect(self.soundio);
 		}
@@ -372,32 +573,114 @@ impl<'a> Context<'a> {
 	///
 	/// Note that if you do not care about learning about updated devices, you
 	/// can call this function only once ever and never call `Context::wait_events()`.
-	pub fn flush_events(&self) {
-		unsafe {
-			raw::soundio_flush_events(self.soundio);
+	///
+	/// If one of the callbacks panics, the panic is caught so that it doesn't unwind
+	/// through libsoundio, and then resumed when this function returns.
+	pub fn flush_events(&mut self) {
+		{
+			let _guard = self.inner.lock();
+			unsafe {
+				raw::soundio_flush_events(self.soundio);
+			}
 		}
+		self.resume_panic();
 	}
 
 	/// This function calls `Context::flush_events()` then blocks until another event
-	/// is ready or you call `wakeup`. Be ready for spurious wakeups.
-	pub fn wait_events(&self) {
-		unsafe {
-			raw::soundio_wait_events(self.soundio);
-		}
+	/// is ready or you call `ContextWaker::wakeup()`. Be ready for spurious wakeups.
+	///
+	/// Panics in the callbacks are handled in the same way as `Context::flush_events()`.
+	pub fn wait_events(&mut self) {
+		self.inner.lock.wait_events();
+		self.resume_panic();
+	}
+
+	/// Return a future that does the same as `Context::wait_events()`, but without blocking the
+	/// executor. It calls `wait_events()` on a helper thread, so the callbacks are called on that
+	/// thread. See `WaitEvents`.
+	///
+	/// This requires the `async` feature.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// extern crate futures;
+	/// extern crate soundio;
+	///
+	/// # fn main() {
+	/// let mut ctx = soundio::Context::new();
+	/// ctx.connect_backend(soundio::Backend::Dummy).expect("Couldn't connect to backend");
+	/// ctx.flush_events();
+	///
+	/// // Wait for events, or until another future completes.
+	/// let wait = ctx.wait_events_async();
+	/// let other = futures::future::ready(());
+	/// futures::executor::block_on(futures::future::select(wait, other));
+	/// # }
+	/// ```
+	#[cfg(feature = "async")]
+	pub fn wait_events_async<'c>(&'c mut self) -> WaitEvents<'c, 'a> {
+		WaitEvents::new(self)
 	}
 
-	/// Wake up any other threads currently blocking in `Context::wait_events()`.
+	/// Return a `Stream` of device changes and backend disconnects, which waits for them on a
+	/// helper thread. See `ContextEvents`.
 	///
-	/// In order to enable this functionality, `Context` implements the `Send` and
-	/// `Sync` traits despite the fact that not all functions can be called from all threads.
-	/// Be careful.
+	/// This requires the `async` feature.
+	#[cfg(feature = "async")]
+	pub fn events<'c>(&'c mut self) -> ContextEvents<'c, 'a> {
+		ContextEvents::new(self)
+	}
+
+	// The helper thread that `WaitEvents` waits for events on, starting it if necessary.
+	#[cfg(feature = "async")]
+	pub(crate) fn wait_thread(&mut self) -> &WaitThread {
+		if self.wait_thread.is_none() {
+			self.wait_thread = Some(WaitThread::spawn(&self.inner));
+		}
+		self.wait_thread.as_ref().unwrap()
+	}
+
+	// If a callback panicked, continue the panic on this thread.
+	pub(crate) fn resume_panic(&self) {
+		let payload = match self.inner.userdata.panic.lock() {
+			Ok(mut payload) => payload.take(),
+			Err(_) => None,
+		};
+		if let Some(payload) = payload {
+			panic::resume_unwind(payload);
+		}
+	}
+
+	/// Wake up `Context::wait_events()` if it is blocking. Since `wait_events()` borrows the
+	/// context mutably this is only useful from a callback; use `Context::waker()` to wake it up
+	/// from another thread.
 	pub fn wakeup(&self) {
-		
-		// To do this properly it might be necessary to split Context into multiple objects, one for
-		// each thread, or maybe one that is Send/Sync and another that isn't. That is rather
-		// complicated however and there is probably a better way.
-		unsafe {
-			raw::soundio_wakeup(self.soundio);
+		self.inner.wakeup();
+	}
+
+	/// Return a `ContextWaker`, which can wake up `Context::wait_events()` from another thread.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// let mut ctx = soundio::Context::new();
+	/// ctx.connect_backend(soundio::Backend::Dummy).expect("Couldn't connect to backend");
+	/// ctx.flush_events();
+	///
+	/// let waker = ctx.waker();
+	/// std::thread::spawn(move || {
+	///     // Wake up the main thread every 100 ms.
+	///     loop {
+	///         std::thread::sleep(std::time::Duration::from_millis(100));
+	///         waker.wakeup();
+	///     }
+	/// });
+	/// ctx.wait_events();
+	/// ```
+	pub fn waker(&self) -> ContextWaker<'a> {
+		ContextWaker {
+			context: self.inner.clone(),
 		}
 	}
 
@@ -421,9 +704,11 @@ impl<'a> Context<'a> {
 
 	/// Use this function to retrieve an input device given its index. Before getting devices
 	/// you must call `Context::flush_events()` at least once, otherwise this will return
-	/// an error. It will also return an error if there is a probe error while opening the
-	/// device or the index is out of bounds (use `Context::input_device_count()` to learn)
-	/// how many input devices there are.
+	/// an error. It will also return an error if the index is out of bounds (use
+	/// `Context::input_device_count()` to learn how many input devices there are).
+	///
+	/// The device is returned even if the backend failed to probe it, so that it can be listed.
+	/// Check `Device::probe_error()` before using its capabilities.
 	///
 	/// It is probably more convenient to use the `Context::input_devices()` function instead
 	/// of this one unless you have some very specific requirements.
@@ -439,29 +724,26 @@ impl<'a> Context<'a> {
 	///     println!("Device {} is called {}", i, dev.name());
 	/// }
 	/// ```
-	pub fn input_device(&self, index: usize) -> Result<Device> {
+	pub fn input_device(&self, index: usize) -> Result<Device<'a>> {
+		let _guard = self.inner.lock();
 		let device = unsafe { raw::soundio_get_input_device(self.soundio, index as c_int) };
 		if device == ptr::null_mut() {
 			return Err(Error::OpeningDevice);
 		}
 
-		let probe_error = unsafe { (*device).probe_error };
-
-		if probe_error != 0 {
-			return Err(probe_error.into());
-		}
-
 		Ok(Device {
 			device: device,
-			phantom: PhantomData,
+			context: self.inner.clone(),
 		})
 	}
 
 	/// Use this function to retrieve an output device given its index. Before getting devices
 	/// you must call `Context::flush_events()` at least once, otherwise this will return
-	/// an error. It will also return an error if there is a probe error while opening the
-	/// device or the index is out of bounds (use `Context::output_device_count()` to learn)
-	/// how many output devices there are.
+	/// an error. It will also return an error if the index is out of bounds (use
+	/// `Context::output_device_count()` to learn how many output devices there are).
+	///
+	/// The device is returned even if the backend failed to probe it, so that it can be listed.
+	/// Check `Device::probe_error()` before using its capabilities.
 	///
 	/// It is probably more convenient to use the `Context::output_devices()` function instead
 	/// of this one unless you have some very specific requirements.
@@ -477,21 +759,16 @@ impl<'a> Context<'a> {
 	///     println!("Device {} is called {}", i, dev.name());
 	/// }
 	/// ```
-	pub fn output_device(&self, index: usize) -> Result<Device> {
+	pub fn output_device(&self, index: usize) -> Result<Device<'a>> {
+		let _guard = self.inner.lock();
 		let device = unsafe { raw::soundio_get_output_device(self.soundio, index as c_int) };
 		if device == ptr::null_mut() {
 			return Err(Error::OpeningDevice);
 		}
 
-		let probe_error = unsafe { (*device).probe_error };
-
-		if probe_error != 0 {
-			return Err(probe_error.into());
-		}
-
 		Ok(Device {
 			device: device,
-			phantom: PhantomData,
+			context: self.inner.clone(),
 		})
 	}
 
@@ -572,7 +849,8 @@ impl<'a> Context<'a> {
 	/// Get all the input devices as a vector. You *must* call `Context::flush_events()`
 	/// at least once before calling this function. If you don't it will panic.
 	/// 
-	/// It returns an error if there is an error opening any of the devices.
+	/// It returns an error if there is an error opening any of the devices. Devices that the
+	/// backend failed to probe are included; see `Device::probe_error()`.
 	///
 	/// # Examples
 	///
@@ -585,7 +863,7 @@ impl<'a> Context<'a> {
 	///     println!("Device {} ", dev.name());
 	/// }
 	/// ```
-	pub fn input_devices(&self) -> Result<Vec<Device>> {
+	pub fn input_devices(&self) -> Result<Vec<Device<'a>>> {
 		let count = self.input_device_count();
 		let mut devices = Vec::new();
 		for i in 0..count {
@@ -597,7 +875,8 @@ impl<'a> Context<'a> {
 	/// Get all the output devices as a vector. You *must* call `Context::flush_events()`
 	/// at least once before calling this function. If you don't it will panic.
 	/// 
-	/// It returns an error if there is an error opening any of the devices.
+	/// It returns an error if there is an error opening any of the devices. Devices that the
+	/// backend failed to probe are included; see `Device::probe_error()`.
 	///
 	/// # Examples
 	///
@@ -610,7 +889,7 @@ impl<'a> Context<'a> {
 	///     println!("Device {} ", dev.name());
 	/// }
 	/// ```
-	pub fn output_devices(&self) -> Result<Vec<Device>> {
+	pub fn output_devices(&self) -> Result<Vec<Device<'a>>> {
 		let count = self.output_device_count();
 		let mut devices = Vec::new();
 		for i in 0..count {
@@ -623,7 +902,7 @@ impl<'a> Context<'a> {
 	/// at least once before calling this function. If you don't it will panic.
 	///
 	/// If there are no devices it returns `Error::NoSuchDevice`. If there was
-	/// an error opening the device it returns that error.
+	/// an error opening or probing the device it returns that error.
 	///
 	/// # Examples
 	///
@@ -634,19 +913,23 @@ impl<'a> Context<'a> {
 	/// let dev = ctx.default_input_device().expect("No default device");
 	/// println!("The default input device is {}", dev.name());
 	/// ```
-	pub fn default_input_device(&self) -> Result<Device> {
+	pub fn default_input_device(&self) -> Result<Device<'a>> {
 		let index = match self.default_input_device_index() {
 			Some(x) => x,
 			None => return Err(Error::NoSuchDevice),
 		};
-		self.input_device(index)
+		let device = self.input_device(index)?;
+		match device.probe_error() {
+			Some(e) => Err(e),
+			None => Ok(device),
+		}
 	}
 	
 	/// Get the default output device. You *must* call `Context::flush_events()`
 	/// at least once before calling this function. If you don't it will panic.
 	///
 	/// If there are no devices it returns `Error::NoSuchDevice`. If there was
-	/// an error opening the device it returns that error.
+	/// an error opening or probing the device it returns that error.
 	///
 	/// # Examples
 	///
@@ -657,28 +940,160 @@ impl<'a> Context<'a> {
 	/// let dev = ctx.default_output_device().expect("No default device");
 	/// println!("The default output device is {}", dev.name());
 	/// ```
-	pub fn default_output_device(&self) -> Result<Device> {
+	pub fn default_output_device(&self) -> Result<Device<'a>> {
 		let index = match self.default_output_device_index() {
 			Some(x) => x,
 			None => return Err(Error::NoSuchDevice),
 		};
-		self.output_device(index)
+		let device = self.output_device(index)?;
+		match device.probe_error() {
+			Some(e) => Err(e),
+			None => Ok(device),
+		}
+	}
+
+	/// Return a `DeviceQuery` which finds the input and output devices that meet some
+	/// requirements, ranked by how well they fit them. See `DeviceQuery`.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// let mut ctx = soundio::Context::new();
+	/// ctx.connect_backend(soundio::Backend::Dummy).expect("Couldn't connect to backend");
+	/// ctx.flush_events();
+	/// for dev in ctx.devices().output().min_channels(2).list().expect("Couldn't list devices") {
+	///     println!("{}", dev.name());
+	/// }
+	/// ```
+	pub fn devices<'c>(&'c self) -> DeviceQuery<'c, 'a> {
+		DeviceQuery::new(self)
+	}
+
+	/// Find the device that a `DeviceKey` refers to, for example one that was saved in a config
+	/// file. You *must* call `Context::flush_events()` at least once before calling this
+	/// function. If you don't it will panic.
+	///
+	/// It returns the device with the key's id, aim and raw flag. If there isn't one, because the
+	/// backend gave the device a new id, it returns the device with the same aim and raw flag whose
+	/// name is most similar to the key's, if any is similar enough. Differences in case and
+	/// punctuation are ignored. The device is returned even if probing it failed; see
+	/// `Device::probe_error()`.
+	///
+	/// # Errors
+	///
+	/// * `Error::NoSuchDevice` - no device matches the key.
+	/// * The errors from `Context::input_devices()` and `Context::output_devices()`.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// let mut ctx = soundio::Context::new();
+	/// ctx.connect_backend(soundio::Backend::Dummy).expect("Couldn't connect to backend");
+	/// ctx.flush_events();
+	///
+	/// let key = soundio::DeviceKey {
+	///     id: "an id that has changed".to_string(),
+	///     name: "dummy output device".to_string(),
+	///     aim: soundio::DeviceAim::Output,
+	///     is_raw: false,
+	/// };
+	/// match ctx.find_device(&key) {
+	///     Ok(dev) => println!("Found {}", dev.name()),
+	///     Err(e) => println!("Couldn't find {}: {}", key.name, e),
+	/// }
+	/// ```
+	pub fn find_device(&self, key: &DeviceKey) -> Result<Device<'a>> {
+		let devices = match key.aim {
+			DeviceAim::Input => self.input_devices()?,
+			DeviceAim::Output => self.output_devices()?,
+		};
+		key.best_match(devices).ok_or(Error::NoSuchDevice)
+	}
+
+	/// Create a new `RingBuffer` that can hold at least `capacity` elements of type `T`.
+	/// See `RingBuffer` for more information.
+	///
+	/// This panics if `capacity` is 0, if `T` is zero-sized, or if libsoundio fails
+	/// to allocate the buffer (which only happens when out of memory).
+	///
+	/// # Examples
+	///
+	/// ```
+	/// let ctx = soundio::Context::new();
+	/// let ring_buffer = ctx.ring_buffer::<i16>(4096);
+	/// assert!(ring_buffer.capacity() >= 4096);
+	/// ```
+	pub fn ring_buffer<T: Copy>(&self, capacity: usize) -> RingBuffer<T> {
+		RingBuffer::new(self.soundio, capacity)
+	}
+
+	/// Create a `DeviceWatcher`, which reports devices being added and removed and the default
+	/// devices changing. You *must* call `Context::flush_events()` at least once before calling
+	/// this function. If you don't it will panic.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// let mut ctx = soundio::Context::new();
+	/// ctx.connect_backend(soundio::Backend::Dummy).expect("Couldn't connect to backend");
+	/// ctx.flush_events();
+	/// let watcher = ctx.watch_devices();
+	/// for dev in watcher.devices() {
+	///     println!("{} ({:?})", dev.name, dev.aim);
+	/// }
+	/// ```
+	pub fn watch_devices(&self) -> DeviceWatcher<'a> {
+		DeviceWatcher::new(self)
 	}
 }
 
-impl<'a> Drop for Context<'a> {
-	fn drop(&mut self) {
-		unsafe {
-			// This also disconnects if necessary.
-			raw::soundio_destroy(self.soundio);
-		}
+impl Context<'static> {
+	/// Move the context to a new thread that runs its event loop, calling `wait_events()`
+	/// repeatedly, and return a handle that can stop it. The device change and backend
+	/// disconnect callbacks are called on that thread, followed by `handler`. See `EventLoop`.
+	///
+	/// Only contexts whose callbacks are `'static` can be moved to another thread.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// let mut ctx = soundio::Context::new();
+	/// ctx.connect_backend(soundio::Backend::Dummy).expect("Couldn't connect to backend");
+	/// let mut watcher = None;
+	/// let event_loop = ctx.spawn_event_loop(move |ctx| {
+	///     let watcher = watcher.get_or_insert_with(|| ctx.watch_devices());
+	///     for event in watcher.update(ctx).expect("Couldn't update") {
+	///         println!("{:?}", event);
+	///     }
+	/// });
+	/// ```
+	pub fn spawn_event_loop<F>(self, handler: F) -> EventLoop
+		where F: FnMut(&mut Context<'static>) + Send + 'static {
+		EventLoop::spawn(self, handler)
 	}
 }
 
-// This allows wakeup and wait_events to be called from other threads.
-// TODO: Find out exactly the thread-safety properties of libsoundio.
+// The callbacks are `Send`, and the library instance is only used by the thread that owns the
+// context, apart from changes to reference counts which are made while holding the lock. It isn't
+// `Sync` because libsoundio's device list is replaced by `flush_events()` without any locking.
 unsafe impl<'a> Send for Context<'a> {}
-unsafe impl<'a> Sync for Context<'a> {}
+
+/// `ContextWaker` wakes up a `Context` that is blocking in `Context::wait_events()`. It is
+/// obtained using `Context::waker()`, and can be cloned and used on any thread.
+///
+/// If the context isn't waiting for events at the time, `wakeup()` has no effect. It keeps the
+/// underlying library instance alive, but not the backend connection.
+#[derive(Clone)]
+pub struct ContextWaker<'a> {
+	context: Arc<ContextInner<'a>>,
+}
+
+impl<'a> ContextWaker<'a> {
+	/// Wake up `Context::wait_events()` if it is blocking.
+	pub fn wakeup(&self) {
+		self.context.wakeup();
+	}
+}
 
 #[cfg(test)]
 mod tests {
@@ -700,5 +1115,43 @@ mod tests {
 		println!("Available backends: {:?}", ctx.available_backends());
 	}
 
+	#[test]
+	fn multiple_streams_per_device() {
+		use super::super::format::*;
+		use super::super::layout::*;
+		use super::super::outstream::*;
+
+		let mut ctx = Context::new();
+		ctx.connect_backend(Backend::Dummy).expect("Couldn't connect to backend");
+		ctx.flush_events();
+
+		let streams = {
+			// The streams keep their own reference to the device, so it can be dropped.
+			let dev = ctx.default_output_device().expect("No output device");
+			let a = dev.open_outstream(48000, Format::Float32LE, ChannelLayout::get_default(2), 0.1,
+				|_: &mut OutStreamWriter| {}, None::<fn()>, None::<fn(Error)>).expect("Couldn't open first stream");
+			let b = dev.open_outstream(48000, Format::Float32LE, ChannelLayout::get_default(2), 0.1,
+				|_: &mut OutStreamWriter| {}, None::<fn()>, None::<fn(Error)>).expect("Couldn't open second stream");
+			vec![a, b]
+		};
+
+		for stream in &streams {
+			assert_eq!(stream.device().aim(), super::super::types::DeviceAim::Output);
+		}
+	}
+
+	#[test]
+	fn dummy_devices_are_probed() {
+		let mut ctx = Context::new();
+		ctx.connect_backend(Backend::Dummy).expect("Couldn't connect to backend");
+		ctx.flush_events();
+
+		for dev in ctx.output_devices().expect("Couldn't get output devices") {
+			assert!(dev.probe_error().is_none());
+			assert!(!dev.formats().expect("Probing failed").is_empty());
+			assert!(!dev.sample_rates().expect("Probing failed").is_empty());
+		}
+	}
+
 	// TODO: More tests.
 }
\ No newline at end of file
@@ -0,0 +1,103 @@
+extern crate libsoundio_sys as raw;
+
+use std::sync::{Mutex, MutexGuard, Condvar};
+use std::thread::{self, ThreadId};
+use std::time::Duration;
+
+// libsoundio doesn't synchronise anything except `soundio_wakeup()`. In particular device reference
+// counts aren't atomic, and `soundio_flush_events()` and `soundio_wait_events()` change them when
+// they replace the list of devices. So every call that changes a reference count, from any
+// thread, is made while holding this lock. That includes getting, cloning and dropping a
+// `Device`, creating and destroying streams, connecting and disconnecting, and waiting for
+// events.
+//
+// The lock is reentrant because the context's callbacks, which are called while it is held, may
+// drop devices and streams. While the thread that holds it is blocked in `wait_events()` other
+// threads that want it wake it up.
+pub(crate) struct ContextLock {
+	soundio: *mut raw::SoundIo,
+	state: Mutex<LockState>,
+	released: Condvar,
+}
+
+// The lock only uses `soundio` to call `soundio_wakeup()`, while `state` says that a thread is
+// blocked in `soundio_wait_events()`, so the backend can't be disconnected at the time.
+unsafe impl Send for ContextLock {}
+unsafe impl Sync for ContextLock {}
+
+#[derive(Default)]
+struct LockState {
+	owner: Option<ThreadId>,
+	count: usize,
+	// Whether the owner is blocked in `soundio_wait_events()`.
+	waiting: bool,
+}
+
+pub(crate) struct ContextGuard<'l> {
+	lock: &'l ContextLock,
+}
+
+impl ContextLock {
+	pub fn new(soundio: *mut raw::SoundIo) -> ContextLock {
+		ContextLock {
+			soundio: soundio,
+			state: Mutex::new(LockState::default()),
+			released: Condvar::new(),
+		}
+	}
+
+	fn state(&self) -> MutexGuard<'_, LockState> {
+		match self.state.lock() {
+			Ok(state) => state,
+			Err(poisoned) => poisoned.into_inner(),
+		}
+	}
+
+	pub fn lock(&self) -> ContextGuard<'_> {
+		let current = thread::current().id();
+		let mut state = self.state();
+		loop {
+			if state.owner.is_none() || state.owner == Some(current) {
+				state.owner = Some(current);
+				state.count += 1;
+				return ContextGuard { lock: self };
+			}
+			if state.waiting {
+				unsafe { raw::soundio_wakeup(self.soundio); }
+			}
+			// A wakeup is lost if the owner isn't waiting yet, so check again shortly.
+			state = match self.released.wait_timeout(state, Duration::from_millis(1)) {
+				Ok((state, _)) => state,
+				Err(poisoned) => poisoned.into_inner().0,
+			};
+		}
+	}
+
+	// Wake up `soundio_wait_events()`, if a thread is blocked in it. This can be called from any
+	// thread.
+	pub fn wakeup(&self) {
+		let state = self.state();
+		if state.waiting {
+			unsafe { raw::soundio_wakeup(self.soundio); }
+		}
+	}
+
+	// Call `soundio_wait_events()` while holding the lock.
+	pub fn wait_events(&self) {
+		let _guard = self.lock();
+		self.state().waiting = true;
+		unsafe { raw::soundio_wait_events(self.soundio); }
+		self.state().waiting = false;
+	}
+}
+
+impl<'l> Drop for ContextGuard<'l> {
+	fn drop(&mut self) {
+		let mut state = self.lock.state();
+		state.count -= 1;
+		if state.count == 0 {
+			state.owner = None;
+			self.lock.released.notify_all();
+		}
+	}
+}
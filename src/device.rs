@@ -7,23 +7,64 @@ use super::instream::*;
 use super::error::*;
 use super::layout::*;
 use super::format::*;
+use super::context::*;
+use super::panic_policy::*;
+use super::sample::*;
+use super::blocking_outstream::*;
+use super::blocking_instream::*;
+use super::stream_builder::*;
+use super::stream_event::*;
+use super::device_key::*;
+use super::device_info::*;
 
 use std::ptr;
 use std::os::raw::c_int;
 use std::marker::PhantomData;
 use std::slice;
+use std::sync::Arc;
+use std::ffi::CString;
+use std::hash::{Hash, Hasher};
 
 /// Device represents an input or output device.
 ///
 /// It is obtained from a `Context` using `Context::input_device()` or `Context::output_device()`.
 /// You can use it to open an input stream or output stream. 
+///
+/// `Device` is reference counted, so cloning it is cheap. Each `Device` keeps the libsoundio
+/// context it came from alive, and each stream keeps its own `Device`, so you can open as many
+/// streams on a device as you like and store them alongside the `Context`.
+///
+/// `Device` is `Send` and `Sync`. The reference count is only changed while holding the context's
+/// lock, so cloning or dropping a `Device` on another thread may briefly wake up
+/// `Context::wait_events()`. See `Context`.
+///
+/// Two `Device`s are equal if they have the same `id()`, `aim()` and `is_raw()`, even if they were
+/// retrieved after different calls to `Context::flush_events()`. Use `Device::key()` to store the
+/// identity of a device, for example in a config file.
 pub struct Device<'a> {
 	/// The raw pointer to the device.
 	pub device: *mut raw::SoundIoDevice,
 
-	/// This is just here to say that Device cannot outlive the Context it was created from.
-	/// 'a is the lifetime of that Context.
-	pub phantom: PhantomData<&'a ()>,
+	/// The context this device was created from. 'a is the lifetime of the context's callbacks.
+	pub(crate) context: Arc<ContextInner<'a>>,
+}
+
+// Options for opening a stream that aren't parameters of `Device::open_outstream()` and
+// `Device::open_instream()`. They are set using `OutStreamBuilder` and `InStreamBuilder`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StreamOptions {
+	pub name: Option<String>,
+	pub non_terminal_hint: bool,
+	// Only used for output streams.
+	pub volume: Option<f64>,
+}
+
+// Convert a stream name to a C string, checking that it is valid.
+fn stream_name(name: &str) -> Result<CString> {
+	if name.contains(':') {
+		return Err(Error::Invalid);
+	}
+	CString::new(name).map_err(|_| Error::EncodingString)
 }
 
 impl<'a> Device<'a> {
@@ -50,6 +91,32 @@ impl<'a> Device<'a> {
 		utf8_to_string(unsafe { (*self.device).name } )
 	}
 
+	/// A `DeviceKey` for this device, which can be saved and resolved back to a `Device` later
+	/// using `Context::find_device()`.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// let mut ctx = soundio::Context::new();
+	/// ctx.connect_backend(soundio::Backend::Dummy).expect("Couldn't connect to backend");
+	/// ctx.flush_events();
+	/// let dev = ctx.default_output_device().expect("No output device");
+	/// let key = dev.key();
+	///
+	/// // ...
+	///
+	/// assert!(ctx.find_device(&key).expect("Device has gone") == dev);
+	/// ```
+	pub fn key(&self) -> DeviceKey {
+		DeviceKey::from(self)
+	}
+
+	/// A `DeviceInfo` with a copy of this device's properties and capabilities, which can be
+	/// stored or sent to another thread.
+	pub fn capabilities(&self) -> DeviceInfo {
+		DeviceInfo::from(self)
+	}
+
 	/// Tells whether this device is an input device or an output device.
 	///
 	/// If a physical device supports input and output it is split into two
@@ -73,41 +140,84 @@ impl<'a> Device<'a> {
 		}
 	}
 
+	/// The error that occurred while the backend was probing this device, if any.
+	///
+	/// If probing failed the device's layouts, formats, sample rates and latency are unknown,
+	/// so the functions that return them return this error instead, and streams can't be
+	/// opened on it. The id, name, aim and `is_raw()` are still valid, so it can be shown to the
+	/// user.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// let mut ctx = soundio::Context::new();
+	/// ctx.connect_backend(soundio::Backend::Dummy).expect("Couldn't connect to backend");
+	/// ctx.flush_events();
+	/// for dev in ctx.output_devices().expect("Couldn't get output devices") {
+	///     match dev.probe_error() {
+	///         Some(e) => println!("{}: probe error: {}", dev.name(), e),
+	///         None => println!("{}: {} formats", dev.name(), dev.formats().unwrap().len()),
+	///     }
+	/// }
+	/// ```
+	pub fn probe_error(&self) -> Option<Error> {
+		match unsafe { (*self.device).probe_error } {
+			0 => None,
+			x => Some(x.into()),
+		}
+	}
+
+	// Return the probe error, if any, so that the capability accessors can use `?`.
+	fn probed(&self) -> Result<()> {
+		match self.probe_error() {
+			None => Ok(()),
+			Some(e) => Err(e),
+		}
+	}
+
 	/// Returns the list of channel layouts supported by this device.
 	/// A channel layout has a name, and a list of channels with a channel ID.
 	/// For examples `ChannelLayout { name: "Stereo", channels: vec![ChannelId::Left, ChannelId::Right] }`.
 	///
-	/// Devices are guaranteed to have at least 1 channel layout.
+	/// Devices are guaranteed to have at least 1 channel layout, unless probing failed, in which
+	/// case this returns the probe error. See `Device::probe_error()`.
 	///
 	/// If you call `sort_channel_layouts()` before this function, the layouts will
 	/// be sorted by the number of channels in decreasing order.
-	pub fn layouts(&self) -> Vec<ChannelLayout> {
-
+	pub fn layouts(&self) -> Result<Vec<ChannelLayout>> {
+		self.probed()?;
+		// `sort_channel_layouts()` may be called on another `Device` for the same device.
+		let _guard = self.context.lock();
 		let layouts_slice = unsafe {
 			slice::from_raw_parts::<raw::SoundIoChannelLayout>((*self.device).layouts, (*self.device).layout_count as _)
 		};
 
-		layouts_slice.iter().map(|&x| x.into()).collect()
+		Ok(layouts_slice.iter().map(|&x| x.into()).collect())
 	}
 
 	/// Get the current channel layout. This behaves similarly to the current format
 	/// - this value is only meaningful for raw devices that have a sample
 	/// rate defined before a stream is opened. See `Device::current_format()` for
 	/// more information.
-	pub fn current_layout(&self) -> ChannelLayout {
-		unsafe { (*self.device).current_layout.into() }
+	///
+	/// If probing failed this returns the probe error.
+	pub fn current_layout(&self) -> Result<ChannelLayout> {
+		self.probed()?;
+		Ok(unsafe { (*self.device).current_layout.into() })
 	}
 
 	/// List of formats this device supports.
 	///
-	/// Devices are guaranteed to support at least one format.
-	pub fn formats(&self) -> Vec<Format> {
+	/// Devices are guaranteed to support at least one format, unless probing failed, in which
+	/// case this returns the probe error.
+	pub fn formats(&self) -> Result<Vec<Format>> {
+		self.probed()?;
 
 		let formats_slice = unsafe {
 			slice::from_raw_parts::<raw::SoundIoFormat>((*self.device).formats, (*self.device).format_count as _)
 		};
 
-		formats_slice.iter().map(|&x| x.into()).collect()
+		Ok(formats_slice.iter().map(|&x| x.into()).collect())
 	}
 
 	/// Get the current format.
@@ -126,15 +236,18 @@ impl<'a> Device<'a> {
 	/// `current_format()` to yours. You can learn the supported formats via
 	/// `Device::formats()`.
 	///
-	/// If `current_format()` is unavailable, it will be set to `Format::Invalid`.
-	pub fn current_format(&self) -> Format {
-		unsafe { (*self.device).current_format.into() }
+	/// If `current_format()` is unavailable, it will be set to `Format::Invalid`. If probing
+	/// failed this returns the probe error.
+	pub fn current_format(&self) -> Result<Format> {
+		self.probed()?;
+		Ok(unsafe { (*self.device).current_format.into() })
 	}
 
 	/// Sample rate is the number of frames per second (a frame is one sample from all channels).
 	/// Sample rate is handled very similar to `formats()`.
 	///
-	/// Devices are guaranteed to have at least 1 sample rate available.
+	/// Devices are guaranteed to have at least 1 sample rate available, unless probing failed, in
+	/// which case this returns the probe error.
 	///
 	/// # Examples
 	///
@@ -142,17 +255,18 @@ impl<'a> Device<'a> {
 	/// let mut ctx = soundio::Context::new();
 	/// ctx.connect_backend(soundio::Backend::Dummy).expect("Couldn't connect to backend");
 	/// let out_dev = ctx.default_output_device().expect("Couldn't open default output");
-	/// for rate in out_dev.sample_rates() {
+	/// for rate in out_dev.sample_rates().expect("Probing failed") {
 	///     println!("Sample rate min: {} max {}", rate.min, rate.max);
 	/// }
 	/// ```
-	pub fn sample_rates(&self) -> Vec<SampleRateRange> {
+	pub fn sample_rates(&self) -> Result<Vec<SampleRateRange>> {
+		self.probed()?;
 
 		let sample_rates_slice = unsafe {
 			slice::from_raw_parts::<raw::SoundIoSampleRateRange>((*self.device).sample_rates, (*self.device).sample_rate_count as _)
 		};
 
-		sample_rates_slice.iter().map(|&x| x.into()).collect()
+		Ok(sample_rates_slice.iter().map(|&x| x.into()).collect())
 	}
 
 	/// Get the current sample rate. This behaves similarly to the current format
@@ -160,23 +274,26 @@ impl<'a> Device<'a> {
 	/// rate defined before a stream is opened. See `Device::current_format()` for
 	/// more information.
 	///
-	/// If `current_sample_rate()` is unavailable it will return 0.
-	pub fn current_sample_rate(&self) -> i32 {
-		unsafe { (*self.device).sample_rate_current as _ }
+	/// If `current_sample_rate()` is unavailable it will return 0. If probing failed this
+	/// returns the probe error.
+	pub fn current_sample_rate(&self) -> Result<i32> {
+		self.probed()?;
+		Ok(unsafe { (*self.device).sample_rate_current as _ })
 	}
 
 	/// Software latency (current, minimum, maximum) in seconds. If this value is unknown or
-	/// irrelevant, it is set to 0.0.
+	/// irrelevant, it is set to 0.0. If probing failed this returns the probe error.
 	///
 	/// For PulseAudio and WASAPI this value is unknown until you open a stream.
-	pub fn software_latency(&self) -> SoftwareLatency {
-		unsafe {
+	pub fn software_latency(&self) -> Result<SoftwareLatency> {
+		self.probed()?;
+		Ok(unsafe {
 			SoftwareLatency {
 				min: (*self.device).software_latency_min,
 				max: (*self.device).software_latency_max,
 				current: (*self.device).software_latency_current,
 			}
-		}
+		})
 	}
 
 	/// Return whether the device has raw access.
@@ -197,15 +314,21 @@ impl<'a> Device<'a> {
 
 	/// Sorts the channels returned by `layouts()` by channel count, descending.
 	///
-	/// This mutates the internal list of layouts, which is why it takes `&mut self`.
+	/// This mutates the internal list of layouts, which is why it takes `&mut self`. It does
+	/// nothing if probing failed.
 	pub fn sort_channel_layouts(&mut self) {
 		// It may be a good idea to remove this function. I don't think it adds to the API.
+		if self.probe_error().is_some() {
+			return;
+		}
+		let _guard = self.context.lock();
 		unsafe {
 			raw::soundio_device_sort_channel_layouts(self.device);
 		}
 	}
 
-	/// Returns whether or not a given sample `Format` is supported by this device.
+	/// Returns whether or not a given sample `Format` is supported by this device. If probing
+	/// failed nothing is supported.
 	///
 	/// # Examples
 	///
@@ -216,13 +339,17 @@ impl<'a> Device<'a> {
 	/// println!("Default output device {} unsigned 16 bit little endian", if out_dev.supports_format(soundio::Format::S16LE) { "supports" } else { "doesn't support" });
 	/// ```
 	pub fn supports_format(&self, format: Format) -> bool {
+		if self.probe_error().is_some() {
+			return false;
+		}
 		unsafe {
 			raw::soundio_device_supports_format(self.device, format.into()) != 0
 		}
 	}
 
-	/// Returns whether or not a given channel layout is supported by this device.
-	/// 
+	/// Returns whether or not a given channel layout is supported by this device. If probing
+	/// failed nothing is supported.
+	///
 	/// # Examples
 	///
 	/// ```
@@ -232,13 +359,18 @@ impl<'a> Device<'a> {
 	/// println!("Default output device {} stereo", if out_dev.supports_layout(soundio::ChannelLayout::get_builtin(soundio::ChannelLayoutId::Stereo)) { "supports" } else { "doesn't support" });
 	/// ```
 	pub fn supports_layout(&self, layout: ChannelLayout) -> bool {
+		if self.probe_error().is_some() {
+			return false;
+		}
+		let _guard = self.context.lock();
 		unsafe {
 			raw::soundio_device_supports_layout(self.device, &layout.into() as *const _) != 0
 		}
 	}
 
-	/// Returns true if the given sample rate is supported by this device.
-	/// 
+	/// Returns true if the given sample rate is supported by this device. If probing failed
+	/// nothing is supported.
+	///
 	/// # Examples
 	///
 	/// ```
@@ -248,26 +380,31 @@ impl<'a> Device<'a> {
 	/// println!("Default output device {} 44.1 kHz", if out_dev.supports_sample_rate(44100) { "supports" } else { "doesn't support" });
 	/// ```
 	pub fn supports_sample_rate(&self, sample_rate: i32) -> bool {
+		if self.probe_error().is_some() {
+			return false;
+		}
 		unsafe {
 			raw::soundio_device_supports_sample_rate(self.device, sample_rate as c_int) != 0
 		}
 	}
 
 	/// Returns the nearest supported sample rate of this device. Devices are guaranteed
-	/// to support at least one sample rate.
-	/// 
+	/// to support at least one sample rate, unless probing failed, in which case this returns the
+	/// probe error.
+	///
 	/// # Examples
 	///
 	/// ```
 	/// let mut ctx = soundio::Context::new();
 	/// ctx.connect_backend(soundio::Backend::Dummy).expect("Couldn't connect to backend");
 	/// let out_dev = ctx.default_output_device().expect("Couldn't open default output");
-	/// println!("Nearest sample rate to 44000: {}", out_dev.nearest_sample_rate(44000));
+	/// println!("Nearest sample rate to 44000: {}", out_dev.nearest_sample_rate(44000).expect("Probing failed"));
 	/// ```
-	pub fn nearest_sample_rate(&self, sample_rate: i32) -> i32 {
-		unsafe {
+	pub fn nearest_sample_rate(&self, sample_rate: i32) -> Result<i32> {
+		self.probed()?;
+		Ok(unsafe {
 			raw::soundio_device_nearest_sample_rate(self.device, sample_rate as c_int) as i32
-		}
+		})
 	}
 
 
@@ -294,7 +431,8 @@ impl<'a> Device<'a> {
 	/// * `underflow_callback` - Optional callback that is called when your `write_callback` is too slow and the output skips.
 	/// * `error_callback` - Optional error callback.
 	///
-	/// Currently it is not possible to set the outstream name, or libsoundio's `non_terminal_hint`.
+	/// `Device::outstream_builder()` can be used instead to choose the parameters automatically. It can
+	/// also set the stream name, volume and libsoundio's `non_terminal_hint`.
 	///
 	/// # Return Values
 	///
@@ -315,10 +453,34 @@ impl<'a> Device<'a> {
 	///
 	/// # Lifetimes
 	///
-	/// `'a` is the lifetime of the `Device`. The `OutStream` lifetime `'b` must be less than or equal to `'a` (indicated by `'b: 'a`).
-	/// Also the callbacks must have a lifetime greater than or equal to `'b`. They do not need to be `'static`.
-	pub fn open_outstream<'b: 'a, WriteCB, UnderflowCB, ErrorCB>(
-				&'a self,
+	/// `'a` is the lifetime of the `Context` callbacks. The `OutStream` lifetime `'b` must be less than or equal to `'a` (indicated by `'a: 'b`).
+	/// Also the callbacks must have a lifetime greater than or equal to `'b`. They do not need to be `'static`,
+	/// but they must be `Send` because they are called on the audio thread.
+	///
+	/// The `OutStream` holds its own reference to this `Device`, so the `Device` does not need to outlive it
+	/// and it can be used to open more streams.
+	pub fn open_outstream<'b, WriteCB, UnderflowCB, ErrorCB>(
+				&self,
+				sample_rate: i32,
+				format: Format,
+				layout: ChannelLayout,
+				latency: f64,
+				write_callback: WriteCB,
+				underflow_callback: Option<UnderflowCB>,
+				error_callback: Option<ErrorCB>,
+				) -> Result<OutStream<'b>>
+		where
+			'a: 'b,
+			WriteCB: 'b + FnMut(&mut OutStreamWriter) + Send,
+			UnderflowCB: 'b + FnMut() + Send,
+			ErrorCB: 'b + FnMut(Error) + Send {
+
+		self.open_outstream_with_options(sample_rate, format, layout, latency, write_callback, underflow_callback, error_callback, StreamOptions::default())
+	}
+
+	// `open_outstream()` with extra options, which are set using `OutStreamBuilder`.
+	pub(crate) fn open_outstream_with_options<'b, WriteCB, UnderflowCB, ErrorCB>(
+				&self,
 				sample_rate: i32,
 				format: Format,
 				layout: ChannelLayout,
@@ -326,13 +488,26 @@ impl<'a> Device<'a> {
 				write_callback: WriteCB,
 				underflow_callback: Option<UnderflowCB>,
 				error_callback: Option<ErrorCB>,
+				options: StreamOptions,
 				) -> Result<OutStream<'b>>
 		where
-			WriteCB: 'b + FnMut(&mut OutStreamWriter),
-			UnderflowCB: 'b + FnMut(),
-			ErrorCB: 'b + FnMut(Error) {
+			'a: 'b,
+			WriteCB: 'b + FnMut(&mut OutStreamWriter) + Send,
+			UnderflowCB: 'b + FnMut() + Send,
+			ErrorCB: 'b + FnMut(Error) + Send {
+
+		let name = match options.name {
+			Some(ref name) => Some(stream_name(name)?),
+			None => None,
+		};
 
-		let mut outstream = unsafe { raw::soundio_outstream_create(self.device) };
+		let (event_sender, event_receiver) = event_queue(&self.context);
+
+		// This increments the device's reference count.
+		let mut outstream = {
+			let _guard = self.context.lock();
+			unsafe { raw::soundio_outstream_create(self.device) }
+		};
 		if outstream == ptr::null_mut() {
 			// Note that we should really abort() here (that's what the rest of Rust
 			// does on OOM), but there is no stable way to abort in Rust that I can see.
@@ -347,6 +522,13 @@ impl<'a> Device<'a> {
 			(*outstream).write_callback = outstream_write_callback;
 			(*outstream).underflow_callback = Some(outstream_underflow_callback);
 			(*outstream).error_callback = Some(outstream_error_callback);
+			if let Some(ref name) = name {
+				(*outstream).name = name.as_ptr();
+			}
+			(*outstream).non_terminal_hint = options.non_terminal_hint as _;
+			if let Some(volume) = options.volume {
+				(*outstream).volume = volume as _;
+			}
 		}
 
 		let mut stream = OutStream {
@@ -360,9 +542,16 @@ impl<'a> Device<'a> {
 				error_callback: match error_callback {
 					Some(cb) => Some(Box::new(cb)),
 					None => None,
-				}
+				},
+				panic_state: PanicState::new(),
+				name: name,
+				events: event_sender,
+				context: self.context.clone(),
 			} ),
+			device: self.clone(),
+			events: event_receiver,
 			phantom: PhantomData,
+			not_sync: PhantomData,
 		};
 
 		// Safe userdata pointer.
@@ -379,11 +568,49 @@ impl<'a> Device<'a> {
 			0 => {},
 			x => return Err(x.into()),
 		}
-		
+		// The volume is also set before opening, but some backends only apply it like this. Where
+		// the backend doesn't support it at all the stream is still usable, just at full volume.
+		if let Some(volume) = options.volume {
+			match stream.set_volume(volume) {
+				Ok(()) | Err(Error::IncompatibleBackend) => {},
+				Err(e) => return Err(e),
+			}
+		}
+
+
 		Ok(stream)
 	}
 
 
+	/// Return an `OutStreamBuilder` which opens an output stream on this device, choosing the
+	/// format, sample rate and layout from lists of preferences. This is an alternative to
+	/// `open_outstream()` where you don't have to check what the device supports first.
+	pub fn outstream_builder(&self) -> OutStreamBuilder<'a> {
+		OutStreamBuilder::new(self)
+	}
+
+	/// Open a `BlockingOutStream` on an output device. This is an alternative to `open_outstream()`
+	/// where you write interleaved samples of type `T` from a normal thread and `write()` blocks
+	/// until there is space for them, so you don't have to write a real-time callback.
+	///
+	/// `sample_rate`, `format`, `layout` and `latency` are the same as for `open_outstream()`.
+	/// `buffer_frames` is the number of frames that can be queued before `write()` blocks.
+	///
+	/// # Return Values
+	///
+	/// The same as `open_outstream()`. `Error::Invalid` is also returned if `layout` has no
+	/// channels or `buffer_frames` is 0.
+	pub fn open_blocking_outstream<T: Sample + Copy + Send + 'a>(
+				&self,
+				sample_rate: i32,
+				format: Format,
+				layout: ChannelLayout,
+				latency: f64,
+				buffer_frames: usize,
+				) -> Result<BlockingOutStream<'a, T>> {
+		BlockingOutStream::open(self, sample_rate, format, layout, latency, buffer_frames)
+	}
+
 	/// Open an input stream on an input device. After opening you can start, pause and stop it
 	/// using the functions on the `InStream` that is returned. Then your read callback
 	/// will be called. See the documentation on `InStreamReader` for more information.
@@ -398,7 +625,8 @@ impl<'a> Device<'a> {
 	/// * `overflow_callback` - Optional callback that is called when your `read_callback` is too slow and skips some input.
 	/// * `error_callback` - Optional error callback.
 	///
-	/// Currently it is not possible to set the outstream name, or libsoundio's `non_terminal_hint`.
+	/// `Device::instream_builder()` can be used instead to choose the parameters automatically. It can
+	/// also set the stream name and libsoundio's `non_terminal_hint`.
 	///
 	/// # Return Values
 	///
@@ -419,10 +647,34 @@ impl<'a> Device<'a> {
 	///
 	/// # Lifetimes
 	///
-	/// `'a` is the lifetime of the `Device`. The `InStream` lifetime `'b` must be less than or equal to `'a` (indicated by `'b: 'a`).
-	/// Also the callbacks must have a lifetime greater than or equal to `'b`. They do not need to be `'static`.
-	pub fn open_instream<'b: 'a, ReadCB, OverflowCB, ErrorCB>(
-				&'a self,
+	/// `'a` is the lifetime of the `Context` callbacks. The `InStream` lifetime `'b` must be less than or equal to `'a` (indicated by `'a: 'b`).
+	/// Also the callbacks must have a lifetime greater than or equal to `'b`. They do not need to be `'static`,
+	/// but they must be `Send` because they are called on the audio thread.
+	///
+	/// The `InStream` holds its own reference to this `Device`, so the `Device` does not need to outlive it
+	/// and it can be used to open more streams.
+	pub fn open_instream<'b, ReadCB, OverflowCB, ErrorCB>(
+				&self,
+				sample_rate: i32,
+				format: Format,
+				layout: ChannelLayout,
+				latency: f64,
+				read_callback: ReadCB,
+				overflow_callback: Option<OverflowCB>,
+				error_callback: Option<ErrorCB>,
+				) -> Result<InStream<'b>>
+		where
+			'a: 'b,
+			ReadCB: 'b + FnMut(&mut InStreamReader) + Send,
+			OverflowCB: 'b + FnMut() + Send,
+			ErrorCB: 'b + FnMut(Error) + Send {
+
+		self.open_instream_with_options(sample_rate, format, layout, latency, read_callback, overflow_callback, error_callback, StreamOptions::default())
+	}
+
+	// `open_instream()` with extra options, which are set using `InStreamBuilder`.
+	pub(crate) fn open_instream_with_options<'b, ReadCB, OverflowCB, ErrorCB>(
+				&self,
 				sample_rate: i32,
 				format: Format,
 				layout: ChannelLayout,
@@ -430,13 +682,26 @@ impl<'a> Device<'a> {
 				read_callback: ReadCB,
 				overflow_callback: Option<OverflowCB>,
 				error_callback: Option<ErrorCB>,
+				options: StreamOptions,
 				) -> Result<InStream<'b>>
 		where
-			ReadCB: 'b + FnMut(&mut InStreamReader),
-			OverflowCB: 'b + FnMut(),
-			ErrorCB: 'b + FnMut(Error) {
+			'a: 'b,
+			ReadCB: 'b + FnMut(&mut InStreamReader) + Send,
+			OverflowCB: 'b + FnMut() + Send,
+			ErrorCB: 'b + FnMut(Error) + Send {
+
+		let name = match options.name {
+			Some(ref name) => Some(stream_name(name)?),
+			None => None,
+		};
+
+		let (event_sender, event_receiver) = event_queue(&self.context);
 
-		let mut instream = unsafe { raw::soundio_instream_create(self.device) };
+		// This increments the device's reference count.
+		let mut instream = {
+			let _guard = self.context.lock();
+			unsafe { raw::soundio_instream_create(self.device) }
+		};
 		if instream == ptr::null_mut() {
 			// Note that we should really abort() here (that's what the rest of Rust
 			// does on OOM), but there is no stable way to abort in Rust that I can see.
@@ -451,6 +716,10 @@ impl<'a> Device<'a> {
 			(*instream).read_callback = instream_read_callback;
 			(*instream).overflow_callback = Some(instream_overflow_callback);
 			(*instream).error_callback = Some(instream_error_callback);
+			if let Some(ref name) = name {
+				(*instream).name = name.as_ptr();
+			}
+			(*instream).non_terminal_hint = options.non_terminal_hint as _;
 		}
 
 		let mut stream = InStream {
@@ -464,9 +733,16 @@ impl<'a> Device<'a> {
 				error_callback: match error_callback {
 					Some(cb) => Some(Box::new(cb)),
 					None => None,
-				}
+				},
+				panic_state: PanicState::new(),
+				name: name,
+				events: event_sender,
+				context: self.context.clone(),
 			} ),
+			device: self.clone(),
+			events: event_receiver,
 			phantom: PhantomData,
+			not_sync: PhantomData,
 		};
 
 		// Safe userdata pointer.
@@ -486,10 +762,80 @@ impl<'a> Device<'a> {
 		
 		Ok(stream)
 	}
+
+	/// Return an `InStreamBuilder` which opens an input stream on this device, choosing the
+	/// format, sample rate and layout from lists of preferences. This is an alternative to
+	/// `open_instream()` where you don't have to check what the device supports first.
+	pub fn instream_builder(&self) -> InStreamBuilder<'a> {
+		InStreamBuilder::new(self)
+	}
+
+	/// Open a `BlockingInStream` on an input device. This is an alternative to `open_instream()`
+	/// where you read interleaved samples of type `T` from a normal thread, and `read()` blocks
+	/// until they are available, so you don't have to write a real-time callback.
+	///
+	/// `sample_rate`, `format`, `layout` and `latency` are the same as for `open_instream()`.
+	/// `buffer_frames` is the number of frames that can be buffered before input is lost.
+	///
+	/// # Return Values
+	///
+	/// The same as `open_instream()`. `Error::Invalid` is also returned if `layout` has no
+	/// channels or `buffer_frames` is 0.
+	pub fn open_blocking_instream<T: Sample + Copy + Send + 'a>(
+				&self,
+				sample_rate: i32,
+				format: Format,
+				layout: ChannelLayout,
+				latency: f64,
+				buffer_frames: usize,
+				) -> Result<BlockingInStream<'a, T>> {
+		BlockingInStream::open(self, sample_rate, format, layout, latency, buffer_frames)
+	}
+}
+
+// The device's properties don't change once it has been probed. Its reference count and the
+// order of its layouts do, but only while holding the context's lock.
+unsafe impl<'a> Send for Device<'a> {}
+unsafe impl<'a> Sync for Device<'a> {}
+
+impl<'a> Clone for Device<'a> {
+	/// Returns a new reference to the same device. This just increments libsoundio's
+	/// reference count so it is cheap.
+	fn clone(&self) -> Device<'a> {
+		let _guard = self.context.lock();
+		unsafe {
+			raw::soundio_device_ref(self.device);
+		}
+		Device {
+			device: self.device,
+			context: self.context.clone(),
+		}
+	}
+}
+
+impl<'a> PartialEq for Device<'a> {
+	/// Returns true if both devices have the same `id()`, `aim()` and `is_raw()`.
+	fn eq(&self, other: &Device<'a>) -> bool {
+		unsafe {
+			raw::soundio_device_equal(self.device, other.device) != 0
+		}
+	}
+}
+
+impl<'a> Eq for Device<'a> {}
+
+impl<'a> Hash for Device<'a> {
+	// This must agree with `soundio_device_equal()`.
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.id().hash(state);
+		self.aim().hash(state);
+		self.is_raw().hash(state);
+	}
 }
 
 impl<'a> Drop for Device<'a> {
 	fn drop(&mut self) {
+		let _guard = self.context.lock();
 		unsafe {
 			raw::soundio_device_unref(self.device);
 		}
@@ -0,0 +1,104 @@
+use super::types::*;
+use super::error::*;
+use super::layout::*;
+use super::format::*;
+use super::device::*;
+
+/// A snapshot of a device's properties and capabilities, obtained using `Device::capabilities()`.
+///
+/// Unlike `Device` it owns all of its data, so it can be stored, sent to other threads and kept
+/// after the device has gone, for example to log a report of the devices. With the `serde`
+/// feature enabled it implements `Serialize` and `Deserialize`.
+///
+/// If probing the device failed, `probe_error` is set, the lists are empty, and the current
+/// values are the ones libsoundio uses for unknown values: a layout with no channels,
+/// `Format::Invalid`, a sample rate of 0 and latencies of 0.0.
+///
+/// # Examples
+///
+/// ```
+/// let mut ctx = soundio::Context::new();
+/// ctx.connect_backend(soundio::Backend::Dummy).expect("Couldn't connect to backend");
+/// ctx.flush_events();
+/// for dev in ctx.output_devices().expect("Couldn't get output devices") {
+///     println!("{:#?}", dev.capabilities());
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeviceInfo {
+	/// See `Device::id()`.
+	pub id: String,
+	/// See `Device::name()`.
+	pub name: String,
+	/// See `Device::aim()`.
+	pub aim: DeviceAim,
+	/// See `Device::is_raw()`.
+	pub is_raw: bool,
+	/// See `Device::layouts()`.
+	pub layouts: Vec<ChannelLayout>,
+	/// See `Device::current_layout()`.
+	pub current_layout: ChannelLayout,
+	/// See `Device::formats()`.
+	pub formats: Vec<Format>,
+	/// See `Device::current_format()`.
+	pub current_format: Format,
+	/// See `Device::sample_rates()`.
+	pub sample_rates: Vec<SampleRateRange>,
+	/// See `Device::current_sample_rate()`.
+	pub current_sample_rate: i32,
+	/// See `Device::software_latency()`.
+	pub software_latency: SoftwareLatency,
+	/// See `Device::probe_error()`.
+	pub probe_error: Option<Error>,
+}
+
+impl<'a, 'b> From<&'b Device<'a>> for DeviceInfo {
+	fn from(device: &'b Device<'a>) -> DeviceInfo {
+		DeviceInfo {
+			id: device.id(),
+			name: device.name(),
+			aim: device.aim(),
+			is_raw: device.is_raw(),
+			layouts: device.layouts().unwrap_or_default(),
+			current_layout: device.current_layout().unwrap_or_else(|_| ChannelLayout {
+				name: String::new(),
+				channels: Vec::new(),
+			}),
+			formats: device.formats().unwrap_or_default(),
+			current_format: device.current_format().unwrap_or(Format::Invalid),
+			sample_rates: device.sample_rates().unwrap_or_default(),
+			current_sample_rate: device.current_sample_rate().unwrap_or(0),
+			software_latency: device.software_latency().unwrap_or(SoftwareLatency {
+				min: 0.0,
+				max: 0.0,
+				current: 0.0,
+			}),
+			probe_error: device.probe_error(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::context::*;
+	use super::super::backend::*;
+
+	#[test]
+	fn dummy_device_capabilities() {
+		let mut ctx = Context::new();
+		ctx.connect_backend(Backend::Dummy).expect("Couldn't connect to backend");
+		ctx.flush_events();
+
+		let dev = ctx.default_output_device().expect("No output device");
+		let info = dev.capabilities();
+		assert_eq!(info.id, dev.id());
+		assert_eq!(info.aim, DeviceAim::Output);
+		assert!(info.probe_error.is_none());
+		assert_eq!(info.formats, dev.formats().expect("Probing failed"));
+		assert!(!info.layouts.is_empty());
+		assert!(!info.sample_rates.is_empty());
+		assert_eq!(info, dev.clone().capabilities());
+	}
+}
@@ -0,0 +1,100 @@
+use super::types::*;
+use super::device::*;
+
+use std::collections::BTreeSet;
+
+/// A description of a device that can be saved, for example in a config file, and resolved back
+/// to a `Device` later using `Context::find_device()`. It is obtained using `Device::key()` or
+/// `WatchedDevice::key()`.
+///
+/// Devices are identified by `id`, `aim` and `is_raw` together; see `Device::id()`. Some backends
+/// don't keep ids stable, for example across reboots or when a USB device is plugged into another
+/// port, so the name is kept as well and used when there is no device with the id.
+///
+/// With the `serde` feature enabled it implements `Serialize` and `Deserialize`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeviceKey {
+	/// See `Device::id()`.
+	pub id: String,
+	/// See `Device::name()`.
+	pub name: String,
+	/// See `Device::aim()`.
+	pub aim: DeviceAim,
+	/// See `Device::is_raw()`.
+	pub is_raw: bool,
+}
+
+// How similar a device's name must be to the key's for `find_device()` to choose it when the id
+// doesn't match.
+const MIN_NAME_SIMILARITY: f64 = 0.5;
+
+impl DeviceKey {
+	// Choose the device in `devices` (which all have the key's aim) that the key refers to: the one
+	// with the same id, otherwise the one with the most similar name.
+	pub(crate) fn best_match<'a>(&self, devices: Vec<Device<'a>>) -> Option<Device<'a>> {
+		let mut best: Option<(f64, Device<'a>)> = None;
+		for device in devices {
+			if device.is_raw() != self.is_raw {
+				continue;
+			}
+			if device.id() == self.id {
+				return Some(device);
+			}
+			let similarity = name_similarity(&device.name(), &self.name);
+			if similarity < MIN_NAME_SIMILARITY {
+				continue;
+			}
+			// The first of equally similar devices wins.
+			if best.as_ref().map_or(true, |&(best_similarity, _)| similarity > best_similarity) {
+				best = Some((similarity, device));
+			}
+		}
+		best.map(|(_, device)| device)
+	}
+}
+
+impl<'a, 'b> From<&'b Device<'a>> for DeviceKey {
+	fn from(device: &'b Device<'a>) -> DeviceKey {
+		DeviceKey {
+			id: device.id(),
+			name: device.name(),
+			aim: device.aim(),
+			is_raw: device.is_raw(),
+		}
+	}
+}
+
+// The lower case words in a device name, ignoring punctuation.
+fn name_words(name: &str) -> BTreeSet<String> {
+	name.split(|c: char| !c.is_alphanumeric())
+		.filter(|word| !word.is_empty())
+		.map(|word| word.to_lowercase())
+		.collect()
+}
+
+// How similar two device names are, from 0 (no words in common) to 1 (the same words, ignoring
+// case and punctuation).
+fn name_similarity(a: &str, b: &str) -> f64 {
+	let a = name_words(a);
+	let b = name_words(b);
+	let union = a.union(&b).count();
+	if union == 0 {
+		return 0.0;
+	}
+	a.intersection(&b).count() as f64 / union as f64
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn similar_names() {
+		assert_eq!(name_similarity("USB Audio CODEC", "usb audio codec"), 1.0);
+		assert_eq!(name_similarity("Built-in Output", "Built in output"), 1.0);
+		assert!(name_similarity("USB Audio CODEC (2)", "USB Audio CODEC") >= MIN_NAME_SIMILARITY);
+		assert!(name_similarity("HDMI Output", "USB Audio CODEC") < MIN_NAME_SIMILARITY);
+		assert_eq!(name_similarity("", ""), 0.0);
+	}
+}
@@ -0,0 +1,267 @@
+use super::types::*;
+use super::error::*;
+use super::format::*;
+use super::context::*;
+use super::device::*;
+
+use std::cmp::Reverse;
+
+/// `DeviceQuery` finds the devices that meet a set of requirements, ranked by how well they fit
+/// them. It is obtained using `Context::devices()`, and is useful for choosing a device from a
+/// command line option or a config file.
+///
+/// Every requirement must be met. A device that couldn't be probed (see `Device::probe_error()`)
+/// doesn't meet any requirement on its sample rates, formats or channels. The devices that are
+/// left are ranked by:
+///
+/// 1. Whether they could be probed.
+/// 2. Whether the name is the one given to `name_contains()`, ignoring case.
+/// 3. How many of the requested sample rate and format are the device's current ones, so the
+///    stream wouldn't be converted.
+/// 4. How few channels the device's smallest suitable layout has beyond `min_channels()`.
+/// 5. Whether it is the default device.
+///
+/// Devices that fit equally well are in the backend's order, inputs before outputs.
+///
+/// You *must* call `Context::flush_events()` at least once before running a query. If you don't
+/// it will panic.
+///
+/// # Examples
+///
+/// ```
+/// let mut ctx = soundio::Context::new();
+/// ctx.connect_backend(soundio::Backend::Dummy).expect("Couldn't connect to backend");
+/// ctx.flush_events();
+///
+/// let dev = ctx.devices()
+///     .output()
+///     .name_contains("dummy")
+///     .supports_rate(48000)
+///     .supports_format(soundio::Format::Float32LE)
+///     .min_channels(2)
+///     .raw(false)
+///     .best()
+///     .expect("No suitable device");
+/// println!("Using {}", dev.name());
+/// ```
+pub struct DeviceQuery<'c, 'a: 'c> {
+	context: &'c Context<'a>,
+	aim: Option<DeviceAim>,
+	name: Option<String>,
+	sample_rate: Option<i32>,
+	format: Option<Format>,
+	min_channels: Option<usize>,
+	is_raw: Option<bool>,
+}
+
+// How well a device fits a query. Larger is better, and the fields are compared in order. See
+// `DeviceQuery`.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd)]
+struct Fit {
+	probed: bool,
+	exact_name: bool,
+	current_settings: usize,
+	extra_channels: Reverse<usize>,
+	is_default: bool,
+}
+
+impl<'c, 'a> DeviceQuery<'c, 'a> {
+	// Use `Context::devices()` rather than calling this directly.
+	pub(crate) fn new(context: &'c Context<'a>) -> DeviceQuery<'c, 'a> {
+		DeviceQuery {
+			context: context,
+			aim: None,
+			name: None,
+			sample_rate: None,
+			format: None,
+			min_channels: None,
+			is_raw: None,
+		}
+	}
+
+	/// Only input devices.
+	pub fn input(mut self) -> DeviceQuery<'c, 'a> {
+		self.aim = Some(DeviceAim::Input);
+		self
+	}
+
+	/// Only output devices.
+	pub fn output(mut self) -> DeviceQuery<'c, 'a> {
+		self.aim = Some(DeviceAim::Output);
+		self
+	}
+
+	/// Only devices whose name contains `name`, ignoring case.
+	pub fn name_contains(mut self, name: &str) -> DeviceQuery<'c, 'a> {
+		self.name = Some(name.to_lowercase());
+		self
+	}
+
+	/// Only devices that support `sample_rate`. See `Device::supports_sample_rate()`.
+	pub fn supports_rate(mut self, sample_rate: i32) -> DeviceQuery<'c, 'a> {
+		self.sample_rate = Some(sample_rate);
+		self
+	}
+
+	/// Only devices that support `format`. See `Device::supports_format()`.
+	pub fn supports_format(mut self, format: Format) -> DeviceQuery<'c, 'a> {
+		self.format = Some(format);
+		self
+	}
+
+	/// Only devices with a channel layout that has at least `channels` channels.
+	pub fn min_channels(mut self, channels: usize) -> DeviceQuery<'c, 'a> {
+		self.min_channels = Some(channels);
+		self
+	}
+
+	/// Only raw devices, or only devices that aren't raw. See `Device::is_raw()`.
+	pub fn raw(mut self, is_raw: bool) -> DeviceQuery<'c, 'a> {
+		self.is_raw = Some(is_raw);
+		self
+	}
+
+	/// The devices that meet the requirements, best first.
+	///
+	/// # Errors
+	///
+	/// The errors from `Context::input_device()` and `Context::output_device()`.
+	pub fn list(&self) -> Result<Vec<Device<'a>>> {
+		let aims = match self.aim {
+			Some(aim) => vec![aim],
+			None => vec![DeviceAim::Input, DeviceAim::Output],
+		};
+
+		let mut matches = Vec::new();
+		for aim in aims {
+			let (count, default_index) = match aim {
+				DeviceAim::Input => (self.context.input_device_count(), self.context.default_input_device_index()),
+				DeviceAim::Output => (self.context.output_device_count(), self.context.default_output_device_index()),
+			};
+			for i in 0..count {
+				let device = match aim {
+					DeviceAim::Input => self.context.input_device(i)?,
+					DeviceAim::Output => self.context.output_device(i)?,
+				};
+				if let Some(fit) = self.fit(&device, default_index == Some(i)) {
+					matches.push((fit, device));
+				}
+			}
+		}
+
+		// The sort is stable so devices that fit equally well stay in order.
+		matches.sort_by(|a, b| b.0.cmp(&a.0));
+		Ok(matches.into_iter().map(|(_, device)| device).collect())
+	}
+
+	/// The device that fits the requirements best.
+	///
+	/// # Errors
+	///
+	/// * `Error::NoSuchDevice` - no device meets the requirements.
+	/// * The errors from `list()`.
+	pub fn best(&self) -> Result<Device<'a>> {
+		self.list()?.into_iter().next().ok_or(Error::NoSuchDevice)
+	}
+
+	// How well `device` fits, or `None` if it doesn't meet the requirements.
+	fn fit(&self, device: &Device<'a>, is_default: bool) -> Option<Fit> {
+		if let Some(is_raw) = self.is_raw {
+			if device.is_raw() != is_raw {
+				return None;
+			}
+		}
+
+		let name = device.name().to_lowercase();
+		if let Some(ref wanted) = self.name {
+			if !name.contains(wanted.as_str()) {
+				return None;
+			}
+		}
+
+		let probed = device.probe_error().is_none();
+		let needs_probe = self.sample_rate.is_some() || self.format.is_some() || self.min_channels.is_some();
+		if needs_probe && !probed {
+			return None;
+		}
+
+		let mut current_settings = 0;
+		if let Some(sample_rate) = self.sample_rate {
+			if !device.supports_sample_rate(sample_rate) {
+				return None;
+			}
+			if device.current_sample_rate().ok() == Some(sample_rate) {
+				current_settings += 1;
+			}
+		}
+		if let Some(format) = self.format {
+			if !device.supports_format(format) {
+				return None;
+			}
+			if device.current_format().ok() == Some(format) {
+				current_settings += 1;
+			}
+		}
+
+		let mut extra_channels = 0;
+		if let Some(min_channels) = self.min_channels {
+			let layouts = device.layouts().ok()?;
+			extra_channels = layouts.iter()
+				.map(|layout| layout.channels.len())
+				.filter(|&channels| channels >= min_channels)
+				.map(|channels| channels - min_channels)
+				.min()?;
+		}
+
+		Some(Fit {
+			probed: probed,
+			exact_name: self.name.as_ref().map_or(false, |wanted| *wanted == name),
+			current_settings: current_settings,
+			extra_channels: Reverse(extra_channels),
+			is_default: is_default,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::backend::*;
+
+	#[test]
+	fn query_dummy_devices() {
+		let mut ctx = Context::new();
+		ctx.connect_backend(Backend::Dummy).expect("Couldn't connect to backend");
+		ctx.flush_events();
+
+		let outputs = ctx.devices().output().supports_rate(48000).min_channels(2).list()
+			.expect("Couldn't list devices");
+		assert!(!outputs.is_empty());
+		for dev in &outputs {
+			assert_eq!(dev.aim(), DeviceAim::Output);
+		}
+
+		let all = ctx.devices().list().expect("Couldn't list devices");
+		assert_eq!(all.len(), ctx.input_device_count() + ctx.output_device_count());
+
+		match ctx.devices().name_contains("no such device").best() {
+			Err(Error::NoSuchDevice) => {},
+			x => panic!("Expected Error::NoSuchDevice, got {:?}", x.map(|dev| dev.name())),
+		}
+	}
+
+	#[test]
+	fn fit_order() {
+		let fit = |exact_name, current_settings, extra_channels, is_default| Fit {
+			probed: true,
+			exact_name: exact_name,
+			current_settings: current_settings,
+			extra_channels: Reverse(extra_channels),
+			is_default: is_default,
+		};
+		assert!(fit(true, 0, 4, false) > fit(false, 2, 0, true));
+		assert!(fit(false, 1, 4, false) > fit(false, 0, 0, true));
+		assert!(fit(false, 0, 0, false) > fit(false, 0, 2, true));
+		assert!(fit(false, 0, 0, true) > fit(false, 0, 0, false));
+	}
+}
@@ -0,0 +1,282 @@
+use super::error::*;
+use super::types::*;
+use super::context::*;
+use super::device::*;
+use super::device_key::*;
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::mpsc::{channel, Sender, Receiver};
+
+/// A description of a device in a `DeviceEvent`. Unlike `Device` it can be sent to other threads
+/// and kept after the device has gone.
+///
+/// Devices are identified by `id`, `aim` and `is_raw` together; see `Device::id()`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct WatchedDevice {
+	/// See `Device::id()`.
+	pub id: String,
+	/// See `Device::name()`.
+	pub name: String,
+	/// See `Device::aim()`.
+	pub aim: DeviceAim,
+	/// See `Device::is_raw()`.
+	pub is_raw: bool,
+}
+
+impl WatchedDevice {
+	/// A `DeviceKey` for this device, which can be saved and resolved back to a `Device` later
+	/// using `Context::find_device()`.
+	pub fn key(&self) -> DeviceKey {
+		DeviceKey {
+			id: self.id.clone(),
+			name: self.name.clone(),
+			aim: self.aim,
+			is_raw: self.is_raw,
+		}
+	}
+}
+
+impl<'a, 'b> From<&'b Device<'a>> for WatchedDevice {
+	fn from(device: &'b Device<'a>) -> WatchedDevice {
+		WatchedDevice {
+			id: device.id(),
+			name: device.name(),
+			aim: device.aim(),
+			is_raw: device.is_raw(),
+		}
+	}
+}
+
+/// A change to the devices, reported by a `DeviceWatcher`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum DeviceEvent {
+	/// A device was connected.
+	DeviceAdded(WatchedDevice),
+	/// A device was disconnected.
+	DeviceRemoved(WatchedDevice),
+	/// The default input or output device changed. `device` is `None` if there isn't one any more.
+	DefaultChanged {
+		aim: DeviceAim,
+		device: Option<WatchedDevice>,
+	},
+}
+
+// The key devices are stored under: the id, whether it's an input (so that inputs and outputs are
+// kept apart), and whether it's raw.
+type SnapshotKey = (String, bool, bool);
+
+fn snapshot_key(device: &WatchedDevice) -> SnapshotKey {
+	(device.id.clone(), device.aim == DeviceAim::Input, device.is_raw)
+}
+
+// The devices at one point in time.
+#[derive(Default)]
+struct DeviceSnapshot {
+	devices: BTreeMap<SnapshotKey, WatchedDevice>,
+	default_input: Option<WatchedDevice>,
+	default_output: Option<WatchedDevice>,
+}
+
+impl DeviceSnapshot {
+	fn take(ctx: &Context) -> DeviceSnapshot {
+		let mut snapshot = DeviceSnapshot::default();
+		// Devices that have a probe error are left out.
+		for i in 0..ctx.input_device_count() {
+			if let Ok(device) = ctx.input_device(i) {
+				if device.probe_error().is_some() {
+					continue;
+				}
+				let device = WatchedDevice::from(&device);
+				snapshot.devices.insert(snapshot_key(&device), device);
+			}
+		}
+		for i in 0..ctx.output_device_count() {
+			if let Ok(device) = ctx.output_device(i) {
+				if device.probe_error().is_some() {
+					continue;
+				}
+				let device = WatchedDevice::from(&device);
+				snapshot.devices.insert(snapshot_key(&device), device);
+			}
+		}
+		snapshot.default_input = ctx.default_input_device().ok().map(|d| WatchedDevice::from(&d));
+		snapshot.default_output = ctx.default_output_device().ok().map(|d| WatchedDevice::from(&d));
+		snapshot
+	}
+
+	// The events that turn `self` into `new`.
+	fn diff(&self, new: &DeviceSnapshot) -> Vec<DeviceEvent> {
+		let mut events = Vec::new();
+		for (key, device) in &self.devices {
+			if !new.devices.contains_key(key) {
+				events.push(DeviceEvent::DeviceRemoved(device.clone()));
+			}
+		}
+		for (key, device) in &new.devices {
+			if !self.devices.contains_key(key) {
+				events.push(DeviceEvent::DeviceAdded(device.clone()));
+			}
+		}
+		if self.default_input.as_ref().map(snapshot_key) != new.default_input.as_ref().map(snapshot_key) {
+			events.push(DeviceEvent::DefaultChanged {
+				aim: DeviceAim::Input,
+				device: new.default_input.clone(),
+			});
+		}
+		if self.default_output.as_ref().map(snapshot_key) != new.default_output.as_ref().map(snapshot_key) {
+			events.push(DeviceEvent::DefaultChanged {
+				aim: DeviceAim::Output,
+				device: new.default_output.clone(),
+			});
+		}
+		events
+	}
+}
+
+/// `DeviceWatcher` keeps a snapshot of the input and output devices, and works out what changed
+/// each time the list of devices is updated.
+///
+/// It is obtained using `Context::watch_devices()`. Call `update()` after `Context::flush_events()`
+/// or `Context::wait_events()`, on the same thread. It returns the `DeviceEvent`s, and also sends
+/// them to every channel returned by `subscribe()`, so they can be received on any other thread.
+///
+/// # Examples
+///
+/// ```
+/// let mut ctx = soundio::Context::new();
+/// ctx.connect_backend(soundio::Backend::Dummy).expect("Couldn't connect to backend");
+/// ctx.flush_events();
+///
+/// let mut watcher = ctx.watch_devices();
+/// let events = watcher.subscribe();
+/// std::thread::spawn(move || {
+///     for event in events {
+///         println!("{:?}", event);
+///     }
+/// });
+///
+/// ctx.flush_events();
+/// for event in watcher.update(&ctx).expect("Couldn't update") {
+///     println!("{:?}", event);
+/// }
+/// ```
+pub struct DeviceWatcher<'a> {
+	context: Arc<ContextInner<'a>>,
+	snapshot: DeviceSnapshot,
+	// The value of `ContextInner::devices_generation()` when the snapshot was taken.
+	generation: usize,
+	subscribers: Vec<Sender<DeviceEvent>>,
+}
+
+impl<'a> DeviceWatcher<'a> {
+	// Use `Context::watch_devices()` rather than calling this directly.
+	pub(crate) fn new(ctx: &Context<'a>) -> DeviceWatcher<'a> {
+		DeviceWatcher {
+			context: ctx.inner.clone(),
+			generation: ctx.inner.devices_generation(),
+			snapshot: DeviceSnapshot::take(ctx),
+			subscribers: Vec::new(),
+		}
+	}
+
+	/// Return a channel that receives every event from now on. It can be used on any thread, and
+	/// iterating over it blocks until the next event. Once the `DeviceWatcher` is dropped the
+	/// iteration ends.
+	pub fn subscribe(&mut self) -> Receiver<DeviceEvent> {
+		let (sender, receiver) = channel();
+		self.subscribers.push(sender);
+		receiver
+	}
+
+	/// If the list of devices has changed since the last update, compare it to the snapshot and
+	/// return the events, which are also sent to the subscribers. Otherwise it returns an empty
+	/// list.
+	///
+	/// Removed devices are reported before added ones, then changes to the default devices.
+	///
+	/// # Errors
+	///
+	/// * `Error::Invalid` - `ctx` isn't the `Context` that the watcher was created from.
+	pub fn update(&mut self, ctx: &Context<'a>) -> Result<Vec<DeviceEvent>> {
+		if !Arc::ptr_eq(&ctx.inner, &self.context) {
+			return Err(Error::Invalid);
+		}
+		let generation = self.context.devices_generation();
+		if generation == self.generation {
+			return Ok(Vec::new());
+		}
+		self.generation = generation;
+
+		let snapshot = DeviceSnapshot::take(ctx);
+		let events = self.snapshot.diff(&snapshot);
+		self.snapshot = snapshot;
+
+		// Subscribers that have gone away are forgotten.
+		self.subscribers.retain(|subscriber| {
+			events.iter().all(|event| subscriber.send(event.clone()).is_ok())
+		});
+		Ok(events)
+	}
+
+	/// The devices in the current snapshot, inputs first.
+	pub fn devices(&self) -> Vec<WatchedDevice> {
+		let (mut inputs, outputs): (Vec<_>, Vec<_>) = self.snapshot.devices.values().cloned()
+			.partition(|device| device.aim == DeviceAim::Input);
+		inputs.extend(outputs);
+		inputs
+	}
+
+	/// The default device for `aim` in the current snapshot.
+	pub fn default_device(&self, aim: DeviceAim) -> Option<&WatchedDevice> {
+		match aim {
+			DeviceAim::Input => self.snapshot.default_input.as_ref(),
+			DeviceAim::Output => self.snapshot.default_output.as_ref(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn device(id: &str, aim: DeviceAim) -> WatchedDevice {
+		WatchedDevice {
+			id: id.to_string(),
+			name: id.to_string(),
+			aim: aim,
+			is_raw: false,
+		}
+	}
+
+	fn snapshot(devices: &[WatchedDevice], default_output: Option<&WatchedDevice>) -> DeviceSnapshot {
+		DeviceSnapshot {
+			devices: devices.iter().map(|d| (snapshot_key(d), d.clone())).collect(),
+			default_input: None,
+			default_output: default_output.cloned(),
+		}
+	}
+
+	#[test]
+	fn diff_snapshots() {
+		let speakers = device("speakers", DeviceAim::Output);
+		let headphones = device("headphones", DeviceAim::Output);
+		let mic = device("headphones", DeviceAim::Input);
+
+		let old = snapshot(&[speakers.clone()], Some(&speakers));
+		let new = snapshot(&[speakers.clone(), headphones.clone(), mic.clone()], Some(&headphones));
+		assert_eq!(old.diff(&new), vec![
+			DeviceEvent::DeviceAdded(headphones.clone()),
+			DeviceEvent::DeviceAdded(mic.clone()),
+			DeviceEvent::DefaultChanged { aim: DeviceAim::Output, device: Some(headphones.clone()) },
+		]);
+
+		assert_eq!(new.diff(&old), vec![
+			DeviceEvent::DeviceRemoved(headphones.clone()),
+			DeviceEvent::DeviceRemoved(mic.clone()),
+			DeviceEvent::DefaultChanged { aim: DeviceAim::Output, device: Some(speakers.clone()) },
+		]);
+
+		assert_eq!(new.diff(&new), vec![]);
+	}
+}
@@ -18,7 +18,8 @@ use std::os::raw::c_int;
 /// let e = soundio::Error::IncompatibleDevice;
 /// println!("{}", e.description());
 /// ```
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Error {
 	/// Out of memory.
 	NoMem,
@@ -50,6 +51,15 @@ pub enum Error {
 	Underflow,
 	/// Unable to convert to or from UTF-8 to the native string format.
 	EncodingString,
+	/// The sample type requested does not match the stream's format, or the samples in the
+	/// stream's buffer are not aligned for it. This is not a libsoundio error; it is returned by
+	/// soundio-rs functions that access the stream's buffer directly, such as
+	/// `OutStreamWriter::channel_mut()`.
+	IncompatibleFormat,
+	/// Buffer overrun occurred, so some input was lost. This is not a libsoundio error; it is
+	/// returned by `BlockingInStream::read()` at the point in the stream where frames are missing.
+	/// Like `Error::Underflow` the stream is still valid and reading can continue.
+	Overflow,
 	/// Unknown error that libsoundio should never return.
 	Unknown,
 }
@@ -95,6 +105,9 @@ impl From<Error> for c_int {
 			Error::Interrupted => 13,
 			Error::Underflow => 14,
 			Error::EncodingString => 15,
+			// These have no libsoundio equivalent.
+			Error::IncompatibleFormat => -1,
+			Error::Overflow => -1,
 			Error::Unknown => -1, // This should never happen really.
 		}
 	}
@@ -114,6 +127,12 @@ impl fmt::Display for Error {
 // Implement the description for errors using soundio_strerror(), and the cause which we never know.
 impl error::Error for Error {
 	fn description(&self) -> &str {
+		match *self {
+			Error::IncompatibleFormat => return "sample type does not match the stream format",
+			Error::Overflow => return "buffer overflow",
+			_ => {},
+		}
+
 		let c_str: &CStr = unsafe { CStr::from_ptr(raw::soundio_strerror((*self).into())) };
 
 		// to_str() checks for valid UTF-8 since that what a &str is. For now at least there are
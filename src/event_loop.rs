@@ -0,0 +1,135 @@
+use super::context::*;
+
+use std::thread;
+use std::time::Duration;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// `EventLoop` is a handle to a thread that runs a `Context`'s event loop, so that you don't
+/// have to call `Context::wait_events()` yourself.
+///
+/// It is obtained using `Context::spawn_event_loop()`, which moves the `Context` to the new thread.
+/// The thread calls `wait_events()` repeatedly. That is when the context's device change and
+/// backend disconnect callbacks are called, and after each call it calls the handler that was
+/// passed to `spawn_event_loop()`. The handler is given the `Context`, because devices must be
+/// retrieved on the thread that updates them, so it is the place to do things like
+/// `DeviceWatcher::update()` or `FollowingOutStream::follow()`.
+///
+/// The thread stops when `shutdown()` is called, which returns the `Context`, or when the handle
+/// is dropped. If a callback or the handler panics the thread stops, and `shutdown()` returns the
+/// panic. Note that the default backend disconnect callback panics; see
+/// `Context::new_with_callbacks()`.
+///
+/// # Examples
+///
+/// ```
+/// let mut ctx = soundio::Context::new();
+/// ctx.connect_backend(soundio::Backend::Dummy).expect("Couldn't connect to backend");
+///
+/// let event_loop = ctx.spawn_event_loop(|ctx| {
+///     println!("{} output devices", ctx.output_device_count());
+/// });
+///
+/// // ...
+///
+/// let ctx = event_loop.shutdown().expect("The event loop panicked");
+/// ```
+pub struct EventLoop {
+	thread: Option<thread::JoinHandle<Context<'static>>>,
+	stop: Arc<AtomicBool>,
+	// Used to wake the thread up.
+	context: Arc<ContextInner<'static>>,
+}
+
+impl EventLoop {
+	// Use `Context::spawn_event_loop()` rather than calling this directly.
+	pub(crate) fn spawn<F>(ctx: Context<'static>, mut handler: F) -> EventLoop
+		where F: FnMut(&mut Context<'static>) + Send + 'static {
+
+		let stop = Arc::new(AtomicBool::new(false));
+		let context = ctx.inner.clone();
+
+		let thread_stop = stop.clone();
+		let thread = thread::Builder::new()
+			.name("soundio event loop".to_string())
+			.spawn(move || {
+				let mut ctx = ctx;
+				// The handler is called once at the start so that it can see the initial devices.
+				ctx.flush_events();
+				while !thread_stop.load(Ordering::Acquire) {
+					handler(&mut ctx);
+					ctx.wait_events();
+				}
+				ctx
+			})
+			.expect("Couldn't spawn the event loop thread");
+
+		EventLoop {
+			thread: Some(thread),
+			stop: stop,
+			context: context,
+		}
+	}
+
+	/// Wake up the event loop so that the handler is called, even if there are no events. Like
+	/// `Context::wakeup()` it has no effect if the thread isn't waiting for events at the time.
+	pub fn wakeup(&self) {
+		self.context.wakeup();
+	}
+
+	/// Stop the event loop and wait for the thread to finish. This returns the `Context`, or the
+	/// panic payload if a callback or the handler panicked.
+	pub fn shutdown(mut self) -> thread::Result<Context<'static>> {
+		self.stop_thread().expect("The event loop has already been shut down")
+	}
+
+	fn stop_thread(&mut self) -> Option<thread::Result<Context<'static>>> {
+		let thread = self.thread.take()?;
+		self.stop.store(true, Ordering::Release);
+		// A wakeup is lost if the thread isn't waiting yet, e.g. if it is running the handler,
+		// so keep trying until it has stopped.
+		while !thread.is_finished() {
+			self.context.wakeup();
+			thread::sleep(Duration::from_millis(1));
+		}
+		Some(thread.join())
+	}
+}
+
+impl Drop for EventLoop {
+	fn drop(&mut self) {
+		// A panic on the thread is ignored here; use `shutdown()` to find out about it.
+		let _ = self.stop_thread();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::backend::*;
+	use std::sync::mpsc::channel;
+
+	#[test]
+	fn spawn_and_shutdown() {
+		let mut ctx = Context::new();
+		ctx.connect_backend(Backend::Dummy).expect("Couldn't connect to backend");
+
+		let (sender, receiver) = channel();
+		let event_loop = ctx.spawn_event_loop(move |ctx| {
+			let _ = sender.send(ctx.output_device_count());
+		});
+
+		assert!(receiver.recv().expect("The handler wasn't called") > 0);
+		// The wakeup is lost if the thread isn't waiting yet.
+		loop {
+			event_loop.wakeup();
+			if let Ok(count) = receiver.recv_timeout(Duration::from_millis(10)) {
+				assert!(count > 0);
+				break;
+			}
+		}
+
+		let ctx = event_loop.shutdown().expect("The event loop panicked");
+		assert_eq!(ctx.current_backend(), Backend::Dummy);
+	}
+}
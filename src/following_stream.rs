@@ -0,0 +1,662 @@
+use super::error::*;
+use super::types::*;
+use super::context::*;
+use super::device::*;
+use super::outstream::*;
+use super::instream::*;
+use super::stream_builder::*;
+use super::util::*;
+
+use std::cmp;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+// How long a move waits for the old stream to fade out before switching anyway, in addition to
+// the length of the fade. The old stream might not be called at all, e.g. if its device was
+// unplugged.
+const FADE_TIMEOUT_MS: u64 = 500;
+
+// Controls the fade out of a stream that is being moved away from. It is shared between the
+// control thread and the stream's callback.
+struct FadeControl {
+	// Set by the control thread to start fading out.
+	fade_out: AtomicBool,
+	// Set by the callback once the gain has reached 0. After that it doesn't call the user's
+	// callback again.
+	faded_out: AtomicBool,
+}
+
+impl FadeControl {
+	fn new() -> FadeControl {
+		FadeControl {
+			fade_out: AtomicBool::new(false),
+			faded_out: AtomicBool::new(false),
+		}
+	}
+}
+
+// A linear gain ramp, applied to interleaved samples on the audio thread.
+struct Fade {
+	gain: f32,
+	// The change in gain per frame.
+	step: f32,
+}
+
+impl Fade {
+	fn new(fade_frames: usize, fade_in: bool) -> Fade {
+		Fade {
+			gain: if fade_in { 0.0 } else { 1.0 },
+			step: if fade_frames == 0 { 1.0 } else { 1.0 / fade_frames as f32 },
+		}
+	}
+
+	// Ramp the gain towards 1, or towards 0 if `out` is set, applying it to `samples`. It returns
+	// the gain at the end.
+	fn apply(&mut self, samples: &mut [f32], channel_count: usize, out: bool) -> f32 {
+		if !out && self.gain >= 1.0 {
+			return self.gain;
+		}
+		for frame in samples.chunks_mut(channel_count) {
+			self.gain = if out {
+				(self.gain - self.step).max(0.0)
+			} else {
+				(self.gain + self.step).min(1.0)
+			};
+			for sample in frame.iter_mut() {
+				*sample *= self.gain;
+			}
+		}
+		self.gain
+	}
+}
+
+// A stream that has been opened on the new default device and will replace the current one once
+// it has faded out.
+struct PendingMove<S> {
+	stream: S,
+	control: Arc<FadeControl>,
+	device_id: String,
+	is_raw: bool,
+	deadline: Instant,
+}
+
+// Works out when the default device has changed. This is shared by both kinds of following
+// stream.
+struct Follower<'a> {
+	context: Arc<ContextInner<'a>>,
+	aim: DeviceAim,
+	// The device the stream is on, identified by `Device::id()` and `Device::is_raw()`.
+	device_id: String,
+	is_raw: bool,
+	// The value of `ContextInner::devices_generation()` when the default was last checked.
+	generation: usize,
+	config: StreamConfig,
+	options: StreamOptions,
+	fade_frames: usize,
+	fade_duration: Duration,
+	started: bool,
+	paused: bool,
+}
+
+impl<'a> Follower<'a> {
+	fn new(device: &Device<'a>, config: StreamConfig, options: StreamOptions, crossfade: f64) -> Follower<'a> {
+		let crossfade = if crossfade > 0.0 { crossfade } else { 0.0 };
+		Follower {
+			context: device.context.clone(),
+			aim: device.aim(),
+			device_id: device.id(),
+			is_raw: device.is_raw(),
+			generation: device.context.devices_generation(),
+			fade_frames: (crossfade * config.sample_rate as f64) as usize,
+			fade_duration: Duration::from_millis((crossfade * 1000.0) as u64),
+			config: config,
+			options: options,
+			started: false,
+			paused: false,
+		}
+	}
+
+	// Return the default device if the list of devices has changed since this was last called
+	// and the default is no longer the device the stream is on.
+	fn new_default(&mut self, ctx: &Context<'a>) -> Result<Option<Device<'a>>> {
+		if !Arc::ptr_eq(&ctx.inner, &self.context) {
+			return Err(Error::Invalid);
+		}
+		let generation = self.context.devices_generation();
+		if generation == self.generation {
+			return Ok(None);
+		}
+		self.generation = generation;
+
+		let device = match self.aim {
+			DeviceAim::Output => ctx.default_output_device(),
+			DeviceAim::Input => ctx.default_input_device(),
+		};
+		match device {
+			Ok(device) => {
+				if device.id() == self.device_id && device.is_raw() == self.is_raw {
+					Ok(None)
+				} else {
+					Ok(Some(device))
+				}
+			},
+			// Stay where we are until there is a default device again.
+			Err(Error::NoSuchDevice) => Ok(None),
+			Err(e) => Err(e),
+		}
+	}
+
+	// Whether a move should fade out the old stream. It doesn't if it isn't playing.
+	fn fades(&self) -> bool {
+		self.fade_frames > 0 && self.started && !self.paused
+	}
+
+	fn pending_move<S>(&self, stream: S, control: Arc<FadeControl>, device: &Device<'a>) -> PendingMove<S> {
+		PendingMove {
+			stream: stream,
+			control: control,
+			device_id: device.id(),
+			is_raw: device.is_raw(),
+			deadline: Instant::now() + self.fade_duration + Duration::from_millis(FADE_TIMEOUT_MS),
+		}
+	}
+
+	fn moved<S>(&mut self, pending: &PendingMove<S>) {
+		self.device_id = pending.device_id.clone();
+		self.is_raw = pending.is_raw;
+	}
+
+	// Move `stream` to the default device if it has changed, using `open_on` to open the new
+	// stream. `control` controls the fade out of `stream`. See `FollowingOutStream::follow()`.
+	fn follow<S, F>(
+				&mut self,
+				ctx: &Context<'a>,
+				stream: &mut S,
+				control: &mut Arc<FadeControl>,
+				pending: &mut Option<PendingMove<S>>,
+				mut open_on: F,
+				) -> Result<bool>
+		where S: StreamControl, F: FnMut(&Follower<'a>, &Device<'a>, Arc<FadeControl>, bool) -> Result<S> {
+
+		if pending.is_none() {
+			let device = match self.new_default(ctx)? {
+				Some(device) => device,
+				None => return Ok(false),
+			};
+			let fades = self.fades();
+			let new_control = Arc::new(FadeControl::new());
+			let new_stream = open_on(self, &device, new_control.clone(), fades)?;
+			*pending = Some(self.pending_move(new_stream, new_control, &device));
+			if fades {
+				control.fade_out.store(true, Ordering::Release);
+			}
+		}
+
+		let finished = match *pending {
+			Some(ref pending) => !self.fades() || fade_finished(control, pending),
+			None => false,
+		};
+		if !finished {
+			return Ok(false);
+		}
+
+		let new = pending.take().unwrap();
+		self.moved(&new);
+		// The old stream is dropped here, so it stops calling the callbacks before the new one
+		// is started.
+		*stream = new.stream;
+		*control = new.control;
+		if self.started {
+			stream.start()?;
+			if self.paused {
+				stream.pause(true)?;
+			}
+		}
+		Ok(true)
+	}
+
+	fn start<S: StreamControl>(&mut self, stream: &mut S) -> Result<()> {
+		stream.start()?;
+		self.started = true;
+		Ok(())
+	}
+
+	fn pause<S: StreamControl>(&mut self, stream: &mut S, pause: bool) -> Result<()> {
+		stream.pause(pause)?;
+		self.paused = pause;
+		Ok(())
+	}
+}
+
+// Whether a pending move can be completed.
+fn fade_finished<S>(control: &FadeControl, pending: &PendingMove<S>) -> bool {
+	control.faded_out.load(Ordering::Acquire) || Instant::now() >= pending.deadline
+}
+
+/// `FollowingOutStream` is an output stream that moves to the system's default output device
+/// whenever it changes, for example when headphones are plugged in.
+///
+/// It is obtained using `OutStreamBuilder::open_following_default()`. Your callback is passed a
+/// buffer of interleaved `f32` samples in the negotiated layout to fill, which are converted to the
+/// stream's format. When `follow()` sees that the default device has changed, it opens a stream
+/// with the same `StreamConfig` on the new device. If a crossfade was requested the old stream
+/// fades out, then the new one fades in, so that there is no click. Your callbacks are never
+/// called by both streams at once.
+///
+/// # Examples
+///
+/// ```no_run
+/// let mut ctx = soundio::Context::new();
+/// ctx.connect().unwrap();
+/// ctx.flush_events();
+/// let dev = ctx.default_output_device().unwrap();
+///
+/// let (mut stream, _) = dev.outstream_builder()
+///     .formats(&[soundio::native::Float32NE])
+///     .open_following_default(0.05, |samples: &mut [f32]| {
+///         for s in samples.iter_mut() {
+///             *s = 0.0;
+///         }
+///     })
+///     .unwrap();
+/// stream.start().unwrap();
+///
+/// loop {
+///     ctx.wait_events();
+///     if let Ok(true) = stream.follow(&ctx) {
+///         println!("Moved to {}", stream.stream().device().name());
+///     }
+/// }
+/// ```
+pub struct FollowingOutStream<'a> {
+	// These must be dropped before the callbacks, which they point to.
+	stream: OutStream<'a>,
+	control: Arc<FadeControl>,
+	pending: Option<PendingMove<OutStream<'a>>>,
+	follower: Follower<'a>,
+	// The user's callbacks. They are boxed so that each stream can be given a pointer to them.
+	callback: Box<FnMut(&mut [f32]) + Send + 'a>,
+	underflow_callback: Option<Box<FnMut() + Send + 'a>>,
+	error_callback: Option<Box<FnMut(Error) + Send + 'a>>,
+}
+
+impl<'a> FollowingOutStream<'a> {
+	// Open the stream. Use `OutStreamBuilder::open_following_default()` rather than calling
+	// this directly.
+	pub(crate) fn open(
+				device: &Device<'a>,
+				config: StreamConfig,
+				options: StreamOptions,
+				crossfade: f64,
+				mut callback: Box<FnMut(&mut [f32]) + Send + 'a>,
+				mut underflow_callback: Option<Box<FnMut() + Send + 'a>>,
+				mut error_callback: Option<Box<FnMut(Error) + Send + 'a>>,
+				) -> Result<FollowingOutStream<'a>> {
+
+		let mut follower = Follower::new(device, config, options, crossfade);
+		let control = Arc::new(FadeControl::new());
+		let stream = open_outstream_on(&follower, device, control.clone(), false,
+		                               &mut callback, &mut underflow_callback, &mut error_callback)?;
+		follower.config.software_latency = stream.software_latency();
+
+		Ok(FollowingOutStream {
+			stream: stream,
+			control: control,
+			pending: None,
+			follower: follower,
+			callback: callback,
+			underflow_callback: underflow_callback,
+			error_callback: error_callback,
+		})
+	}
+
+	/// Check whether the default output device has changed, and move the stream to it if it has.
+	/// This should be called on the thread that owns `ctx`, after `Context::flush_events()` or
+	/// `Context::wait_events()`, which is when the list of devices is updated. While a crossfade
+	/// is in progress it should be called again until it returns `Ok(true)`; the old stream wakes
+	/// up `wait_events()` when it has faded out.
+	///
+	/// It returns `Ok(true)` if the stream has moved. `ctx` must be the `Context` that the stream
+	/// was opened from.
+	///
+	/// # Errors
+	///
+	/// * `Error::Invalid` - `ctx` is a different context.
+	/// * Any error from opening or starting the stream on the new device. The stream stays on the
+	///   old device until the devices change again.
+	pub fn follow(&mut self, ctx: &Context<'a>) -> Result<bool> {
+		let callback = &mut self.callback;
+		let underflow_callback = &mut self.underflow_callback;
+		let error_callback = &mut self.error_callback;
+		self.follower.follow(ctx, &mut self.stream, &mut self.control, &mut self.pending, |follower, device, control, fade_in| {
+			open_outstream_on(follower, device, control, fade_in, callback, underflow_callback, error_callback)
+		})
+	}
+
+	/// Start the stream. See `OutStream::start()`.
+	pub fn start(&mut self) -> Result<()> {
+		self.follower.start(&mut self.stream)
+	}
+
+	/// Pause or unpause the stream. The state is kept when it moves. See `OutStream::pause()`.
+	pub fn pause(&mut self, pause: bool) -> Result<()> {
+		self.follower.pause(&mut self.stream, pause)
+	}
+
+	/// The configuration used to open the stream.
+	pub fn config(&self) -> &StreamConfig {
+		&self.follower.config
+	}
+
+	/// The current stream. Its `OutStream::device()` is the device it is playing on.
+	pub fn stream(&self) -> &OutStream<'a> {
+		&self.stream
+	}
+}
+
+// Open an output stream for a `FollowingOutStream` on `device`.
+fn open_outstream_on<'a>(
+			follower: &Follower<'a>,
+			device: &Device<'a>,
+			control: Arc<FadeControl>,
+			fade_in: bool,
+			callback: &mut Box<FnMut(&mut [f32]) + Send + 'a>,
+			underflow_callback: &mut Option<Box<FnMut() + Send + 'a>>,
+			error_callback: &mut Option<Box<FnMut(Error) + Send + 'a>>,
+			) -> Result<OutStream<'a>> {
+
+	let callback: SendPtr<FnMut(&mut [f32]) + Send + 'a> = SendPtr(&mut **callback);
+	let underflow_callback = underflow_callback.as_mut().map(|cb| SendPtr(&mut **cb as *mut (FnMut() + Send + 'a)));
+	let error_callback = error_callback.as_mut().map(|cb| SendPtr(&mut **cb as *mut (FnMut(Error) + Send + 'a)));
+
+	let channel_count = follower.config.layout.channels.len();
+	// The callback fills this, so it never allocates. It holds a second of audio; if the backend
+	// asks for more than that at once the rest is silence.
+	let mut scratch = vec![0.0f32; cmp::max(follower.config.sample_rate as usize, 1) * channel_count];
+	let max_frames = scratch.len() / channel_count;
+	let mut fade = Fade::new(follower.fade_frames, fade_in);
+	let context = follower.context.clone();
+
+	let write_callback = move |writer: &mut OutStreamWriter| {
+		let mut frames_left = writer.frame_count_max();
+		while frames_left > 0 {
+			let request = cmp::max(writer.frame_count_min(), cmp::min(frames_left, max_frames));
+			let frame_count = match writer.begin_write(request) {
+				Ok(0) | Err(_) => break,
+				Ok(x) => x,
+			};
+
+			let frames = cmp::min(frame_count, max_frames);
+			let samples = &mut scratch[..frames * channel_count];
+			if control.faded_out.load(Ordering::Relaxed) {
+				for s in samples.iter_mut() {
+					*s = 0.0;
+				}
+			} else {
+				unsafe { (*callback.0)(samples); }
+				let out = control.fade_out.load(Ordering::Acquire);
+				if fade.apply(samples, channel_count, out) <= 0.0 && out {
+					control.faded_out.store(true, Ordering::Release);
+					context.wakeup();
+				}
+			}
+			writer.write_interleaved(samples);
+			for c in 0..channel_count {
+				for f in frames..frame_count {
+					writer.set_sample::<f32>(c, f, 0.0);
+				}
+			}
+
+			if writer.end_write().is_err() {
+				break;
+			}
+			frames_left = frames_left.saturating_sub(frame_count);
+		}
+	};
+
+	device.open_outstream_with_options(
+		follower.config.sample_rate,
+		follower.config.format,
+		follower.config.layout.clone(),
+		follower.config.software_latency,
+		write_callback,
+		underflow_callback.map(|cb| move || unsafe { (*cb.0)() }),
+		error_callback.map(|cb| move |err: Error| unsafe { (*cb.0)(err) }),
+		follower.options.clone(),
+	)
+}
+
+/// `FollowingInStream` is an input stream that moves to the system's default input device
+/// whenever it changes. It is obtained using `InStreamBuilder::open_following_default()`, and
+/// works the same way as `FollowingOutStream`, except that your callback is passed the recorded
+/// interleaved `f32` samples.
+pub struct FollowingInStream<'a> {
+	// These must be dropped before the callbacks, which they point to.
+	stream: InStream<'a>,
+	control: Arc<FadeControl>,
+	pending: Option<PendingMove<InStream<'a>>>,
+	follower: Follower<'a>,
+	// The user's callbacks. They are boxed so that each stream can be given a pointer to them.
+	callback: Box<FnMut(&[f32]) + Send + 'a>,
+	overflow_callback: Option<Box<FnMut() + Send + 'a>>,
+	error_callback: Option<Box<FnMut(Error) + Send + 'a>>,
+}
+
+impl<'a> FollowingInStream<'a> {
+	// Open the stream. Use `InStreamBuilder::open_following_default()` rather than calling
+	// this directly.
+	pub(crate) fn open(
+				device: &Device<'a>,
+				config: StreamConfig,
+				options: StreamOptions,
+				crossfade: f64,
+				mut callback: Box<FnMut(&[f32]) + Send + 'a>,
+				mut overflow_callback: Option<Box<FnMut() + Send + 'a>>,
+				mut error_callback: Option<Box<FnMut(Error) + Send + 'a>>,
+				) -> Result<FollowingInStream<'a>> {
+
+		let mut follower = Follower::new(device, config, options, crossfade);
+		let control = Arc::new(FadeControl::new());
+		let stream = open_instream_on(&follower, device, control.clone(), false,
+		                              &mut callback, &mut overflow_callback, &mut error_callback)?;
+		follower.config.software_latency = stream.software_latency();
+
+		Ok(FollowingInStream {
+			stream: stream,
+			control: control,
+			pending: None,
+			follower: follower,
+			callback: callback,
+			overflow_callback: overflow_callback,
+			error_callback: error_callback,
+		})
+	}
+
+	/// Check whether the default input device has changed, and move the stream to it if it has.
+	/// See `FollowingOutStream::follow()`.
+	pub fn follow(&mut self, ctx: &Context<'a>) -> Result<bool> {
+		let callback = &mut self.callback;
+		let overflow_callback = &mut self.overflow_callback;
+		let error_callback = &mut self.error_callback;
+		self.follower.follow(ctx, &mut self.stream, &mut self.control, &mut self.pending, |follower, device, control, fade_in| {
+			open_instream_on(follower, device, control, fade_in, callback, overflow_callback, error_callback)
+		})
+	}
+
+	/// Start the stream. See `InStream::start()`.
+	pub fn start(&mut self) -> Result<()> {
+		self.follower.start(&mut self.stream)
+	}
+
+	/// Pause or unpause the stream. The state is kept when it moves. See `InStream::pause()`.
+	pub fn pause(&mut self, pause: bool) -> Result<()> {
+		self.follower.pause(&mut self.stream, pause)
+	}
+
+	/// The configuration used to open the stream.
+	pub fn config(&self) -> &StreamConfig {
+		&self.follower.config
+	}
+
+	/// The current stream. Its `InStream::device()` is the device it is recording from.
+	pub fn stream(&self) -> &InStream<'a> {
+		&self.stream
+	}
+}
+
+// Open an input stream for a `FollowingInStream` on `device`.
+fn open_instream_on<'a>(
+			follower: &Follower<'a>,
+			device: &Device<'a>,
+			control: Arc<FadeControl>,
+			fade_in: bool,
+			callback: &mut Box<FnMut(&[f32]) + Send + 'a>,
+			overflow_callback: &mut Option<Box<FnMut() + Send + 'a>>,
+			error_callback: &mut Option<Box<FnMut(Error) + Send + 'a>>,
+			) -> Result<InStream<'a>> {
+
+	let callback: SendPtr<FnMut(&[f32]) + Send + 'a> = SendPtr(&mut **callback);
+	let overflow_callback = overflow_callback.as_mut().map(|cb| SendPtr(&mut **cb as *mut (FnMut() + Send + 'a)));
+	let error_callback = error_callback.as_mut().map(|cb| SendPtr(&mut **cb as *mut (FnMut(Error) + Send + 'a)));
+
+	let channel_count = follower.config.layout.channels.len();
+	// The callback reads into this, so it never allocates. It holds a second of audio; if the
+	// backend delivers more than that at once the rest is lost.
+	let mut scratch = vec![0.0f32; cmp::max(follower.config.sample_rate as usize, 1) * channel_count];
+	let max_frames = scratch.len() / channel_count;
+	let mut fade = Fade::new(follower.fade_frames, fade_in);
+	let context = follower.context.clone();
+
+	let read_callback = move |reader: &mut InStreamReader| {
+		let mut frames_left = reader.frame_count_max();
+		while frames_left > 0 {
+			let request = cmp::max(reader.frame_count_min(), cmp::min(frames_left, max_frames));
+			let frame_count = match reader.begin_read(request) {
+				Ok(0) | Err(_) => break,
+				Ok(x) => x,
+			};
+
+			if !control.faded_out.load(Ordering::Relaxed) {
+				let frames = reader.read_interleaved(&mut scratch[..]);
+				let samples = &mut scratch[..frames * channel_count];
+				let out = control.fade_out.load(Ordering::Acquire);
+				let gain = fade.apply(samples, channel_count, out);
+				unsafe { (*callback.0)(samples); }
+				if gain <= 0.0 && out {
+					control.faded_out.store(true, Ordering::Release);
+					context.wakeup();
+				}
+			}
+
+			if reader.end_read().is_err() {
+				break;
+			}
+			frames_left = frames_left.saturating_sub(frame_count);
+		}
+	};
+
+	device.open_instream_with_options(
+		follower.config.sample_rate,
+		follower.config.format,
+		follower.config.layout.clone(),
+		follower.config.software_latency,
+		read_callback,
+		overflow_callback.map(|cb| move || unsafe { (*cb.0)() }),
+		error_callback.map(|cb| move |err: Error| unsafe { (*cb.0)(err) }),
+		follower.options.clone(),
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::backend::*;
+	use super::super::format::*;
+	use super::super::layout::*;
+	use std::thread;
+
+	#[test]
+	fn fade_out_and_in() {
+		let mut samples = vec![1.0f32; 2 * 4];
+		let mut fade = Fade::new(4, false);
+		assert_eq!(fade.apply(&mut samples, 2, true), 0.0);
+		assert_eq!(samples, vec![0.75, 0.75, 0.5, 0.5, 0.25, 0.25, 0.0, 0.0]);
+
+		let mut samples = vec![1.0f32; 2 * 6];
+		let mut fade = Fade::new(4, true);
+		assert_eq!(fade.apply(&mut samples, 2, false), 1.0);
+		assert_eq!(samples, vec![0.25, 0.25, 0.5, 0.5, 0.75, 0.75, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0]);
+	}
+
+	// Make the follower think that the default device has changed since the stream was opened on
+	// it. The dummy backend's default device never changes.
+	fn change_default(follower: &mut Follower) {
+		follower.device_id = "previous default".to_string();
+		follower.generation = follower.generation.wrapping_sub(1);
+	}
+
+	fn silence(samples: &mut [f32]) {
+		for s in samples.iter_mut() {
+			*s = 0.0;
+		}
+	}
+
+	#[test]
+	fn follow_without_fade() {
+		let mut ctx = Context::new();
+		ctx.connect_backend(Backend::Dummy).expect("Couldn't connect to backend");
+		ctx.flush_events();
+
+		let dev = ctx.default_output_device().expect("No output device");
+		let (mut stream, _) = dev.outstream_builder()
+			.formats(&[Format::Float32LE])
+			.layouts(&[ChannelLayout::get_default(2)])
+			.open_following_default(0.0, silence)
+			.expect("Couldn't open stream");
+
+		assert_eq!(stream.follow(&ctx), Ok(false));
+
+		// A stream that isn't playing moves straight away.
+		change_default(&mut stream.follower);
+		assert_eq!(stream.follow(&ctx), Ok(true));
+		assert_eq!(stream.follower.device_id, dev.id());
+		assert!(stream.pending.is_none());
+		assert_eq!(stream.follow(&ctx), Ok(false));
+
+		match stream.follow(&Context::new()) {
+			Err(Error::Invalid) => {},
+			x => panic!("Expected Error::Invalid, got {:?}", x),
+		}
+	}
+
+	#[test]
+	fn follow_with_fade() {
+		let mut ctx = Context::new();
+		ctx.connect_backend(Backend::Dummy).expect("Couldn't connect to backend");
+		ctx.flush_events();
+
+		let dev = ctx.default_output_device().expect("No output device");
+		let (mut stream, _) = dev.outstream_builder()
+			.formats(&[Format::Float32LE])
+			.layouts(&[ChannelLayout::get_default(2)])
+			.open_following_default(0.01, silence)
+			.expect("Couldn't open stream");
+		stream.start().expect("Couldn't start stream");
+
+		// A playing stream fades out before it moves.
+		change_default(&mut stream.follower);
+		assert_eq!(stream.follow(&ctx), Ok(false));
+		assert!(stream.pending.is_some());
+		assert!(stream.control.fade_out.load(Ordering::Acquire));
+
+		let deadline = Instant::now() + Duration::from_secs(5);
+		while !stream.follow(&ctx).expect("Couldn't follow") {
+			assert!(Instant::now() < deadline, "The stream didn't move");
+			thread::sleep(Duration::from_millis(1));
+		}
+		assert_eq!(stream.follower.device_id, dev.id());
+		assert!(stream.pending.is_none());
+		assert!(!stream.control.fade_out.load(Ordering::Acquire));
+	}
+}
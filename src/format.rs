@@ -5,6 +5,7 @@ use std::fmt;
 
 /// Format defines the format of the samples. In 90% of cases you'll want `S16LE`, or maybe `Float64LE`.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Format {
 	/// Invalid format
 	Invalid,
@@ -4,31 +4,53 @@ use super::error::*;
 use super::format::*;
 use super::util::*;
 use super::sample::*;
+use super::device::*;
+use super::panic_policy::*;
+use super::channel_view::*;
+use super::stream_event::*;
+use super::context::*;
+#[cfg(feature = "async")]
+use super::async_events::*;
+#[cfg(feature = "mock")]
+use super::memory_stream::*;
 
 use std::ptr;
 use std::os::raw::{c_int, c_double};
 use std::marker::PhantomData;
+use std::cell::Cell;
 use std::slice;
+use std::cmp;
+use std::any::Any;
+use std::ffi::CString;
+use std::sync::Arc;
 
 /// This is called when an instream has been read. The `InStreamUserData` struct is obtained
 /// from the stream.userdata, then the user-supplied callback is called with an `InStreamReader`
 /// object.
+///
+/// Panics in the callback are caught and handled according to the stream's `PanicPolicy`.
 pub extern fn instream_read_callback(stream: *mut raw::SoundIoInStream, frame_count_min: c_int, frame_count_max: c_int) {
 	// Use stream.userdata to get a reference to the InStreamUserData object.
 	let raw_userdata_pointer = unsafe { (*stream).userdata as *mut InStreamUserData };
 	let userdata = unsafe { &mut (*raw_userdata_pointer) };
 
-	let mut stream_reader = InStreamReader {
-		instream: userdata.instream,
-		frame_count_min: frame_count_min as _,
-		frame_count_max: frame_count_max as _,
-		read_started: false,
-		channel_areas: Vec::new(),
-		frame_count: 0,
-		phantom: PhantomData,
+	// Once a callback has panicked we don't call it again.
+	if userdata.panic_state.panicked() {
+		discard_input(userdata.instream, frame_count_max as _);
+		return;
+	}
+
+	let mut stream_reader = InStreamReader::new(userdata.instream, frame_count_min as _, frame_count_max as _);
+
+	let panicked = {
+		let read_callback = &mut userdata.read_callback;
+		userdata.panic_state.call(|| read_callback(&mut stream_reader))
 	};
 
-	(userdata.read_callback)(&mut stream_reader);
+	if panicked {
+		drop(stream_reader);
+		userdata.report_panic();
+	}
 }
 
 pub extern fn instream_overflow_callback(stream: *mut raw::SoundIoInStream) {
@@ -36,10 +58,16 @@ pub extern fn instream_overflow_callback(stream: *mut raw::SoundIoInStream) {
 	let raw_userdata_pointer = unsafe { (*stream).userdata as *mut InStreamUserData };
 	let userdata = unsafe { &mut (*raw_userdata_pointer) };
 
+	if userdata.panic_state.panicked() {
+		return;
+	}
+
 	if let Some(ref mut cb) = userdata.overflow_callback {
-		cb();
+		if userdata.panic_state.call(|| cb()) {
+			userdata.report_panic();
+		}
 	} else {
-		println!("Overflow!");
+		userdata.events.send(StreamEvent::Overflow);
 	}
 }
 
@@ -48,10 +76,33 @@ pub extern fn instream_error_callback(stream: *mut raw::SoundIoInStream, err: c_
 	let raw_userdata_pointer = unsafe { (*stream).userdata as *mut InStreamUserData };
 	let userdata = unsafe { &mut (*raw_userdata_pointer) };
 
+	if userdata.panic_state.panicked() {
+		return;
+	}
+
 	if let Some(ref mut cb) = userdata.error_callback {
-		cb(err.into());
+		// The error callback is not called again to report its own panic.
+		userdata.panic_state.call(|| cb(err.into()));
 	} else {
-		println!("Error: {}", Error::from(err));
+		userdata.events.send(StreamEvent::Error(err.into()));
+	}
+}
+
+// Read and throw away the input instead of calling the read callback. This is used after a
+// callback has panicked.
+fn discard_input(instream: *mut raw::SoundIoInStream, frame_count_max: usize) {
+	let mut stream_reader = InStreamReader::new(instream, 0, frame_count_max);
+
+	let mut frames_left = frame_count_max;
+	while frames_left > 0 {
+		let frame_count = match stream_reader.begin_read(frames_left) {
+			Ok(0) | Err(_) => return,
+			Ok(x) => x,
+		};
+		if stream_reader.end_read().is_err() {
+			return;
+		}
+		frames_left -= frame_count;
 	}
 }
 
@@ -59,11 +110,24 @@ pub extern fn instream_error_callback(stream: *mut raw::SoundIoInStream, err: c_
 ///
 /// It is obtained from `Device` using `Device::open_instream()` and
 /// can be started and paused.
+///
+/// `InStream` is `Send` but not `Sync`, so it can be moved to another thread but only used from one
+/// thread at a time.
 pub struct InStream<'a> {
 	pub userdata: Box<InStreamUserData<'a>>,
-	
-	// This is just here to say that InStream cannot outlive the Device it was created from.
+
+	// The device this stream was opened on. This must be dropped after `userdata` so
+	// that the raw stream is destroyed before the device is unreferenced.
+	pub(crate) device: Device<'a>,
+
+	// The receiving end of `userdata.events`.
+	pub(crate) events: EventReceiver,
+
+	// This is just here to say that InStream cannot outlive the callbacks it was created with.
 	pub phantom: PhantomData<&'a ()>,
+
+	// This makes InStream `!Sync`. It is the only field that isn't `Sync`.
+	pub(crate) not_sync: PhantomData<Cell<()>>,
 }
 
 /// The callbacks required for an instream are stored in this object. We also store a pointer
@@ -71,20 +135,121 @@ pub struct InStream<'a> {
 pub struct InStreamUserData<'a> {
 	pub instream: *mut raw::SoundIoInStream,
 
-	pub read_callback: Box<FnMut(&mut InStreamReader) + 'a>,
-	pub overflow_callback: Option<Box<FnMut() + 'a>>,
-	pub error_callback: Option<Box<FnMut(Error) + 'a>>,
+	pub read_callback: Box<FnMut(&mut InStreamReader) + Send + 'a>,
+	pub overflow_callback: Option<Box<FnMut() + Send + 'a>>,
+	pub error_callback: Option<Box<FnMut(Error) + Send + 'a>>,
+
+	// What to do if one of the callbacks panics, and the panic payload if it did.
+	pub(crate) panic_state: PanicState,
+
+	// The stream name, if one was set. It is never read, but the raw stream points to it so it
+	// must live as long.
+	#[allow(dead_code)]
+	pub(crate) name: Option<CString>,
+	// Diagnostic events for the control thread. See `StreamEvent`.
+	pub(crate) events: EventSender,
+	// Used to lock the context while the raw stream is destroyed, which unreferences its device.
+	pub(crate) context: Arc<ContextInner<'a>>,
+}
+
+impl<'a> InStreamUserData<'a> {
+	// Called after a callback has panicked (and the policy isn't `PanicPolicy::Abort`).
+	fn report_panic(&mut self) {
+		if self.panic_state.policy() != PanicPolicy::ReportError {
+			return;
+		}
+		if let Some(ref mut cb) = self.error_callback {
+			self.panic_state.call(|| cb(Error::Streaming));
+		}
+	}
+
+	// Called when the implicit commit of a dropped `InStreamReader` fails. It is passed to the
+	// error callback, or queued as an event if there isn't one or a callback has panicked.
+	fn report_commit_error(&mut self, error: Error) {
+		if !self.panic_state.panicked() {
+			if let Some(ref mut cb) = self.error_callback {
+				self.panic_state.call(|| cb(error));
+				return;
+			}
+		}
+		self.events.send(StreamEvent::EndReadFailed(error));
+	}
 }
 
 impl<'a> Drop for InStreamUserData<'a> {
 	fn drop(&mut self) {
+		let _guard = self.context.lock();
 		unsafe {
 			raw::soundio_instream_destroy(self.instream);
 		}
 	}
 }
 
+// The callbacks are `Send`, and libsoundio's stream functions can be called from any thread
+// except the audio thread. It isn't `Sync` because the stream functions aren't synchronised
+// with each other; see `not_sync`.
+unsafe impl<'a> Send for InStream<'a> {}
+
+// Nothing can be done with a shared reference to the userdata: the callbacks need `&mut` and the
+// raw stream is only used through a `InStream`, which isn't `Sync`.
+unsafe impl<'a> Sync for InStreamUserData<'a> {}
+
 impl<'a> InStream<'a> {
+	/// Take the next diagnostic event from the stream, or `None` if there aren't any. Events are
+	/// generated on the audio thread for things that would otherwise go unreported, such as
+	/// overflows when there is no overflow callback. See `StreamEvent`.
+	///
+	/// If the `log` feature is enabled the event is also logged.
+	pub fn poll_event(&mut self) -> Option<StreamEvent> {
+		self.events.poll()
+	}
+
+	/// The number of events that were discarded because the event queue was full. Call
+	/// `poll_event()` regularly to avoid this.
+	pub fn dropped_event_count(&self) -> usize {
+		self.events.dropped()
+	}
+
+	/// Return a `Stream` of the stream's diagnostic events, as `Event::Stream`. It takes events
+	/// from the same queue as `poll_event()`. See `StreamEvents`.
+	///
+	/// This requires the `async` feature.
+	#[cfg(feature = "async")]
+	pub fn events(&mut self) -> StreamEvents<'_> {
+		StreamEvents::new(&mut self.events)
+	}
+
+	/// Returns the `Device` that this stream was opened on.
+	pub fn device(&self) -> &Device<'a> {
+		&self.device
+	}
+
+	/// Set what happens if one of the stream's callbacks panics. The default
+	/// is `PanicPolicy::Abort`. See `PanicPolicy` for more information.
+	///
+	/// This can be called at any time, including while the stream is running.
+	pub fn set_panic_policy(&mut self, policy: PanicPolicy) {
+		self.userdata.panic_state.set_policy(policy);
+	}
+
+	/// Returns the current `PanicPolicy`.
+	pub fn panic_policy(&self) -> PanicPolicy {
+		self.userdata.panic_state.policy()
+	}
+
+	/// Returns `true` if one of the stream's callbacks has panicked. After that
+	/// the callbacks are never called again.
+	pub fn has_panicked(&self) -> bool {
+		self.userdata.panic_state.panicked()
+	}
+
+	/// Take the payload of the first panic in one of the stream's callbacks, if
+	/// there was one. You can pass it to `std::panic::resume_unwind()` to continue
+	/// the panic on this thread.
+	pub fn take_panic(&mut self) -> Option<Box<Any + Send>> {
+		self.userdata.panic_state.take()
+	}
+
 	/// Starts the stream, returning `Ok(())` if it started successfully. Once
 	/// started the read callback will be periodically called according to the
 	/// requested latency.
@@ -160,7 +325,7 @@ impl<'a> InStream<'a> {
     /// For PulseAudio, if you set this value to non-default, it sets
     /// `PA_STREAM_ADJUST_LATENCY` and is the value used for `fragsize`.
     /// For JACK, this value is always equal to
-    /// `Device::software_latency().current`.
+    /// the current latency given by `Device::software_latency()`.
 	pub fn software_latency(&self) -> f64 {
 		unsafe {
 			(*self.userdata.instream).software_latency as _
@@ -175,7 +340,7 @@ impl<'a> InStream<'a> {
     /// WASAPI uses this for the session display name.
     /// Must not contain a colon (":").
 	///
-	/// TODO: Currently there is no way to set this.
+	/// It can be set using `InStreamBuilder::name()`.
 	pub fn name(&self) -> String {
 		unsafe {
 			utf8_to_string((*self.userdata.instream).name)
@@ -200,8 +365,12 @@ impl<'a> InStream<'a> {
 
 /// `InStreamReader` is passed to the read callback and can be used to read from the stream.
 ///
-/// You start by calling `begin_read()` and then you can read the samples. When the `InStreamReader`
-/// is dropped the samples are dropped. An error at that point is written to the console and ignored.
+/// You start by calling `begin_read()` and then you can read the samples, and drop them from the
+/// buffer with `end_read()`. If you don't, they are dropped when the `InStreamReader` is dropped,
+/// and an error at that point is passed to the stream's error callback.
+///
+/// Once ending a read has failed the reader can't be used any more: `begin_read()` and
+/// `end_read()` return the same error, which is also returned by `error()`.
 ///
 pub struct InStreamReader<'a> {
 	instream: *mut raw::SoundIoInStream,
@@ -209,17 +378,96 @@ pub struct InStreamReader<'a> {
 	frame_count_max: usize,
 
 	read_started: bool,
+	// The first error from committing a read. After that the reader can't be used.
+	error: Option<Error>,
 
-	// The memory area to write to - one for each channel. Populated after begin_read()
-	channel_areas: Vec<raw::SoundIoChannelArea>,
+	// The memory area to write to - one for each channel. Populated after begin_read(). This is
+	// a fixed size array rather than a `Vec` so that the audio thread never allocates.
+	channel_areas: [raw::SoundIoChannelArea; raw::SOUNDIO_MAX_CHANNELS],
 	// The actual frame count. Populated after begin_read()
 	frame_count: usize,
 
+	// If this is set the buffer is in memory rather than libsoundio's. See `MockInStream`.
+	#[cfg(feature = "mock")]
+	memory: *mut MemoryInBuffer,
+
 	// This cannot outlive the scope that it is spawned from (in the write callback).
 	phantom: PhantomData<&'a ()>,
 }
 
 impl<'a> InStreamReader<'a> {
+	// Create a reader for a call of the read callback.
+	fn new(instream: *mut raw::SoundIoInStream, frame_count_min: usize, frame_count_max: usize) -> InStreamReader<'a> {
+		InStreamReader {
+			instream: instream,
+			frame_count_min: frame_count_min,
+			frame_count_max: frame_count_max,
+			read_started: false,
+			error: None,
+			channel_areas: [raw::SoundIoChannelArea { ptr: ptr::null_mut(), step: 0 }; raw::SOUNDIO_MAX_CHANNELS],
+			frame_count: 0,
+			#[cfg(feature = "mock")]
+			memory: ptr::null_mut(),
+			phantom: PhantomData,
+		}
+	}
+
+	// Create a reader that reads from `memory` instead of libsoundio's buffer. `instream` is only
+	// used for the stream's parameters.
+	#[cfg(feature = "mock")]
+	pub(crate) fn with_memory(instream: *mut raw::SoundIoInStream, memory: *mut MemoryInBuffer, frame_count_min: usize, frame_count_max: usize) -> InStreamReader<'a> {
+		let mut reader = InStreamReader::new(instream, frame_count_min, frame_count_max);
+		reader.memory = memory;
+		reader
+	}
+
+	// `soundio_instream_begin_read()`, or its stand-in.
+	unsafe fn raw_begin_read(&mut self, areas: *mut *mut raw::SoundIoChannelArea, frame_count: *mut c_int) -> c_int {
+		#[cfg(feature = "mock")]
+		{
+			if !self.memory.is_null() {
+				return (*self.memory).begin_read(areas, frame_count);
+			}
+		}
+		raw::soundio_instream_begin_read(self.instream, areas, frame_count)
+	}
+
+	// `soundio_instream_end_read()`, or its stand-in.
+	unsafe fn raw_end_read(&mut self) -> c_int {
+		#[cfg(feature = "mock")]
+		{
+			if !self.memory.is_null() {
+				return (*self.memory).end_read();
+			}
+		}
+		raw::soundio_instream_end_read(self.instream)
+	}
+
+	// `soundio_instream_get_latency()`, or its stand-in.
+	unsafe fn raw_get_latency(&mut self, latency: *mut c_double) -> c_int {
+		#[cfg(feature = "mock")]
+		{
+			if !self.memory.is_null() {
+				*latency = (*self.memory).latency();
+				return 0;
+			}
+		}
+		raw::soundio_instream_get_latency(self.instream, latency)
+	}
+
+	// Pass an error from the implicit end in `drop()` on to the stream.
+	unsafe fn report_commit_error(&mut self, error: Error) {
+		#[cfg(feature = "mock")]
+		{
+			if !self.memory.is_null() {
+				(*self.memory).commit_errors.push(error);
+				return;
+			}
+		}
+		let userdata = (*self.instream).userdata as *mut InStreamUserData;
+		(*userdata).report_commit_error(error);
+	}
+
 	/// Start a read. You can only call this once per callback otherwise it panics.
 	///
 	/// frame_count is the number of frames you want to read. It must be between
@@ -236,14 +484,19 @@ impl<'a> InStreamReader<'a> {
 	/// * `Error::IncompatibleDevice` - in rare cases it might just now
 	///   be discovered that the device uses non-byte-aligned access, in which
 	///   case this error code is returned.
+	/// * Any error from an earlier `end_read()`. See `error()`.
 	///
 	pub fn begin_read(&mut self, frame_count: usize) -> Result<usize> {
 		assert!(frame_count >= self.frame_count_min && frame_count <= self.frame_count_max, "frame_count out of range");
 
+		if let Some(e) = self.error {
+			return Err(e);
+		}
+
 		let mut areas: *mut raw::SoundIoChannelArea = ptr::null_mut();
 		let mut actual_frame_count: c_int = frame_count as _;
 
-		match unsafe { raw::soundio_instream_begin_read(self.instream, &mut areas as *mut _, &mut actual_frame_count as *mut _) } {
+		match unsafe { self.raw_begin_read(&mut areas as *mut _, &mut actual_frame_count as *mut _) } {
 			0 => {
 				self.read_started = true;
 				self.frame_count = actual_frame_count as _;
@@ -252,36 +505,54 @@ impl<'a> InStreamReader<'a> {
 					return Ok(0);
 				}
 				let cc = self.channel_count();
-				self.channel_areas = vec![raw::SoundIoChannelArea { ptr: ptr::null_mut(), step: 0 }; cc];
-				unsafe { self.channel_areas.copy_from_slice(slice::from_raw_parts::<raw::SoundIoChannelArea>(areas, cc)); }
+				if areas.is_null() {
+					// There is a hole in the buffer; there is no data for these frames.
+					for area in self.channel_areas[..cc].iter_mut() {
+						area.ptr = ptr::null_mut();
+						area.step = 0;
+					}
+				} else {
+					unsafe { self.channel_areas[..cc].copy_from_slice(slice::from_raw_parts::<raw::SoundIoChannelArea>(areas, cc)); }
+				}
 				Ok(actual_frame_count as _)
 			},
 			e => Err(e.into()),
 		}
 	}
 
-	/// Commits the write that you began with `begin_read()`.
-	///
-	/// Errors are currently are just printed to the console and ignored.
+	/// Drops the frames that you began reading with `begin_read()` from the buffer. It does
+	/// nothing if there isn't a read to end.
 	///
 	/// # Errors
 	///
 	/// * `Error::Streaming`
-	/// * `Error::Underflow` - an underflow caused this call to fail. You might
-	///   also get an `underflow_callback()`, and you might not get
-	///   this error code when an underflow occurs. Unlike `Error::Streaming`,
-	///   the outstream is still in a valid state and streaming can continue.
-	pub fn end_read(&mut self) {
-		if self.read_started {
-			unsafe {
-				match raw::soundio_instream_end_read(self.instream) {
-					0 => {self.read_started = false;},
-					x => println!("Error ending instream: {}", Error::from(x)),
-				}
-			}
+	/// * Any error from an earlier `end_read()`. See `error()`.
+	///
+	pub fn end_read(&mut self) -> Result<()> {
+		if let Some(e) = self.error {
+			return Err(e);
 		}
+		if !self.read_started {
+			return Ok(());
+		}
+		self.read_started = false;
+		match unsafe { self.raw_end_read() } {
+			0 => Ok(()),
+			x => {
+				let e = x.into();
+				self.error = Some(e);
+				Err(e)
+			},
+		}
+	}
+
+	/// The error from ending a read, if it failed. Once this is set the reader can't be used
+	/// any more.
+	pub fn error(&self) -> Option<Error> {
+		self.error
 	}
 	
+
 	/// Get the minimum frame count that you can call `begin_read()` with.
 	/// Retreive this value before calling `begin_read()` to ensure you read the correct number
 	/// of frames.
@@ -334,7 +605,7 @@ impl<'a> InStreamReader<'a> {
 	///
 	pub fn get_latency(&mut self) -> Result<f64> {
 		let mut x: c_double = 0.0;
-		match unsafe { raw::soundio_instream_get_latency(self.instream, &mut x as *mut c_double) } {
+		match unsafe { self.raw_get_latency(&mut x as *mut c_double) } {
 			0 => Ok(x),
 			e => Err(e.into()),
 		}
@@ -343,7 +614,8 @@ impl<'a> InStreamReader<'a> {
 	/// Get the value of a sample. This panics if the `channel` or `frame` are
 	/// out of range or if you haven't called `begin_read()` yet.
 	///
-	/// If you request a different type from the actual one it will be converted.
+	/// If you request a different type from the actual one it will be converted. If the frames are
+	/// a hole in the buffer (there was an overflow) the sample is silence.
 	///
 	/// # Examples
 	///
@@ -365,6 +637,10 @@ impl<'a> InStreamReader<'a> {
 		assert!(channel < self.channel_count(), "Channel out of range");
 		assert!(frame < self.frame_count(), "Frame out of range");
 
+		if self.channel_areas[channel].ptr.is_null() {
+			return T::from_f32(0.0);
+		}
+
 		unsafe {
 			let ptr = self.channel_areas[channel].ptr.offset((frame * self.channel_areas[channel].step as usize) as isize) as *mut u8;
 
@@ -392,25 +668,153 @@ impl<'a> InStreamReader<'a> {
 		}
 	}
 
-	// TODO: To acheive speed *and* safety I can use iterators. That will be in a future API.
-}
+	/// Get a typed, read-only view of one channel's samples, which can be read in place without
+	/// the per-sample format conversion done by `sample()`. This panics if `channel` is out of
+	/// range or if you haven't called `begin_read()` yet.
+	///
+	/// # Errors
+	///
+	/// * `Error::IncompatibleFormat`
+	///   - `T` is not the stream's format, e.g. `i16` for a stream that isn't `native::S16NE`.
+	///   - the channel's samples are not aligned for `T`.
+	/// * `Error::Streaming` - the frames are a hole in the buffer because of an overflow, so there
+	///   are no samples to read.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// fn read_callback(stream: &mut soundio::InStreamReader) {
+	///     let frame_count_max = stream.frame_count_max();
+	///     stream.begin_read(frame_count_max).unwrap();
+	///     for c in 0..stream.channel_count() {
+	///         let channel = stream.channel::<i16>(c).unwrap();
+	///         for sample in channel.iter() {
+	///             do_something_with(*sample);
+	///         }
+	///     }
+	/// }
+	/// # fn do_something_with(_: i16) { }
+	/// ```
+	pub fn channel<T: NativeSample>(&self, channel: usize) -> Result<Channel<'_, T>> {
+		assert!(self.read_started);
 
-impl<'a> Drop for InStreamReader<'a> {
-	/// This will drop all of the frames from when you called `begin_read()`.
+		assert!(channel < self.channel_count(), "Channel out of range");
+
+		if Format::from(unsafe { (*self.instream).format }) != T::native_format() {
+			return Err(Error::IncompatibleFormat);
+		}
+
+		let area = self.channel_areas[channel];
+		if self.frame_count == 0 {
+			return Ok(unsafe { Channel::new(ptr::null(), 0, 0) });
+		}
+		if area.ptr.is_null() {
+			return Err(Error::Streaming);
+		}
+		if !is_aligned_for::<T>(area.ptr as *const u8, area.step as usize) {
+			return Err(Error::IncompatibleFormat);
+		}
+		Ok(unsafe { Channel::new(area.ptr as *const u8, area.step as usize, self.frame_count) })
+	}
+
+	/// Read interleaved samples, e.g. `[l0, r0, l1, r1, ...]` for a stereo stream, converting them
+	/// from the stream's format. This panics if you haven't called `begin_read()` yet.
 	///
-	/// Errors are currently are just printed to the console and ignored.
+	/// It reads as many whole frames as fit in `samples`, up to `frame_count()`, and returns the
+	/// number of frames read. If the frames are a hole in the buffer (there was an overflow) they
+	/// are read as silence.
 	///
-	/// # Errors
+	/// # Examples
 	///
-	/// * `Error::Streaming`
-	fn drop(&mut self) {
-		if self.read_started {
-			unsafe {
-				match raw::soundio_instream_end_read(self.instream) {
-					0 => {},
-					x => println!("Error reading instream: {}", Error::from(x)),
+	/// ```
+	/// fn read_callback(stream: &mut soundio::InStreamReader) {
+	///     let frame_count_max = stream.frame_count_max();
+	///     stream.begin_read(frame_count_max).unwrap();
+	///     let mut audio = vec![0.0f32; stream.frame_count() * stream.channel_count()];
+	///     stream.read_interleaved(&mut audio);
+	/// }
+	/// ```
+	pub fn read_interleaved<T: Sample + Copy>(&self, samples: &mut [T]) -> usize {
+		assert!(self.read_started);
+
+		let channel_count = self.channel_count();
+		let frames = cmp::min(self.frame_count, samples.len() / channel_count);
+		if frames == 0 {
+			return 0;
+		}
+
+		let format = Format::from(unsafe { (*self.instream).format });
+		for c in 0..channel_count {
+			let area = self.channel_areas[c];
+			let channel_samples = samples[c..].iter_mut().step_by(channel_count).take(frames);
+			if area.ptr.is_null() {
+				for sample in channel_samples {
+					*sample = T::from_f32(0.0);
+				}
+			} else {
+				unsafe {
+					read_samples(format, area.ptr as *const u8, area.step as usize, channel_samples);
 				}
 			}
 		}
+		frames
+	}
+
+	/// Read planar samples, i.e. one slice per channel, converting them from the stream's format.
+	/// This panics if you haven't called `begin_read()` yet, or if the number of slices is not
+	/// `channel_count()`.
+	///
+	/// It reads as many frames as fit in the shortest slice, up to `frame_count()`, and returns the
+	/// number of frames read. Holes are read as silence, as in `read_interleaved()`.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// fn read_callback(stream: &mut soundio::InStreamReader, audio: &mut [Vec<i16>]) {
+	///     let frame_count_max = stream.frame_count_max();
+	///     stream.begin_read(frame_count_max).unwrap();
+	///     stream.read_planar(audio);
+	/// }
+	/// ```
+	pub fn read_planar<T: Sample + Copy, S: AsMut<[T]>>(&self, channels: &mut [S]) -> usize {
+		assert!(self.read_started);
+
+		assert_eq!(channels.len(), self.channel_count(), "Wrong number of channels");
+		let frames = channels.iter_mut().fold(self.frame_count, |frames, ch| cmp::min(frames, ch.as_mut().len()));
+		if frames == 0 {
+			return 0;
+		}
+
+		let format = Format::from(unsafe { (*self.instream).format });
+		for (c, ch) in channels.iter_mut().enumerate() {
+			let area = self.channel_areas[c];
+			let channel_samples = ch.as_mut()[..frames].iter_mut();
+			if area.ptr.is_null() {
+				for sample in channel_samples {
+					*sample = T::from_f32(0.0);
+				}
+			} else {
+				unsafe {
+					read_samples(format, area.ptr as *const u8, area.step as usize, channel_samples);
+				}
+			}
+		}
+		frames
+	}
+}
+
+impl<'a> Drop for InStreamReader<'a> {
+	/// This drops the frames from the buffer if you called `begin_read()` but not `end_read()`.
+	///
+	/// An error is passed to the stream's error callback. If there isn't one it is reported as
+	/// `StreamEvent::EndReadFailed`; see `InStream::poll_event()`.
+	fn drop(&mut self) {
+		// An earlier error has already been returned by `end_read()`.
+		if self.error.is_some() || !self.read_started {
+			return;
+		}
+		if let Err(e) = self.end_read() {
+			unsafe { self.report_commit_error(e); }
+		}
 	}
 }
@@ -20,6 +20,7 @@ use std::cmp::min;
 /// };
 /// ```
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ChannelLayout {
 	/// The name of the layout. This is mostly useful when enumerating built-in layouts.
 	pub name: String,
@@ -116,7 +117,7 @@ impl ChannelLayout {
 	/// let preferred_layouts = vec![ChannelLayout::get_builtin(ChannelLayoutId::Stereo),
 	///                              ChannelLayout::get_builtin(ChannelLayoutId::Mono)];
 	///
-	/// let available_layouts = my_device.layouts();
+	/// let available_layouts = my_device.layouts()?;
 	///
 	/// let best_layout = ChannelLayout::best_matching_channel_layout(preferred_layouts, available_layouts);
 	///
@@ -48,7 +48,10 @@
 //! how ALSA's undocumented and convoluted `.asoundrc` config systems works just to have your app use
 //! a different sound card.
 //! 
-//! To let the user select the output device you can make use of `Context::input_devices()` and `Context::output_devices()`.
+//! To let the user select the output device you can make use of `Context::input_devices()` and `Context::output_devices()`,
+//! or `Context::devices()` to find the devices that meet your requirements, best first.
+//! To remember their choice, save the device's `DeviceKey` and look it up again next time with
+//! `Context::find_device()`. With the `serde` feature enabled, `DeviceKey` can be serialized.
 //!
 //! Onces the device has been opened, you can query it for supported formats and sample rates.
 //!
@@ -72,6 +75,8 @@
 //! # }
 //! ```
 //!
+//! `Device::capabilities()` returns all of this as a `DeviceInfo`, which can be stored or logged.
+//!
 //! If all is well we can open an input or output stream. You can only open an input stream on an input
 //! device, and an output stream on an output device. If a physical device supports input and output it
 //! is split into two `Device` instances, with different `Device::aim()`s but the same `Device::id()`.
@@ -131,9 +136,30 @@
 //! input_stream.start()?;
 //! ```
 //!
-//! There are some extra details regarding `Context::wait_events()` and `Context::wakeup()`, and you
-//! will likely want to use scoped threads via the `crossbeam` crate for those. The best way to learn
-//! more is to see the examples.
+//! There are some extra details regarding `Context::wait_events()` and `ContextWaker`, which wakes
+//! it up from another thread. `Context`, `Device` and the streams can all be moved to other threads;
+//! see `Context` for the details. The best way to learn more is to see the examples.
+//!
+//! # Diagnostics
+//!
+//! soundio-rs never prints anything. Events that would otherwise go unreported, such as underflows
+//! when you didn't provide an underflow callback, are queued without blocking and can be retrieved
+//! with `OutStream::poll_event()` and `InStream::poll_event()`. Messages from JACK and real-time
+//! priority warnings go to the handler set with `set_message_handler()`. If the `log` feature is
+//! enabled all of these are also logged using the `log` crate.
+//!
+//! With the `async` feature, device changes, backend disconnects and stream events are also
+//! available as futures `Stream`s; see `Context::events()`, `OutStream::events()` and
+//! `InStream::events()`. `Context::wait_events_async()` waits for events without blocking.
+//!
+//! # Offline Rendering and Testing
+//!
+//! `OfflineOutStream` calls a write callback without a device, to render its output to a `Vec`, a
+//! raw PCM file or a WAV file, so the same callback can be used live and for exporting audio.
+//!
+//! With the `mock` feature, `MockContext` provides fake devices whose streams pass the usual
+//! `OutStreamWriter` and `InStreamReader` to your callbacks, but only when you step them, so your
+//! callbacks can be unit tested deterministically without a sound card.
 //!
 //! # Examples
 //!
@@ -165,10 +191,22 @@
 //! Bugs, suggestions and praise are welcome!
 
 extern crate libsoundio_sys as raw;
+#[cfg(feature = "log")]
+#[macro_use]
+extern crate log;
+#[cfg(feature = "async")]
+extern crate futures;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
 
 mod types;
 mod context;
+mod context_lock;
 mod device;
+mod device_key;
+mod device_query;
+mod device_info;
 mod instream;
 mod outstream;
 mod util;
@@ -178,10 +216,32 @@ mod channels;
 mod backend;
 mod format;
 mod sample;
+mod ring_buffer;
+mod panic_policy;
+mod channel_view;
+mod blocking_outstream;
+mod blocking_instream;
+mod stream_builder;
+mod messages;
+mod stream_event;
+mod supervised_stream;
+mod following_stream;
+mod device_watcher;
+mod event_loop;
+mod memory_stream;
+mod offline;
+mod wav;
+#[cfg(feature = "async")]
+mod async_events;
+#[cfg(feature = "mock")]
+mod mock;
 
 pub use self::types::*;
 pub use self::context::*;
 pub use self::device::*;
+pub use self::device_key::*;
+pub use self::device_query::*;
+pub use self::device_info::*;
 pub use self::instream::*;
 pub use self::outstream::*;
 pub use self::layout::*;
@@ -190,6 +250,23 @@ pub use self::channels::*;
 pub use self::backend::*;
 pub use self::format::*;
 pub use self::sample::*;
+pub use self::ring_buffer::*;
+pub use self::panic_policy::*;
+pub use self::channel_view::*;
+pub use self::blocking_outstream::*;
+pub use self::blocking_instream::*;
+pub use self::stream_builder::*;
+pub use self::messages::*;
+pub use self::stream_event::*;
+pub use self::supervised_stream::*;
+pub use self::following_stream::*;
+pub use self::device_watcher::*;
+pub use self::event_loop::*;
+pub use self::offline::*;
+#[cfg(feature = "async")]
+pub use self::async_events::*;
+#[cfg(feature = "mock")]
+pub use self::mock::*;
 
 use self::util::*;
 
@@ -0,0 +1,311 @@
+extern crate libsoundio_sys as raw;
+
+use super::error::*;
+use super::format::*;
+use super::layout::*;
+use super::sample::*;
+use super::outstream::*;
+#[cfg(feature = "mock")]
+use super::instream::*;
+
+#[cfg(feature = "mock")]
+use std::collections::VecDeque;
+use std::os::raw::c_int;
+use std::ptr;
+use std::iter;
+
+// Streams that aren't opened with libsoundio, such as `OfflineOutStream` and `MockOutStream`, still
+// pass the usual `OutStreamWriter` and `InStreamReader` to their callbacks. Those read the stream's
+// format, layout and so on from a raw stream struct, which is made here, and get their buffers from
+// a `MemoryOutBuffer` or `MemoryInBuffer` instead of libsoundio. The functions that stand in for
+// libsoundio's return its error codes so that the writer and reader can treat them the same.
+
+// A raw output stream struct with the given parameters. None of its callbacks are called.
+pub(crate) fn raw_outstream(format: Format, sample_rate: i32, layout: ChannelLayout, software_latency: f64) -> Box<raw::SoundIoOutStream> {
+	let channel_count = layout.channels.len();
+	Box::new(raw::SoundIoOutStream {
+		device: ptr::null_mut(),
+		format: format.into(),
+		sample_rate: sample_rate,
+		layout: layout.into(),
+		software_latency: software_latency,
+		volume: 1.0,
+		userdata: ptr::null_mut(),
+		write_callback: outstream_write_callback,
+		underflow_callback: None,
+		error_callback: None,
+		name: ptr::null(),
+		non_terminal_hint: 0,
+		bytes_per_frame: format.bytes_per_frame(channel_count) as _,
+		bytes_per_sample: format.bytes_per_sample() as _,
+		layout_error: 0,
+	})
+}
+
+// A raw input stream struct with the given parameters. None of its callbacks are called.
+#[cfg(feature = "mock")]
+pub(crate) fn raw_instream(format: Format, sample_rate: i32, layout: ChannelLayout, software_latency: f64) -> Box<raw::SoundIoInStream> {
+	let channel_count = layout.channels.len();
+	Box::new(raw::SoundIoInStream {
+		device: ptr::null_mut(),
+		format: format.into(),
+		sample_rate: sample_rate,
+		layout: layout.into(),
+		software_latency: software_latency,
+		userdata: ptr::null_mut(),
+		read_callback: instream_read_callback,
+		overflow_callback: None,
+		error_callback: None,
+		name: ptr::null(),
+		non_terminal_hint: 0,
+		bytes_per_frame: format.bytes_per_frame(channel_count) as _,
+		bytes_per_sample: format.bytes_per_sample() as _,
+		layout_error: 0,
+	})
+}
+
+// The channel areas for interleaved frames starting at `ptr`.
+fn interleaved_areas(areas: &mut [raw::SoundIoChannelArea], ptr: *mut u8, bytes_per_sample: usize, bytes_per_frame: usize) {
+	for (c, area) in areas.iter_mut().enumerate() {
+		area.ptr = ptr.wrapping_offset((c * bytes_per_sample) as isize) as *mut _;
+		area.step = bytes_per_frame as _;
+	}
+}
+
+// The buffer of an output stream that keeps everything that is written, interleaved in the
+// stream's format.
+pub(crate) struct MemoryOutBuffer {
+	bytes_per_sample: usize,
+	bytes_per_frame: usize,
+	software_latency: f64,
+	areas: Vec<raw::SoundIoChannelArea>,
+	// One frame of silence.
+	silence: Vec<u8>,
+	// The frames of the write in progress.
+	pending: Vec<u8>,
+	// The number of frames that can still be written in the current callback.
+	frames_left: usize,
+	// Everything that has been committed.
+	pub written: Vec<u8>,
+	// Errors to return from the next `begin_write()` or `end_write()`.
+	pub fail_begin_write: Option<Error>,
+	pub fail_end_write: Option<Error>,
+	// Errors from the implicit commit of a dropped `OutStreamWriter`, which are reported after the
+	// callback returns.
+	pub commit_errors: Vec<Error>,
+}
+
+impl MemoryOutBuffer {
+	pub fn new(format: Format, channel_count: usize, software_latency: f64) -> MemoryOutBuffer {
+		let bytes_per_sample = format.bytes_per_sample();
+		// Silence isn't zero for unsigned formats so let the sample conversion handle it.
+		let mut silence = vec![0u8; format.bytes_per_frame(channel_count)];
+		unsafe {
+			write_samples(format, silence.as_mut_ptr(), bytes_per_sample, iter::repeat(0.0f32).take(channel_count));
+		}
+		MemoryOutBuffer {
+			bytes_per_sample: bytes_per_sample,
+			bytes_per_frame: silence.len(),
+			software_latency: software_latency,
+			areas: vec![raw::SoundIoChannelArea { ptr: ptr::null_mut(), step: 0 }; channel_count],
+			silence: silence,
+			pending: Vec::new(),
+			frames_left: 0,
+			written: Vec::new(),
+			fail_begin_write: None,
+			fail_end_write: None,
+			commit_errors: Vec::new(),
+		}
+	}
+
+	// The number of whole frames that have been committed.
+	pub fn written_frames(&self) -> usize {
+		self.written.len() / self.bytes_per_frame
+	}
+
+	// Get ready for a call of the write callback, which may write up to `frame_count_max` frames.
+	pub fn start_callback(&mut self, frame_count_max: usize) {
+		self.frames_left = frame_count_max;
+	}
+
+	// Add `frame_count` frames of silence to what has been committed.
+	pub fn write_silence(&mut self, frame_count: usize) {
+		for _ in 0..frame_count {
+			self.written.extend_from_slice(&self.silence);
+		}
+	}
+
+	// Stands in for `soundio_outstream_begin_write()`. Samples that aren't written are silent.
+	pub unsafe fn begin_write(&mut self, areas: *mut *mut raw::SoundIoChannelArea, frame_count: *mut c_int) -> c_int {
+		if let Some(e) = self.fail_begin_write.take() {
+			return e.into();
+		}
+		let requested = *frame_count as usize;
+		if requested > self.frames_left {
+			return Error::Invalid.into();
+		}
+		self.pending.clear();
+		for _ in 0..requested {
+			self.pending.extend_from_slice(&self.silence);
+		}
+		interleaved_areas(&mut self.areas, self.pending.as_mut_ptr(), self.bytes_per_sample, self.bytes_per_frame);
+		*areas = self.areas.as_mut_ptr();
+		0
+	}
+
+	// Stands in for `soundio_outstream_end_write()`.
+	pub fn end_write(&mut self) -> c_int {
+		let frames = self.pending.len() / self.bytes_per_frame;
+		self.frames_left -= frames;
+		if let Some(e) = self.fail_end_write.take() {
+			self.pending.clear();
+			return e.into();
+		}
+		self.written.extend_from_slice(&self.pending);
+		self.pending.clear();
+		0
+	}
+
+	// Stands in for `soundio_outstream_get_latency()`.
+	pub fn latency(&self) -> f64 {
+		self.software_latency
+	}
+}
+
+// A chunk of input for a `MemoryInBuffer`: interleaved frames in the stream's format, or a hole
+// where frames were lost.
+#[cfg(feature = "mock")]
+enum InputChunk {
+	Frames(Vec<u8>),
+	Hole(usize),
+}
+
+// The buffer of an input stream, which holds the input that hasn't been read yet.
+#[cfg(feature = "mock")]
+pub(crate) struct MemoryInBuffer {
+	bytes_per_sample: usize,
+	bytes_per_frame: usize,
+	software_latency: f64,
+	areas: Vec<raw::SoundIoChannelArea>,
+	input: VecDeque<InputChunk>,
+	// The number of frames of the first chunk that have been read.
+	offset: usize,
+	// The number of frames of the read in progress.
+	reading: usize,
+	// The number of frames that can still be read in the current callback.
+	frames_left: usize,
+	// The total number of frames that have been read.
+	pub frames_read: usize,
+	// Errors to return from the next `begin_read()` or `end_read()`.
+	pub fail_begin_read: Option<Error>,
+	pub fail_end_read: Option<Error>,
+	// Errors from the implicit end of a dropped `InStreamReader`, which are reported after the
+	// callback returns.
+	pub commit_errors: Vec<Error>,
+}
+
+#[cfg(feature = "mock")]
+impl MemoryInBuffer {
+	pub fn new(format: Format, channel_count: usize, software_latency: f64) -> MemoryInBuffer {
+		MemoryInBuffer {
+			bytes_per_sample: format.bytes_per_sample(),
+			bytes_per_frame: format.bytes_per_frame(channel_count),
+			software_latency: software_latency,
+			areas: vec![raw::SoundIoChannelArea { ptr: ptr::null_mut(), step: 0 }; channel_count],
+			input: VecDeque::new(),
+			offset: 0,
+			reading: 0,
+			frames_left: 0,
+			frames_read: 0,
+			fail_begin_read: None,
+			fail_end_read: None,
+			commit_errors: Vec::new(),
+		}
+	}
+
+	// Add interleaved frames in the stream's format. Any partial frame at the end is ignored.
+	pub fn push_frames(&mut self, mut data: Vec<u8>) {
+		let len = data.len() - data.len() % self.bytes_per_frame;
+		data.truncate(len);
+		if !data.is_empty() {
+			self.input.push_back(InputChunk::Frames(data));
+		}
+	}
+
+	// Add a hole of `frames` frames.
+	pub fn push_hole(&mut self, frames: usize) {
+		if frames > 0 {
+			self.input.push_back(InputChunk::Hole(frames));
+		}
+	}
+
+	// The number of frames that haven't been read.
+	pub fn available_frames(&self) -> usize {
+		let total: usize = self.input.iter().map(|chunk| self.chunk_frames(chunk)).sum();
+		total - self.offset
+	}
+
+	fn chunk_frames(&self, chunk: &InputChunk) -> usize {
+		match *chunk {
+			InputChunk::Frames(ref data) => data.len() / self.bytes_per_frame,
+			InputChunk::Hole(frames) => frames,
+		}
+	}
+
+	// Get ready for a call of the read callback, which may read up to `frame_count_max` frames.
+	pub fn start_callback(&mut self, frame_count_max: usize) {
+		self.frames_left = frame_count_max;
+	}
+
+	// Stands in for `soundio_instream_begin_read()`. Like libsoundio it returns fewer frames than
+	// requested at the end of a chunk, and no frames if there is no input.
+	pub unsafe fn begin_read(&mut self, areas: *mut *mut raw::SoundIoChannelArea, frame_count: *mut c_int) -> c_int {
+		if let Some(e) = self.fail_begin_read.take() {
+			return e.into();
+		}
+		let requested = *frame_count as usize;
+		if requested > self.frames_left {
+			return Error::Invalid.into();
+		}
+		let (frames, ptr) = match self.input.front_mut() {
+			None => (0, ptr::null_mut()),
+			Some(&mut InputChunk::Hole(frames)) => (frames - self.offset, ptr::null_mut()),
+			Some(&mut InputChunk::Frames(ref mut data)) => (
+				data.len() / self.bytes_per_frame - self.offset,
+				data.as_mut_ptr().wrapping_offset((self.offset * self.bytes_per_frame) as isize),
+			),
+		};
+		self.reading = if frames < requested { frames } else { requested };
+		*frame_count = self.reading as _;
+		if ptr.is_null() {
+			*areas = ptr::null_mut();
+		} else {
+			interleaved_areas(&mut self.areas, ptr, self.bytes_per_sample, self.bytes_per_frame);
+			*areas = self.areas.as_mut_ptr();
+		}
+		0
+	}
+
+	// Stands in for `soundio_instream_end_read()`.
+	pub fn end_read(&mut self) -> c_int {
+		let frames = self.reading;
+		self.reading = 0;
+		self.frames_left -= frames;
+		if let Some(e) = self.fail_end_read.take() {
+			return e.into();
+		}
+		self.frames_read += frames;
+		self.offset += frames;
+		let front_frames = self.input.front().map_or(0, |chunk| self.chunk_frames(chunk));
+		if front_frames > 0 && self.offset == front_frames {
+			self.input.pop_front();
+			self.offset = 0;
+		}
+		0
+	}
+
+	// Stands in for `soundio_instream_get_latency()`.
+	pub fn latency(&self) -> f64 {
+		self.software_latency
+	}
+}
@@ -0,0 +1,174 @@
+extern crate libsoundio_sys as raw;
+
+use std::ffi::CStr;
+use std::panic;
+use std::sync::{Mutex, RwLock};
+use std::os::raw::c_char;
+
+/// A diagnostic message from libsoundio or JACK that isn't associated with a particular `Context`.
+///
+/// These are passed to the handler set with `set_message_handler()`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Message {
+	/// An informational message from JACK.
+	JackInfo(String),
+	/// An error message from JACK.
+	JackError(String),
+	/// libsoundio failed to give a thread real-time priority. This usually means the system
+	/// isn't configured to allow it, and the audio may glitch under load.
+	RtPrioWarning,
+}
+
+type MessageHandler = Box<Fn(Message) + Send + Sync>;
+
+// The process-wide message handler. The messages can't be routed to a particular `Context`
+// because JACK's info and error functions are global, and none of these callbacks have a
+// userdata pointer.
+static MESSAGE_HANDLER: RwLock<Option<MessageHandler>> = RwLock::new(None);
+
+// libsoundio's default real-time priority warning function, which prints a message to stderr
+// the first time it is called. It is used when there is no message handler.
+static DEFAULT_RTPRIO_WARNING: Mutex<Option<extern fn()>> = Mutex::new(None);
+
+/// Set the handler for diagnostic messages from libsoundio and JACK. There is one handler for the
+/// whole process, and it applies to every `Context`, including ones that already exist.
+///
+/// It may be called from any thread, including audio threads, so it should be quick. Panics in the
+/// handler are caught and ignored.
+///
+/// Without a handler the messages are logged if the `log` feature is enabled. Otherwise JACK
+/// messages are discarded and the real-time priority warning is printed to stderr once by
+/// libsoundio.
+///
+/// # Examples
+///
+/// ```
+/// soundio::set_message_handler(|msg| match msg {
+///     soundio::Message::JackInfo(s) => eprintln!("JACK: {}", s),
+///     soundio::Message::JackError(s) => eprintln!("JACK error: {}", s),
+///     soundio::Message::RtPrioWarning => eprintln!("Couldn't set real-time priority"),
+/// });
+/// ```
+pub fn set_message_handler<F>(handler: F)
+	where F: Fn(Message) + Send + Sync + 'static {
+
+	let mut current = match MESSAGE_HANDLER.write() {
+		Ok(current) => current,
+		Err(poisoned) => poisoned.into_inner(),
+	};
+	*current = Some(Box::new(handler));
+}
+
+/// Remove the handler set with `set_message_handler()`, restoring the default behaviour.
+pub fn clear_message_handler() {
+	let mut current = match MESSAGE_HANDLER.write() {
+		Ok(current) => current,
+		Err(poisoned) => poisoned.into_inner(),
+	};
+	*current = None;
+}
+
+// Pass a message to the handler. Returns false if it wasn't handled.
+fn dispatch(message: Message) -> bool {
+	let current = match MESSAGE_HANDLER.read() {
+		Ok(current) => current,
+		Err(poisoned) => poisoned.into_inner(),
+	};
+	match *current {
+		Some(ref handler) => {
+			// Unwinding into C is undefined behaviour.
+			let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| handler(message)));
+			true
+		},
+		None => log_message(message),
+	}
+}
+
+// Log a message when there is no handler. Returns false if the `log` feature isn't enabled.
+#[cfg(feature = "log")]
+fn log_message(message: Message) -> bool {
+	match message {
+		Message::JackInfo(s) => info!("JACK: {}", s),
+		Message::JackError(s) => error!("JACK: {}", s),
+		Message::RtPrioWarning => warn!("soundio: unable to set real-time thread priority"),
+	}
+	true
+}
+
+#[cfg(not(feature = "log"))]
+fn log_message(_: Message) -> bool {
+	false
+}
+
+fn message_to_string(msg: *const c_char) -> String {
+	if msg.is_null() {
+		return String::new();
+	}
+	unsafe { CStr::from_ptr(msg).to_string_lossy().into_owned() }
+}
+
+extern fn jack_info_callback(msg: *const c_char) {
+	dispatch(Message::JackInfo(message_to_string(msg)));
+}
+
+extern fn jack_error_callback(msg: *const c_char) {
+	dispatch(Message::JackError(message_to_string(msg)));
+}
+
+extern fn emit_rtprio_warning() {
+	if dispatch(Message::RtPrioWarning) {
+		return;
+	}
+	let default = match DEFAULT_RTPRIO_WARNING.lock() {
+		Ok(default) => *default,
+		Err(_) => None,
+	};
+	if let Some(default) = default {
+		default();
+	}
+}
+
+// Route a newly created context's messages to the message handler.
+pub(crate) fn install_message_callbacks(soundio: *mut raw::SoundIo) {
+	unsafe {
+		if let Some(default) = (*soundio).emit_rtprio_warning {
+			if let Ok(mut saved) = DEFAULT_RTPRIO_WARNING.lock() {
+				if saved.is_none() {
+					*saved = Some(default);
+				}
+			}
+		}
+		(*soundio).emit_rtprio_warning = Some(emit_rtprio_warning);
+		(*soundio).jack_info_callback = Some(jack_info_callback);
+		(*soundio).jack_error_callback = Some(jack_error_callback);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::ffi::CString;
+	use std::sync::Arc;
+
+	#[test]
+	fn messages_are_dispatched() {
+		let received = Arc::new(Mutex::new(Vec::new()));
+		let sink = received.clone();
+		set_message_handler(move |msg| sink.lock().unwrap().push(msg));
+
+		let info = CString::new("info").unwrap();
+		let error = CString::new("error").unwrap();
+		jack_info_callback(info.as_ptr());
+		jack_error_callback(error.as_ptr());
+		emit_rtprio_warning();
+
+		clear_message_handler();
+		jack_info_callback(info.as_ptr());
+
+		assert_eq!(*received.lock().unwrap(), vec![
+			Message::JackInfo("info".to_string()),
+			Message::JackError("error".to_string()),
+			Message::RtPrioWarning,
+		]);
+	}
+}
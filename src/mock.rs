@@ -0,0 +1,908 @@
+extern crate libsoundio_sys as raw;
+
+use super::types::*;
+use super::error::*;
+use super::format::*;
+use super::layout::*;
+use super::sample::*;
+use super::device_key::*;
+use super::device_info::*;
+use super::outstream::*;
+use super::instream::*;
+use super::stream_event::*;
+use super::memory_stream::*;
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// `MockContext` stands in for `Context` in unit tests. Instead of connecting to a backend you add
+/// fake devices to it with the formats, layouts and sample rates you choose, and the streams opened
+/// on them don't run on an audio thread. Their callbacks are only called when you call
+/// `MockOutStream::step()` or `MockInStream::step()`, with exactly the `frame_count_min` and
+/// `frame_count_max` you give, so tests are deterministic.
+///
+/// The callbacks are passed the same `OutStreamWriter` and `InStreamReader` as real streams, so the
+/// code under test doesn't need to know that it is being mocked. Anything that is written is kept
+/// so that it can be checked, and input is supplied using `MockInStream::push_input()`.
+///
+/// `MockContext` and `MockDevice` are separate types from `Context` and `Device`, with methods of
+/// the same names; there is no trait that both implement. Code that finds devices and opens streams
+/// itself therefore can't be passed a mock as it is. Write it against a trait of your own, or test
+/// the callbacks with the mock and the rest with the dummy backend (`Backend::Dummy`).
+///
+/// It is only available with the `mock` feature enabled.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "mock")]
+/// # fn main() {
+/// let mut ctx = soundio::MockContext::new();
+/// let dev = ctx.add_device(soundio::DeviceInfo {
+///     id: "mock".to_string(),
+///     name: "Mock Output".to_string(),
+///     aim: soundio::DeviceAim::Output,
+///     is_raw: false,
+///     layouts: vec![soundio::ChannelLayout::get_builtin(soundio::ChannelLayoutId::Stereo)],
+///     current_layout: soundio::ChannelLayout::get_builtin(soundio::ChannelLayoutId::Stereo),
+///     formats: vec![soundio::Format::S16LE],
+///     current_format: soundio::Format::S16LE,
+///     sample_rates: vec![soundio::SampleRateRange { min: 48000, max: 48000 }],
+///     current_sample_rate: 48000,
+///     software_latency: soundio::SoftwareLatency { min: 0.001, max: 1.0, current: 0.01 },
+///     probe_error: None,
+/// });
+///
+/// let mut stream = dev.open_outstream(
+///     48000,
+///     soundio::Format::S16LE,
+///     soundio::ChannelLayout::get_builtin(soundio::ChannelLayoutId::Stereo),
+///     0.01,
+///     |writer: &mut soundio::OutStreamWriter| {
+///         let frames = writer.frame_count_max();
+///         writer.begin_write(frames).unwrap();
+///         for c in 0..writer.channel_count() {
+///             for f in 0..writer.frame_count() {
+///                 writer.set_sample(c, f, 0.5f32);
+///             }
+///         }
+///     },
+///     None::<fn()>,
+///     None::<fn(soundio::Error)>,
+/// ).expect("Couldn't open stream");
+///
+/// stream.start().unwrap();
+/// assert_eq!(stream.step(0, 4), 4);
+/// assert_eq!(stream.samples::<f32>().len(), 8);
+/// # }
+/// # #[cfg(not(feature = "mock"))]
+/// # fn main() {}
+/// ```
+pub struct MockContext {
+	inputs: Vec<MockDevice>,
+	outputs: Vec<MockDevice>,
+}
+
+impl MockContext {
+	/// Create a context with no devices.
+	pub fn new() -> MockContext {
+		MockContext {
+			inputs: Vec::new(),
+			outputs: Vec::new(),
+		}
+	}
+
+	/// Add a fake device described by `info`, and return it. The first input and the first output
+	/// device that are added are the defaults.
+	///
+	/// Set `info.probe_error` to mock a device that couldn't be probed. Its other capabilities are
+	/// then ignored.
+	pub fn add_device(&mut self, info: DeviceInfo) -> MockDevice {
+		let device = MockDevice {
+			info: Arc::new(info),
+		};
+		match device.aim() {
+			DeviceAim::Input => self.inputs.push(device.clone()),
+			DeviceAim::Output => self.outputs.push(device.clone()),
+		}
+		device
+	}
+
+	/// See `Context::input_device_count()`.
+	pub fn input_device_count(&self) -> usize {
+		self.inputs.len()
+	}
+
+	/// See `Context::output_device_count()`.
+	pub fn output_device_count(&self) -> usize {
+		self.outputs.len()
+	}
+
+	/// See `Context::input_device()`. It returns `Error::OpeningDevice` if the index is out of
+	/// bounds.
+	pub fn input_device(&self, index: usize) -> Result<MockDevice> {
+		self.inputs.get(index).cloned().ok_or(Error::OpeningDevice)
+	}
+
+	/// See `Context::output_device()`. It returns `Error::OpeningDevice` if the index is out of
+	/// bounds.
+	pub fn output_device(&self, index: usize) -> Result<MockDevice> {
+		self.outputs.get(index).cloned().ok_or(Error::OpeningDevice)
+	}
+
+	/// See `Context::input_devices()`.
+	pub fn input_devices(&self) -> Result<Vec<MockDevice>> {
+		Ok(self.inputs.clone())
+	}
+
+	/// See `Context::output_devices()`.
+	pub fn output_devices(&self) -> Result<Vec<MockDevice>> {
+		Ok(self.outputs.clone())
+	}
+
+	/// See `Context::default_input_device_index()`.
+	pub fn default_input_device_index(&self) -> Option<usize> {
+		if self.inputs.is_empty() { None } else { Some(0) }
+	}
+
+	/// See `Context::default_output_device_index()`.
+	pub fn default_output_device_index(&self) -> Option<usize> {
+		if self.outputs.is_empty() { None } else { Some(0) }
+	}
+
+	/// See `Context::default_input_device()`.
+	pub fn default_input_device(&self) -> Result<MockDevice> {
+		let device = self.inputs.first().cloned().ok_or(Error::NoSuchDevice)?;
+		match device.probe_error() {
+			Some(e) => Err(e),
+			None => Ok(device),
+		}
+	}
+
+	/// See `Context::default_output_device()`.
+	pub fn default_output_device(&self) -> Result<MockDevice> {
+		let device = self.outputs.first().cloned().ok_or(Error::NoSuchDevice)?;
+		match device.probe_error() {
+			Some(e) => Err(e),
+			None => Ok(device),
+		}
+	}
+}
+
+/// A fake device that was added to a `MockContext`. It has the same methods as `Device` for
+/// describing the device and opening streams, which answer from the `DeviceInfo` it was added
+/// with. It doesn't have `sort_channel_layouts()`, the stream builders or the blocking streams.
+/// See `MockContext` for how it relates to `Device`.
+// The methods are copied from `Device` by hand, so they must be kept in step with it.
+#[derive(Debug, Clone)]
+pub struct MockDevice {
+	info: Arc<DeviceInfo>,
+}
+
+impl MockDevice {
+	/// See `Device::id()`.
+	pub fn id(&self) -> String {
+		self.info.id.clone()
+	}
+
+	/// See `Device::name()`.
+	pub fn name(&self) -> String {
+		self.info.name.clone()
+	}
+
+	/// See `Device::aim()`.
+	pub fn aim(&self) -> DeviceAim {
+		self.info.aim
+	}
+
+	/// See `Device::is_raw()`.
+	pub fn is_raw(&self) -> bool {
+		self.info.is_raw
+	}
+
+	/// See `Device::key()`.
+	pub fn key(&self) -> DeviceKey {
+		DeviceKey {
+			id: self.id(),
+			name: self.name(),
+			aim: self.aim(),
+			is_raw: self.is_raw(),
+		}
+	}
+
+	/// See `Device::capabilities()`. This is the `DeviceInfo` the device was added with.
+	pub fn capabilities(&self) -> DeviceInfo {
+		(*self.info).clone()
+	}
+
+	/// See `Device::probe_error()`.
+	pub fn probe_error(&self) -> Option<Error> {
+		self.info.probe_error
+	}
+
+	// Return the probe error, if any, so that the capability accessors can use `?`.
+	fn probed(&self) -> Result<()> {
+		match self.probe_error() {
+			None => Ok(()),
+			Some(e) => Err(e),
+		}
+	}
+
+	/// See `Device::layouts()`.
+	pub fn layouts(&self) -> Result<Vec<ChannelLayout>> {
+		self.probed()?;
+		Ok(self.info.layouts.clone())
+	}
+
+	/// See `Device::current_layout()`.
+	pub fn current_layout(&self) -> Result<ChannelLayout> {
+		self.probed()?;
+		Ok(self.info.current_layout.clone())
+	}
+
+	/// See `Device::formats()`.
+	pub fn formats(&self) -> Result<Vec<Format>> {
+		self.probed()?;
+		Ok(self.info.formats.clone())
+	}
+
+	/// See `Device::current_format()`.
+	pub fn current_format(&self) -> Result<Format> {
+		self.probed()?;
+		Ok(self.info.current_format)
+	}
+
+	/// See `Device::sample_rates()`.
+	pub fn sample_rates(&self) -> Result<Vec<SampleRateRange>> {
+		self.probed()?;
+		Ok(self.info.sample_rates.clone())
+	}
+
+	/// See `Device::current_sample_rate()`.
+	pub fn current_sample_rate(&self) -> Result<i32> {
+		self.probed()?;
+		Ok(self.info.current_sample_rate)
+	}
+
+	/// See `Device::software_latency()`.
+	pub fn software_latency(&self) -> Result<SoftwareLatency> {
+		self.probed()?;
+		Ok(self.info.software_latency)
+	}
+
+	/// See `Device::supports_format()`.
+	pub fn supports_format(&self, format: Format) -> bool {
+		self.probe_error().is_none() && self.info.formats.contains(&format)
+	}
+
+	/// See `Device::supports_layout()`.
+	pub fn supports_layout(&self, layout: ChannelLayout) -> bool {
+		self.probe_error().is_none() && self.info.layouts.contains(&layout)
+	}
+
+	/// See `Device::supports_sample_rate()`.
+	pub fn supports_sample_rate(&self, sample_rate: i32) -> bool {
+		self.probe_error().is_none() && self.info.sample_rates.iter()
+			.any(|range| range.min <= sample_rate && sample_rate <= range.max)
+	}
+
+	/// See `Device::nearest_sample_rate()`. Like libsoundio it prefers rates that are higher
+	/// than `sample_rate` to ones that are lower. If the device has no sample rates it returns -1.
+	pub fn nearest_sample_rate(&self, sample_rate: i32) -> Result<i32> {
+		self.probed()?;
+		let mut best_rate = -1;
+		let mut best_delta = -1;
+		for range in &self.info.sample_rates {
+			let candidate_rate = if sample_rate < range.min {
+				range.min
+			} else if sample_rate > range.max {
+				range.max
+			} else {
+				return Ok(sample_rate);
+			};
+			let delta = (candidate_rate - sample_rate).abs();
+			let best_rate_too_small = best_rate < sample_rate;
+			let candidate_rate_too_small = candidate_rate < sample_rate;
+			if best_rate == -1 ||
+				(best_rate_too_small && !candidate_rate_too_small) ||
+				((best_rate_too_small || !candidate_rate_too_small) && delta < best_delta) {
+				best_rate = candidate_rate;
+				best_delta = delta;
+			}
+		}
+		Ok(best_rate)
+	}
+
+	// Check the parameters of a stream to be opened on this device, and return the software
+	// latency it would have.
+	fn check_stream(&self, aim: DeviceAim, sample_rate: i32, format: Format, layout: &ChannelLayout, latency: f64) -> Result<f64> {
+		if self.aim() != aim || format == Format::Invalid || layout.channels.len() > raw::SOUNDIO_MAX_CHANNELS {
+			return Err(Error::Invalid);
+		}
+		self.probed()?;
+		if !self.supports_sample_rate(sample_rate) || !self.supports_format(format) || !self.supports_layout(layout.clone()) {
+			return Err(Error::IncompatibleDevice);
+		}
+		let range = self.info.software_latency;
+		Ok(if latency == 0.0 {
+			range.current
+		} else if range.max > 0.0 {
+			latency.max(range.min).min(range.max)
+		} else {
+			latency
+		})
+	}
+
+	/// See `Device::open_outstream()`. The stream's callbacks are only called by
+	/// `MockOutStream::step()` and the other `MockOutStream` methods, on the calling thread.
+	///
+	/// If `latency` is 0.0 the stream uses the device's current software latency, otherwise it is
+	/// clamped to the device's range.
+	///
+	/// # Return Values
+	///
+	/// * `Error::Invalid`
+	///   - `aim()` is not `DeviceAim::Output`
+	///   - `format` is not valid
+	///   - the layout has more than `SOUNDIO_MAX_CHANNELS` (24) channels.
+	/// * The device's probe error, if it has one.
+	/// * `Error::IncompatibleDevice` - the device doesn't support `sample_rate`, `format` or `layout`.
+	pub fn open_outstream<'a, WriteCB, UnderflowCB, ErrorCB>(
+				&self,
+				sample_rate: i32,
+				format: Format,
+				layout: ChannelLayout,
+				latency: f64,
+				write_callback: WriteCB,
+				underflow_callback: Option<UnderflowCB>,
+				error_callback: Option<ErrorCB>,
+				) -> Result<MockOutStream<'a>>
+		where
+			WriteCB: 'a + FnMut(&mut OutStreamWriter) + Send,
+			UnderflowCB: 'a + FnMut() + Send,
+			ErrorCB: 'a + FnMut(Error) + Send {
+
+		let latency = self.check_stream(DeviceAim::Output, sample_rate, format, &layout, latency)?;
+		let channel_count = layout.channels.len();
+		Ok(MockOutStream {
+			outstream: raw_outstream(format, sample_rate, layout, latency),
+			buffer: MemoryOutBuffer::new(format, channel_count, latency),
+			write_callback: Box::new(write_callback),
+			underflow_callback: match underflow_callback {
+				Some(cb) => Some(Box::new(cb)),
+				None => None,
+			},
+			error_callback: match error_callback {
+				Some(cb) => Some(Box::new(cb)),
+				None => None,
+			},
+			events: VecDeque::new(),
+			started: false,
+			paused: false,
+			device: self.clone(),
+		})
+	}
+
+	/// See `Device::open_instream()`. The stream's callbacks are only called by
+	/// `MockInStream::step()` and the other `MockInStream` methods, on the calling thread.
+	///
+	/// If `latency` is 0.0 the stream uses the device's current software latency, otherwise it is
+	/// clamped to the device's range.
+	///
+	/// # Return Values
+	///
+	/// * `Error::Invalid`
+	///   - `aim()` is not `DeviceAim::Input`
+	///   - `format` is not valid
+	///   - the layout has more than `SOUNDIO_MAX_CHANNELS` (24) channels.
+	/// * The device's probe error, if it has one.
+	/// * `Error::IncompatibleDevice` - the device doesn't support `sample_rate`, `format` or `layout`.
+	pub fn open_instream<'a, ReadCB, OverflowCB, ErrorCB>(
+				&self,
+				sample_rate: i32,
+				format: Format,
+				layout: ChannelLayout,
+				latency: f64,
+				read_callback: ReadCB,
+				overflow_callback: Option<OverflowCB>,
+				error_callback: Option<ErrorCB>,
+				) -> Result<MockInStream<'a>>
+		where
+			ReadCB: 'a + FnMut(&mut InStreamReader) + Send,
+			OverflowCB: 'a + FnMut() + Send,
+			ErrorCB: 'a + FnMut(Error) + Send {
+
+		let latency = self.check_stream(DeviceAim::Input, sample_rate, format, &layout, latency)?;
+		let channel_count = layout.channels.len();
+		Ok(MockInStream {
+			instream: raw_instream(format, sample_rate, layout, latency),
+			buffer: MemoryInBuffer::new(format, channel_count, latency),
+			read_callback: Box::new(read_callback),
+			overflow_callback: match overflow_callback {
+				Some(cb) => Some(Box::new(cb)),
+				None => None,
+			},
+			error_callback: match error_callback {
+				Some(cb) => Some(Box::new(cb)),
+				None => None,
+			},
+			events: VecDeque::new(),
+			started: false,
+			paused: false,
+			device: self.clone(),
+		})
+	}
+}
+
+/// An output stream opened on a `MockDevice`. It has the same methods as `OutStream` for
+/// controlling the stream, and more for driving its callbacks and checking what was written.
+///
+/// Events that would be queued on an `OutStream` (see `StreamEvent`) are queued here and can be
+/// taken with `poll_event()`. Panics in the callbacks are not caught, so they fail the test.
+pub struct MockOutStream<'a> {
+	outstream: Box<raw::SoundIoOutStream>,
+	buffer: MemoryOutBuffer,
+
+	write_callback: Box<FnMut(&mut OutStreamWriter) + Send + 'a>,
+	underflow_callback: Option<Box<FnMut() + Send + 'a>>,
+	error_callback: Option<Box<FnMut(Error) + Send + 'a>>,
+
+	events: VecDeque<StreamEvent>,
+	started: bool,
+	paused: bool,
+	device: MockDevice,
+}
+
+// The raw stream is only read, by the `OutStreamWriter`s that `step()` creates.
+unsafe impl<'a> Send for MockOutStream<'a> {}
+
+impl<'a> MockOutStream<'a> {
+	/// See `OutStream::start()`. Until it is called `step()` panics.
+	pub fn start(&mut self) -> Result<()> {
+		self.started = true;
+		Ok(())
+	}
+
+	/// See `OutStream::pause()`. While the stream is paused `step()` panics.
+	pub fn pause(&mut self, pause: bool) -> Result<()> {
+		self.paused = pause;
+		Ok(())
+	}
+
+	/// See `OutStream::device()`.
+	pub fn device(&self) -> &MockDevice {
+		&self.device
+	}
+
+	/// See `OutStream::format()`.
+	pub fn format(&self) -> Format {
+		self.outstream.format.into()
+	}
+
+	/// See `OutStream::sample_rate()`.
+	pub fn sample_rate(&self) -> i32 {
+		self.outstream.sample_rate
+	}
+
+	/// See `OutStream::software_latency()`.
+	pub fn software_latency(&self) -> f64 {
+		self.outstream.software_latency
+	}
+
+	/// See `OutStream::bytes_per_frame()`.
+	pub fn bytes_per_frame(&self) -> i32 {
+		self.outstream.bytes_per_frame
+	}
+
+	/// See `OutStream::bytes_per_sample()`.
+	pub fn bytes_per_sample(&self) -> i32 {
+		self.outstream.bytes_per_sample
+	}
+
+	/// See `OutStream::poll_event()`.
+	pub fn poll_event(&mut self) -> Option<StreamEvent> {
+		self.events.pop_front()
+	}
+
+	/// Call the write callback once, as libsoundio would when it needs between `frame_count_min`
+	/// and `frame_count_max` frames. Returns the number of frames that the callback committed.
+	///
+	/// Like a real stream, the callback may call `begin_write()` more than once, but can't write
+	/// more than `frame_count_max` frames in total. If committing a write when the
+	/// `OutStreamWriter` is dropped fails, the error is passed to the error callback, or queued as
+	/// `StreamEvent::EndWriteFailed` if there isn't one.
+	///
+	/// # Panics
+	///
+	/// Panics if the stream hasn't been started, is paused, or `frame_count_min` is greater than
+	/// `frame_count_max`.
+	pub fn step(&mut self, frame_count_min: usize, frame_count_max: usize) -> usize {
+		assert!(self.started && !self.paused, "MockOutStream::step() called on a stream that isn't running");
+		assert!(frame_count_min <= frame_count_max, "frame_count_min is greater than frame_count_max");
+
+		let written_before = self.buffer.written_frames();
+		self.buffer.start_callback(frame_count_max);
+		{
+			let mut writer = OutStreamWriter::with_memory(
+				self.outstream.as_mut() as *mut _,
+				&mut self.buffer as *mut _,
+				frame_count_min,
+				frame_count_max,
+			);
+			(self.write_callback)(&mut writer);
+		}
+		for e in self.buffer.commit_errors.drain(..).collect::<Vec<_>>() {
+			match self.error_callback {
+				Some(ref mut cb) => cb(e),
+				None => self.events.push_back(StreamEvent::EndWriteFailed(e)),
+			}
+		}
+		self.buffer.written_frames() - written_before
+	}
+
+	/// Report an underflow, as libsoundio would if the write callback was too slow. It is passed to
+	/// the underflow callback, or queued as `StreamEvent::Underflow` if there isn't one.
+	pub fn inject_underflow(&mut self) {
+		match self.underflow_callback {
+			Some(ref mut cb) => cb(),
+			None => self.events.push_back(StreamEvent::Underflow),
+		}
+	}
+
+	/// Report a stream error, such as `Error::Streaming`. It is passed to the error callback, or
+	/// queued as `StreamEvent::Error` if there isn't one.
+	pub fn inject_error(&mut self, error: Error) {
+		match self.error_callback {
+			Some(ref mut cb) => cb(error),
+			None => self.events.push_back(StreamEvent::Error(error)),
+		}
+	}
+
+	/// Make the next call of `OutStreamWriter::begin_write()` fail with `error`.
+	pub fn fail_next_begin_write(&mut self, error: Error) {
+		self.buffer.fail_begin_write = Some(error);
+	}
+
+	/// Make the next call of `OutStreamWriter::end_write()`, or the commit when the writer is
+	/// dropped, fail with `error`. The frames of that write are thrown away.
+	pub fn fail_next_end_write(&mut self, error: Error) {
+		self.buffer.fail_end_write = Some(error);
+	}
+
+	/// Everything that has been committed since the stream was opened or `clear()` was called,
+	/// as interleaved frames in the stream's format.
+	pub fn written(&self) -> &[u8] {
+		&self.buffer.written
+	}
+
+	/// The number of frames that have been committed since the stream was opened or `clear()` was
+	/// called.
+	pub fn written_frames(&self) -> usize {
+		self.buffer.written_frames()
+	}
+
+	/// The samples that have been committed since the stream was opened or `clear()` was called,
+	/// interleaved and converted to `T`.
+	pub fn samples<T: Sample>(&self) -> Vec<T> {
+		let format = self.format();
+		let bytes_per_sample = format.bytes_per_sample();
+		let mut samples: Vec<T> = (0..self.buffer.written.len() / bytes_per_sample)
+			.map(|_| T::from_f32(0.0))
+			.collect();
+		unsafe {
+			read_samples(format, self.buffer.written.as_ptr(), bytes_per_sample, samples.iter_mut());
+		}
+		samples
+	}
+
+	/// Forget everything that has been written.
+	pub fn clear(&mut self) {
+		self.buffer.written.clear();
+	}
+}
+
+/// An input stream opened on a `MockDevice`. It has the same methods as `InStream` for
+/// controlling the stream, and more for supplying input and driving its callbacks.
+///
+/// Events that would be queued on an `InStream` (see `StreamEvent`) are queued here and can be
+/// taken with `poll_event()`. Panics in the callbacks are not caught, so they fail the test.
+pub struct MockInStream<'a> {
+	instream: Box<raw::SoundIoInStream>,
+	buffer: MemoryInBuffer,
+
+	read_callback: Box<FnMut(&mut InStreamReader) + Send + 'a>,
+	overflow_callback: Option<Box<FnMut() + Send + 'a>>,
+	error_callback: Option<Box<FnMut(Error) + Send + 'a>>,
+
+	events: VecDeque<StreamEvent>,
+	started: bool,
+	paused: bool,
+	device: MockDevice,
+}
+
+// The raw stream is only read, by the `InStreamReader`s that `step()` creates.
+unsafe impl<'a> Send for MockInStream<'a> {}
+
+impl<'a> MockInStream<'a> {
+	/// See `InStream::start()`. Until it is called `step()` panics.
+	pub fn start(&mut self) -> Result<()> {
+		self.started = true;
+		Ok(())
+	}
+
+	/// See `InStream::pause()`. While the stream is paused `step()` panics.
+	pub fn pause(&mut self, pause: bool) -> Result<()> {
+		self.paused = pause;
+		Ok(())
+	}
+
+	/// See `InStream::device()`.
+	pub fn device(&self) -> &MockDevice {
+		&self.device
+	}
+
+	/// See `InStream::format()`.
+	pub fn format(&self) -> Format {
+		self.instream.format.into()
+	}
+
+	/// See `InStream::sample_rate()`.
+	pub fn sample_rate(&self) -> i32 {
+		self.instream.sample_rate
+	}
+
+	/// See `InStream::software_latency()`.
+	pub fn software_latency(&self) -> f64 {
+		self.instream.software_latency
+	}
+
+	/// See `InStream::bytes_per_frame()`.
+	pub fn bytes_per_frame(&self) -> i32 {
+		self.instream.bytes_per_frame
+	}
+
+	/// See `InStream::bytes_per_sample()`.
+	pub fn bytes_per_sample(&self) -> i32 {
+		self.instream.bytes_per_sample
+	}
+
+	/// See `InStream::poll_event()`.
+	pub fn poll_event(&mut self) -> Option<StreamEvent> {
+		self.events.pop_front()
+	}
+
+	/// Add interleaved samples to the input, converted to the stream's format. Any samples after
+	/// the last whole frame are ignored.
+	pub fn push_input<T: Sample + Copy>(&mut self, samples: &[T]) {
+		let format = self.format();
+		let bytes_per_sample = format.bytes_per_sample();
+		let mut data = vec![0u8; samples.len() * bytes_per_sample];
+		unsafe {
+			write_samples(format, data.as_mut_ptr(), bytes_per_sample, samples.iter().cloned());
+		}
+		self.buffer.push_frames(data);
+	}
+
+	/// Add a hole of `frame_count` frames to the input, where libsoundio would have lost input.
+	/// `InStreamReader::begin_read()` returns the hole separately from the frames around it, and
+	/// `InStreamReader::read_interleaved()` reads it as silence.
+	pub fn push_hole(&mut self, frame_count: usize) {
+		self.buffer.push_hole(frame_count);
+	}
+
+	/// The number of frames of input that haven't been read.
+	pub fn available_frames(&self) -> usize {
+		self.buffer.available_frames()
+	}
+
+	/// The total number of frames that the read callback has read.
+	pub fn frames_read(&self) -> usize {
+		self.buffer.frames_read
+	}
+
+	/// Call the read callback once, as libsoundio would when between `frame_count_min` and
+	/// `frame_count_max` frames can be read. Returns the number of frames that the callback read.
+	///
+	/// The input comes from `push_input()` and `push_hole()`. Like a real stream,
+	/// `InStreamReader::begin_read()` may return fewer frames than requested at the end of a chunk
+	/// of input, so the callback should read in a loop. If ending a read when the `InStreamReader`
+	/// is dropped fails, the error is passed to the error callback, or queued as
+	/// `StreamEvent::EndReadFailed` if there isn't one.
+	///
+	/// # Panics
+	///
+	/// Panics if the stream hasn't been started, is paused, or `frame_count_min` is greater than
+	/// `frame_count_max`.
+	pub fn step(&mut self, frame_count_min: usize, frame_count_max: usize) -> usize {
+		assert!(self.started && !self.paused, "MockInStream::step() called on a stream that isn't running");
+		assert!(frame_count_min <= frame_count_max, "frame_count_min is greater than frame_count_max");
+
+		let read_before = self.buffer.frames_read;
+		self.buffer.start_callback(frame_count_max);
+		{
+			let mut reader = InStreamReader::with_memory(
+				self.instream.as_mut() as *mut _,
+				&mut self.buffer as *mut _,
+				frame_count_min,
+				frame_count_max,
+			);
+			(self.read_callback)(&mut reader);
+		}
+		for e in self.buffer.commit_errors.drain(..).collect::<Vec<_>>() {
+			match self.error_callback {
+				Some(ref mut cb) => cb(e),
+				None => self.events.push_back(StreamEvent::EndReadFailed(e)),
+			}
+		}
+		self.buffer.frames_read - read_before
+	}
+
+	/// Report an overflow, as libsoundio would if the read callback was too slow. It is passed to
+	/// the overflow callback, or queued as `StreamEvent::Overflow` if there isn't one.
+	pub fn inject_overflow(&mut self) {
+		match self.overflow_callback {
+			Some(ref mut cb) => cb(),
+			None => self.events.push_back(StreamEvent::Overflow),
+		}
+	}
+
+	/// Report a stream error, such as `Error::Streaming`. It is passed to the error callback, or
+	/// queued as `StreamEvent::Error` if there isn't one.
+	pub fn inject_error(&mut self, error: Error) {
+		match self.error_callback {
+			Some(ref mut cb) => cb(error),
+			None => self.events.push_back(StreamEvent::Error(error)),
+		}
+	}
+
+	/// Make the next call of `InStreamReader::begin_read()` fail with `error`.
+	pub fn fail_next_begin_read(&mut self, error: Error) {
+		self.buffer.fail_begin_read = Some(error);
+	}
+
+	/// Make the next call of `InStreamReader::end_read()`, or the implicit one when the reader is
+	/// dropped, fail with `error`. The frames of that read are read again by the next one.
+	pub fn fail_next_end_read(&mut self, error: Error) {
+		self.buffer.fail_end_read = Some(error);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn stereo() -> ChannelLayout {
+		ChannelLayout::get_builtin(ChannelLayoutId::Stereo)
+	}
+
+	fn mock_device(ctx: &mut MockContext, aim: DeviceAim) -> MockDevice {
+		ctx.add_device(DeviceInfo {
+			id: "mock".to_string(),
+			name: "Mock".to_string(),
+			aim: aim,
+			is_raw: false,
+			layouts: vec![stereo()],
+			current_layout: stereo(),
+			formats: vec![Format::S16LE, Format::Float32LE],
+			current_format: Format::S16LE,
+			sample_rates: vec![SampleRateRange { min: 44100, max: 48000 }, SampleRateRange { min: 96000, max: 96000 }],
+			current_sample_rate: 48000,
+			software_latency: SoftwareLatency { min: 0.001, max: 1.0, current: 0.01 },
+			probe_error: None,
+		})
+	}
+
+	#[test]
+	fn mock_devices() {
+		let mut ctx = MockContext::new();
+		let output = mock_device(&mut ctx, DeviceAim::Output);
+		assert_eq!(ctx.output_device_count(), 1);
+		assert_eq!(ctx.default_output_device().unwrap().key(), output.key());
+		assert!(ctx.default_input_device().is_err());
+
+		assert_eq!(output.nearest_sample_rate(22050).unwrap(), 44100);
+		assert_eq!(output.nearest_sample_rate(50000).unwrap(), 96000);
+		assert_eq!(output.nearest_sample_rate(100000).unwrap(), 96000);
+
+		match output.open_outstream(22050, Format::S16LE, stereo(), 0.0, |_: &mut OutStreamWriter| {}, None::<fn()>, None::<fn(Error)>) {
+			Err(Error::IncompatibleDevice) => {},
+			x => panic!("Expected Error::IncompatibleDevice, got {:?}", x.err()),
+		}
+		match output.open_instream(48000, Format::S16LE, stereo(), 0.0, |_: &mut InStreamReader| {}, None::<fn()>, None::<fn(Error)>) {
+			Err(Error::Invalid) => {},
+			x => panic!("Expected Error::Invalid, got {:?}", x.err()),
+		}
+	}
+
+	#[test]
+	fn step_outstream() {
+		let mut ctx = MockContext::new();
+		let output = mock_device(&mut ctx, DeviceAim::Output);
+		let mut stream = output.open_outstream(48000, Format::S16LE, stereo(), 0.0, |writer: &mut OutStreamWriter| {
+			let frame_count = writer.frame_count_min();
+			writer.begin_write(frame_count).unwrap();
+			writer.write_interleaved(&vec![1000i16; frame_count * 2]);
+		}, None::<fn()>, None::<fn(Error)>).unwrap();
+		assert_eq!(stream.software_latency(), 0.01);
+
+		stream.start().unwrap();
+		assert_eq!(stream.step(3, 10), 3);
+		assert_eq!(stream.samples::<i16>(), vec![1000i16; 6]);
+
+		stream.fail_next_end_write(Error::Streaming);
+		assert_eq!(stream.step(2, 2), 0);
+		match stream.poll_event() {
+			Some(StreamEvent::EndWriteFailed(Error::Streaming)) => {},
+			x => panic!("Expected EndWriteFailed, got {:?}", x),
+		}
+
+		stream.inject_underflow();
+		match stream.poll_event() {
+			Some(StreamEvent::Underflow) => {},
+			x => panic!("Expected Underflow, got {:?}", x),
+		}
+		assert!(stream.poll_event().is_none());
+	}
+
+	#[test]
+	fn step_instream() {
+		let mut ctx = MockContext::new();
+		let input = mock_device(&mut ctx, DeviceAim::Input);
+		let mut received: Vec<f32> = Vec::new();
+		{
+			let mut stream = input.open_instream(48000, Format::Float32LE, stereo(), 0.0, |reader: &mut InStreamReader| {
+				let mut frames_left = reader.frame_count_max();
+				while frames_left > 0 {
+					let frame_count = reader.begin_read(frames_left).unwrap();
+					if frame_count == 0 {
+						break;
+					}
+					let mut frames = vec![0.0f32; frame_count * 2];
+					reader.read_interleaved(&mut frames);
+					received.extend(frames.iter().step_by(2));
+					reader.end_read().unwrap();
+					frames_left -= frame_count;
+				}
+			}, None::<fn()>, None::<fn(Error)>).unwrap();
+
+			stream.push_input(&[0.25f32, 0.0, 0.5, 0.0]);
+			stream.push_hole(1);
+			stream.push_input(&[0.75f32, 0.0]);
+			stream.start().unwrap();
+			assert_eq!(stream.step(0, 3), 3);
+			assert_eq!(stream.available_frames(), 1);
+			assert_eq!(stream.step(0, 3), 1);
+		}
+		assert_eq!(received, vec![0.25, 0.5, 0.0, 0.75]);
+	}
+
+	#[test]
+	fn sample_across_hole() {
+		let mut ctx = MockContext::new();
+		let input = mock_device(&mut ctx, DeviceAim::Input);
+		let mut received: Vec<i16> = Vec::new();
+		{
+			let mut stream = input.open_instream(48000, Format::S16LE, stereo(), 0.0, |reader: &mut InStreamReader| {
+				let mut frames_left = reader.frame_count_max();
+				while frames_left > 0 {
+					let frame_count = reader.begin_read(frames_left).unwrap();
+					if frame_count == 0 {
+						break;
+					}
+					for f in 0..frame_count {
+						received.push(reader.sample::<i16>(1, f));
+					}
+					reader.end_read().unwrap();
+					frames_left -= frame_count;
+				}
+			}, None::<fn()>, None::<fn(Error)>).unwrap();
+
+			stream.push_input(&[0i16, 100]);
+			stream.push_hole(2);
+			stream.push_input(&[0i16, -100]);
+			stream.start().unwrap();
+			assert_eq!(stream.step(0, 4), 4);
+		}
+		assert_eq!(received, vec![100, 0, 0, -100]);
+	}
+}
@@ -0,0 +1,322 @@
+extern crate libsoundio_sys as raw;
+
+use super::error::*;
+use super::format::*;
+use super::layout::*;
+use super::outstream::*;
+use super::memory_stream::*;
+use super::wav::*;
+
+use std::cmp;
+use std::io;
+use std::io::Write;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// The number of frames requested from the write callback at a time, unless it is changed.
+const DEFAULT_BLOCK_SIZE: usize = 1024;
+
+/// `OfflineOutStream` renders audio using a write callback, without a device. It calls the callback
+/// with the same `OutStreamWriter` as an `OutStream` does, so the code that plays audio live can
+/// also render it to a `Vec`, a raw PCM file or a WAV file, for example to export it or to test it.
+///
+/// The callback is called on the calling thread, as fast as possible or at a simulated rate (see
+/// `set_speed()`), for as many frames as you ask for. Each call asks for exactly one block of
+/// frames: `frame_count_min()` and `frame_count_max()` are both the block size (see
+/// `set_block_size()`), except at the end of a render when fewer frames are left. If the callback
+/// doesn't write them all, the rest are silent.
+///
+/// Panics in the callback are not caught.
+///
+/// # Examples
+///
+/// ```
+/// let mut phase = 0.0f32;
+/// let mut stream = soundio::OfflineOutStream::new(
+///     44100,
+///     soundio::Format::S16LE,
+///     soundio::ChannelLayout::get_builtin(soundio::ChannelLayoutId::Stereo),
+///     move |writer: &mut soundio::OutStreamWriter| {
+///         let frame_count = writer.frame_count_max();
+///         writer.begin_write(frame_count).unwrap();
+///         for f in 0..writer.frame_count() {
+///             let sample = (phase * 2.0 * std::f32::consts::PI).sin() * 0.5;
+///             phase = (phase + 440.0 / 44100.0) % 1.0;
+///             for c in 0..writer.channel_count() {
+///                 writer.set_sample(c, f, sample);
+///             }
+///         }
+///     },
+/// ).expect("Couldn't create stream");
+///
+/// // One second of audio.
+/// let mut file = Vec::new();
+/// stream.render_wav(44100, &mut file).expect("Couldn't write WAV");
+/// ```
+pub struct OfflineOutStream<'a> {
+	outstream: Box<raw::SoundIoOutStream>,
+	buffer: MemoryOutBuffer,
+	write_callback: Box<FnMut(&mut OutStreamWriter) + Send + 'a>,
+
+	block_size: usize,
+	speed: Option<f64>,
+	frames_rendered: usize,
+}
+
+// The raw stream is only read, by the `OutStreamWriter`s that rendering creates.
+unsafe impl<'a> Send for OfflineOutStream<'a> {}
+
+impl<'a> OfflineOutStream<'a> {
+	/// Create a stream that renders `write_callback`'s output with the given sample rate, format
+	/// and channel layout. Any values that libsoundio supports can be used.
+	///
+	/// # Errors
+	///
+	/// * `Error::Invalid`
+	///   - `sample_rate` is not positive
+	///   - `format` is not valid
+	///   - the layout has no channels, or more than `SOUNDIO_MAX_CHANNELS` (24).
+	pub fn new<WriteCB>(sample_rate: i32, format: Format, layout: ChannelLayout, write_callback: WriteCB) -> Result<OfflineOutStream<'a>>
+		where WriteCB: 'a + FnMut(&mut OutStreamWriter) + Send {
+
+		let channel_count = layout.channels.len();
+		if sample_rate <= 0 || format == Format::Invalid || channel_count == 0 || channel_count > raw::SOUNDIO_MAX_CHANNELS {
+			return Err(Error::Invalid);
+		}
+		let latency = DEFAULT_BLOCK_SIZE as f64 / sample_rate as f64;
+		Ok(OfflineOutStream {
+			outstream: raw_outstream(format, sample_rate, layout, latency),
+			buffer: MemoryOutBuffer::new(format, channel_count, latency),
+			write_callback: Box::new(write_callback),
+			block_size: DEFAULT_BLOCK_SIZE,
+			speed: None,
+			frames_rendered: 0,
+		})
+	}
+
+	/// Set the number of frames that the write callback is asked for at a time. The default is
+	/// 1024. The stream's software latency is the duration of one block.
+	///
+	/// # Panics
+	///
+	/// Panics if `block_size` is 0.
+	pub fn set_block_size(&mut self, block_size: usize) {
+		assert!(block_size > 0, "block_size must be positive");
+		let latency = block_size as f64 / self.sample_rate() as f64;
+		self.block_size = block_size;
+		self.outstream.software_latency = latency;
+		self.buffer = MemoryOutBuffer::new(self.format(), self.channel_count(), latency);
+	}
+
+	/// The number of frames that the write callback is asked for at a time.
+	pub fn block_size(&self) -> usize {
+		self.block_size
+	}
+
+	/// Set how fast to render. `None`, the default, renders as fast as possible. `Some(speed)`
+	/// simulates a device that plays `speed` times faster than real time, by sleeping between calls
+	/// of the write callback, so `Some(1.0)` renders in real time. The rate can only be
+	/// slower than this if the callback is slow.
+	///
+	/// # Panics
+	///
+	/// Panics if `speed` is not positive.
+	pub fn set_speed(&mut self, speed: Option<f64>) {
+		if let Some(speed) = speed {
+			assert!(speed > 0.0, "speed must be positive");
+		}
+		self.speed = speed;
+	}
+
+	/// How fast the stream renders. See `set_speed()`.
+	pub fn speed(&self) -> Option<f64> {
+		self.speed
+	}
+
+	/// See `OutStream::format()`.
+	pub fn format(&self) -> Format {
+		self.outstream.format.into()
+	}
+
+	/// See `OutStream::sample_rate()`.
+	pub fn sample_rate(&self) -> i32 {
+		self.outstream.sample_rate
+	}
+
+	/// The channel layout that the stream was created with.
+	pub fn layout(&self) -> ChannelLayout {
+		self.outstream.layout.into()
+	}
+
+	/// The number of channels in the stream's layout.
+	pub fn channel_count(&self) -> usize {
+		self.outstream.layout.channel_count as _
+	}
+
+	/// See `OutStream::software_latency()`. This is the duration of one block.
+	pub fn software_latency(&self) -> f64 {
+		self.outstream.software_latency
+	}
+
+	/// See `OutStream::bytes_per_frame()`.
+	pub fn bytes_per_frame(&self) -> i32 {
+		self.outstream.bytes_per_frame
+	}
+
+	/// See `OutStream::bytes_per_sample()`.
+	pub fn bytes_per_sample(&self) -> i32 {
+		self.outstream.bytes_per_sample
+	}
+
+	/// The total number of frames that have been rendered by this stream.
+	pub fn frames_rendered(&self) -> usize {
+		self.frames_rendered
+	}
+
+	/// Render `frame_count` frames and return them as interleaved samples in the stream's format.
+	pub fn render_to_vec(&mut self, frame_count: usize) -> Vec<u8> {
+		let mut data = Vec::with_capacity(frame_count * self.bytes_per_frame() as usize);
+		self.render_raw(frame_count, &mut data).expect("Writing to a Vec can't fail");
+		data
+	}
+
+	/// Render `frame_count` frames and write them to `writer` as raw interleaved samples in the
+	/// stream's format, with no header. The frames are written as each block is rendered, so
+	/// `writer` should be buffered if it is a file.
+	///
+	/// # Errors
+	///
+	/// Any error from `writer`. Rendering stops at the first one.
+	pub fn render_raw<W: Write>(&mut self, frame_count: usize, mut writer: W) -> io::Result<()> {
+		self.render(frame_count, |data| writer.write_all(data))
+	}
+
+	/// Render `frame_count` frames and write them to `writer` as a WAV file. The frames are written
+	/// as each block is rendered, so `writer` should be buffered if it is a file.
+	///
+	/// WAV files can only hold little endian samples, which must be unsigned if they are 8 bit and
+	/// signed otherwise, so other formats are converted to the WAV format of the same size without
+	/// losing anything. 24 bit samples are packed into 3 bytes. The channel
+	/// layout is saved if its channels are standard WAV speakers in the standard order.
+	///
+	/// # Errors
+	///
+	/// * `io::ErrorKind::InvalidInput` - the frames won't fit in a WAV file, which is limited to 4 GiB.
+	/// * Any error from `writer`. Rendering stops at the first one.
+	pub fn render_wav<W: Write>(&mut self, frame_count: usize, mut writer: W) -> io::Result<()> {
+		let format = self.format();
+		let channel_count = self.channel_count();
+		write_wav_header(&mut writer, format, &self.layout(), self.sample_rate(), frame_count)?;
+		let mut converted = Vec::new();
+		self.render(frame_count, |data| {
+			converted.clear();
+			convert_to_wav(format, data, &mut converted);
+			writer.write_all(&converted)
+		})?;
+		write_wav_padding(&mut writer, format, channel_count, frame_count)
+	}
+
+	// Render `frame_count` frames a block at a time, passing each block to `output`.
+	fn render<F>(&mut self, frame_count: usize, mut output: F) -> io::Result<()>
+		where F: FnMut(&[u8]) -> io::Result<()> {
+
+		let start = Instant::now();
+		let mut frames_done = 0;
+		while frames_done < frame_count {
+			let block_size = cmp::min(self.block_size, frame_count - frames_done);
+			self.render_block(block_size);
+			output(&self.buffer.written)?;
+			self.buffer.written.clear();
+			frames_done += block_size;
+
+			if let Some(speed) = self.speed {
+				let due = Duration::from_secs_f64(frames_done as f64 / (self.sample_rate() as f64 * speed));
+				let elapsed = start.elapsed();
+				if due > elapsed {
+					thread::sleep(due - elapsed);
+				}
+			}
+		}
+		Ok(())
+	}
+
+	// Call the write callback for `block_size` frames, and add them to `buffer.written`.
+	fn render_block(&mut self, block_size: usize) {
+		self.buffer.start_callback(block_size);
+		{
+			let mut writer = OutStreamWriter::with_memory(
+				self.outstream.as_mut() as *mut _,
+				&mut self.buffer as *mut _,
+				block_size,
+				block_size,
+			);
+			(self.write_callback)(&mut writer);
+		}
+		// Committing to memory can't fail.
+		self.buffer.commit_errors.clear();
+
+		let written = self.buffer.written_frames();
+		if written < block_size {
+			self.buffer.write_silence(block_size - written);
+		}
+		self.frames_rendered += block_size;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn stereo() -> ChannelLayout {
+		ChannelLayout::get_builtin(ChannelLayoutId::Stereo)
+	}
+
+	#[test]
+	fn render_blocks() {
+		let mut calls = Vec::new();
+		{
+			let mut stream = OfflineOutStream::new(48000, Format::S16LE, stereo(), |writer: &mut OutStreamWriter| {
+				calls.push((writer.frame_count_min(), writer.frame_count_max()));
+				// Only write the first half of each block.
+				let frame_count = writer.frame_count_max();
+				writer.begin_write(frame_count).unwrap();
+				for f in 0..frame_count / 2 {
+					writer.set_sample(0, f, 1000i16);
+					writer.set_sample(1, f, -1000i16);
+				}
+			}).unwrap();
+			stream.set_block_size(4);
+
+			let data = stream.render_to_vec(10);
+			assert_eq!(data.len(), 10 * 4);
+			assert_eq!(&data[0..8], &[0xE8, 0x03, 0x18, 0xFC, 0xE8, 0x03, 0x18, 0xFC]);
+			assert_eq!(&data[8..16], &[0; 8]);
+			assert_eq!(stream.frames_rendered(), 10);
+		}
+		assert_eq!(calls, vec![(4, 4), (4, 4), (2, 2)]);
+	}
+
+	#[test]
+	fn unsigned_silence() {
+		let mut stream = OfflineOutStream::new(8000, Format::U8, stereo(), |_: &mut OutStreamWriter| {}).unwrap();
+		assert_eq!(stream.render_to_vec(3), vec![0x80; 6]);
+	}
+
+	#[test]
+	fn render_wav() {
+		let mut stream = OfflineOutStream::new(44100, Format::S16BE, stereo(), |writer: &mut OutStreamWriter| {
+			let frame_count = writer.frame_count_max();
+			writer.begin_write(frame_count).unwrap();
+			for f in 0..frame_count {
+				writer.set_sample(0, f, 1i16);
+				writer.set_sample(1, f, -2i16);
+			}
+		}).unwrap();
+
+		let mut file = Vec::new();
+		stream.render_wav(5, &mut file).unwrap();
+		assert_eq!(file.len(), 68 + 5 * 4);
+		assert_eq!(&file[0..4], b"RIFF");
+		assert_eq!(&file[68..72], &[0x01, 0x00, 0xFE, 0xFF]);
+	}
+}
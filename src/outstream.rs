@@ -4,31 +4,53 @@ use super::error::*;
 use super::format::*;
 use super::util::*;
 use super::sample::*;
+use super::device::*;
+use super::panic_policy::*;
+use super::channel_view::*;
+use super::stream_event::*;
+use super::context::*;
+#[cfg(feature = "async")]
+use super::async_events::*;
+use super::memory_stream::*;
 
 use std::ptr;
 use std::os::raw::{c_int, c_double};
 use std::marker::PhantomData;
+use std::cell::Cell;
 use std::slice;
+use std::cmp;
+use std::any::Any;
+use std::ffi::CString;
+use std::sync::Arc;
 
 /// This is called when an outstream needs to be written to. The `OutStreamUserData` struct is obtained
 /// from the stream.userdata, then the user-supplied callback is called with an `OutStreamWriter`
 /// object.
+///
+/// Panics in the callback are caught and handled according to the stream's `PanicPolicy`.
 pub extern fn outstream_write_callback(stream: *mut raw::SoundIoOutStream, frame_count_min: c_int, frame_count_max: c_int) {
 	// Use stream.userdata to get a reference to the OutStreamUserData object.
 	let raw_userdata_pointer = unsafe { (*stream).userdata as *mut OutStreamUserData };
 	let userdata = unsafe { &mut (*raw_userdata_pointer) };
 
-	let mut stream_writer = OutStreamWriter {
-		outstream: userdata.outstream,
-		frame_count_min: frame_count_min as _,
-		frame_count_max: frame_count_max as _,
-		write_started: false,
-		channel_areas: Vec::new(),
-		frame_count: 0,
-		phantom: PhantomData,
+	// Once a callback has panicked we don't call it again.
+	if userdata.panic_state.panicked() {
+		write_silence(userdata.outstream, frame_count_max as _);
+		return;
+	}
+
+	let mut stream_writer = OutStreamWriter::new(userdata.outstream, frame_count_min as _, frame_count_max as _);
+
+	let panicked = {
+		let write_callback = &mut userdata.write_callback;
+		userdata.panic_state.call(|| write_callback(&mut stream_writer))
 	};
 
-	(userdata.write_callback)(&mut stream_writer);
+	if panicked {
+		// Commit whatever was written before the panic.
+		drop(stream_writer);
+		userdata.report_panic();
+	}
 }
 
 pub extern fn outstream_underflow_callback(stream: *mut raw::SoundIoOutStream) {
@@ -36,10 +58,16 @@ pub extern fn outstream_underflow_callback(stream: *mut raw::SoundIoOutStream) {
 	let raw_userdata_pointer = unsafe { (*stream).userdata as *mut OutStreamUserData };
 	let userdata = unsafe { &mut (*raw_userdata_pointer) };
 
+	if userdata.panic_state.panicked() {
+		return;
+	}
+
 	if let Some(ref mut cb) = userdata.underflow_callback {
-		cb();
+		if userdata.panic_state.call(|| cb()) {
+			userdata.report_panic();
+		}
 	} else {
-		println!("Underflow!");
+		userdata.events.send(StreamEvent::Underflow);
 	}
 }
 
@@ -48,10 +76,38 @@ pub extern fn outstream_error_callback(stream: *mut raw::SoundIoOutStream, err:
 	let raw_userdata_pointer = unsafe { (*stream).userdata as *mut OutStreamUserData };
 	let userdata = unsafe { &mut (*raw_userdata_pointer) };
 
+	if userdata.panic_state.panicked() {
+		return;
+	}
+
 	if let Some(ref mut cb) = userdata.error_callback {
-		cb(err.into());
+		// The error callback is not called again to report its own panic.
+		userdata.panic_state.call(|| cb(err.into()));
 	} else {
-		println!("Error: {}", Error::from(err));
+		userdata.events.send(StreamEvent::Error(err.into()));
+	}
+}
+
+// Write silence instead of calling the write callback. This is used after a callback has panicked.
+fn write_silence(outstream: *mut raw::SoundIoOutStream, frame_count_max: usize) {
+	let mut stream_writer = OutStreamWriter::new(outstream, 0, frame_count_max);
+
+	let mut frames_left = frame_count_max;
+	while frames_left > 0 {
+		let frame_count = match stream_writer.begin_write(frames_left) {
+			Ok(0) | Err(_) => return,
+			Ok(x) => x,
+		};
+		// Silence isn't zero for unsigned formats so let the sample conversion handle it.
+		for c in 0..stream_writer.channel_count() {
+			for f in 0..frame_count {
+				stream_writer.set_sample::<f32>(c, f, 0.0);
+			}
+		}
+		if stream_writer.end_write().is_err() {
+			return;
+		}
+		frames_left -= frame_count;
 	}
 }
 
@@ -59,11 +115,24 @@ pub extern fn outstream_error_callback(stream: *mut raw::SoundIoOutStream, err:
 ///
 /// It is obtained from `Device` using `Device::open_outstream()` and
 /// can be started and paused.
+///
+/// `OutStream` is `Send` but not `Sync`, so it can be moved to another thread but only used from one
+/// thread at a time.
 pub struct OutStream<'a> {
 	pub userdata: Box<OutStreamUserData<'a>>,
-	
-	// This is just here to say that OutStream cannot outlive the Device it was created from.
+
+	// The device this stream was opened on. This must be dropped after `userdata` so
+	// that the raw stream is destroyed before the device is unreferenced.
+	pub(crate) device: Device<'a>,
+
+	// The receiving end of `userdata.events`.
+	pub(crate) events: EventReceiver,
+
+	// This is just here to say that OutStream cannot outlive the callbacks it was created with.
 	pub phantom: PhantomData<&'a ()>,
+
+	// This makes OutStream `!Sync`. It is the only field that isn't `Sync`.
+	pub(crate) not_sync: PhantomData<Cell<()>>,
 }
 
 // The callbacks required for an outstream are stored in this object. We also store a pointer
@@ -71,20 +140,121 @@ pub struct OutStream<'a> {
 pub struct OutStreamUserData<'a> {
 	pub outstream: *mut raw::SoundIoOutStream,
 
-	pub write_callback: Box<FnMut(&mut OutStreamWriter) + 'a>,
-	pub underflow_callback: Option<Box<FnMut() + 'a>>,
-	pub error_callback: Option<Box<FnMut(Error) + 'a>>,
+	pub write_callback: Box<FnMut(&mut OutStreamWriter) + Send + 'a>,
+	pub underflow_callback: Option<Box<FnMut() + Send + 'a>>,
+	pub error_callback: Option<Box<FnMut(Error) + Send + 'a>>,
+
+	// What to do if one of the callbacks panics, and the panic payload if it did.
+	pub(crate) panic_state: PanicState,
+
+	// The stream name, if one was set. It is never read, but the raw stream points to it so it
+	// must live as long.
+	#[allow(dead_code)]
+	pub(crate) name: Option<CString>,
+	// Diagnostic events for the control thread. See `StreamEvent`.
+	pub(crate) events: EventSender,
+	// Used to lock the context while the raw stream is destroyed, which unreferences its device.
+	pub(crate) context: Arc<ContextInner<'a>>,
+}
+
+impl<'a> OutStreamUserData<'a> {
+	// Called after a callback has panicked (and the policy isn't `PanicPolicy::Abort`).
+	fn report_panic(&mut self) {
+		if self.panic_state.policy() != PanicPolicy::ReportError {
+			return;
+		}
+		if let Some(ref mut cb) = self.error_callback {
+			self.panic_state.call(|| cb(Error::Streaming));
+		}
+	}
+
+	// Called when the implicit commit of a dropped `OutStreamWriter` fails. It is passed to the
+	// error callback, or queued as an event if there isn't one or a callback has panicked.
+	fn report_commit_error(&mut self, error: Error) {
+		if !self.panic_state.panicked() {
+			if let Some(ref mut cb) = self.error_callback {
+				self.panic_state.call(|| cb(error));
+				return;
+			}
+		}
+		self.events.send(StreamEvent::EndWriteFailed(error));
+	}
 }
 
 impl<'a> Drop for OutStreamUserData<'a> {
 	fn drop(&mut self) {
+		let _guard = self.context.lock();
 		unsafe {
 			raw::soundio_outstream_destroy(self.outstream);
 		}
 	}
 }
 
+// The callbacks are `Send`, and libsoundio's stream functions can be called from any thread
+// except the audio thread. It isn't `Sync` because the stream functions aren't synchronised
+// with each other; see `not_sync`.
+unsafe impl<'a> Send for OutStream<'a> {}
+
+// Nothing can be done with a shared reference to the userdata: the callbacks need `&mut` and the
+// raw stream is only used through a `OutStream`, which isn't `Sync`.
+unsafe impl<'a> Sync for OutStreamUserData<'a> {}
+
 impl<'a> OutStream<'a> {
+	/// Take the next diagnostic event from the stream, or `None` if there aren't any. Events are
+	/// generated on the audio thread for things that would otherwise go unreported, such as
+	/// underflows when there is no underflow callback. See `StreamEvent`.
+	///
+	/// If the `log` feature is enabled the event is also logged.
+	pub fn poll_event(&mut self) -> Option<StreamEvent> {
+		self.events.poll()
+	}
+
+	/// The number of events that were discarded because the event queue was full. Call
+	/// `poll_event()` regularly to avoid this.
+	pub fn dropped_event_count(&self) -> usize {
+		self.events.dropped()
+	}
+
+	/// Return a `Stream` of the stream's diagnostic events, as `Event::Stream`. It takes events
+	/// from the same queue as `poll_event()`. See `StreamEvents`.
+	///
+	/// This requires the `async` feature.
+	#[cfg(feature = "async")]
+	pub fn events(&mut self) -> StreamEvents<'_> {
+		StreamEvents::new(&mut self.events)
+	}
+
+	/// Returns the `Device` that this stream was opened on.
+	pub fn device(&self) -> &Device<'a> {
+		&self.device
+	}
+
+	/// Set what happens if one of the stream's callbacks panics. The default
+	/// is `PanicPolicy::Abort`. See `PanicPolicy` for more information.
+	///
+	/// This can be called at any time, including while the stream is running.
+	pub fn set_panic_policy(&mut self, policy: PanicPolicy) {
+		self.userdata.panic_state.set_policy(policy);
+	}
+
+	/// Returns the current `PanicPolicy`.
+	pub fn panic_policy(&self) -> PanicPolicy {
+		self.userdata.panic_state.policy()
+	}
+
+	/// Returns `true` if one of the stream's callbacks has panicked. After that
+	/// the callbacks are never called again.
+	pub fn has_panicked(&self) -> bool {
+		self.userdata.panic_state.panicked()
+	}
+
+	/// Take the payload of the first panic in one of the stream's callbacks, if
+	/// there was one. You can pass it to `std::panic::resume_unwind()` to continue
+	/// the panic on this thread.
+	pub fn take_panic(&mut self) -> Option<Box<Any + Send>> {
+		self.userdata.panic_state.take()
+	}
+
 	/// Starts the stream, returning `Ok(())` if it started successfully. Once
 	/// started the write callback will be periodically called according to the
 	/// requested latency.
@@ -201,13 +371,41 @@ impl<'a> OutStream<'a> {
 	/// `tlength`.
 	///
 	/// For JACK, this value is always equal to
-	/// `Device::software_latency().current`.
+	/// the current latency given by `Device::software_latency()`.
 	pub fn software_latency(&self) -> f64 {
 		unsafe {
 			(*self.userdata.outstream).software_latency as _
 		}
 	}
 
+	/// The volume of the stream, from 0.0 to 1.0. This defaults to 1.0.
+	pub fn volume(&self) -> f64 {
+		unsafe {
+			(*self.userdata.outstream).volume as _
+		}
+	}
+
+	/// Set the volume of the stream, from 0.0 to 1.0. Values outside that range are clamped.
+	///
+	/// This is only supported by some backends, for example PulseAudio, where it sets the
+	/// volume shown in the system mixer for this stream.
+	///
+	/// # Errors
+	///
+	/// * `Error::IncompatibleBackend` - the backend doesn't support setting the volume.
+	///
+	pub fn set_volume(&mut self, volume: f64) -> Result<()> {
+		let volume = volume.max(0.0).min(1.0);
+		match unsafe { raw::soundio_outstream_set_volume(self.userdata.outstream, volume as c_double) } {
+			0 => {
+				// Not all backends update this.
+				unsafe { (*self.userdata.outstream).volume = volume as _; }
+				Ok(())
+			},
+			x => Err(x.into()),
+		}
+	}
+
 	/// The name of the stream, which defaults to "SoundIoOutStream".
 	///
     /// PulseAudio uses this for the stream name.
@@ -216,7 +414,7 @@ impl<'a> OutStream<'a> {
     /// WASAPI uses this for the session display name.
     /// Must not contain a colon (":").
 	///
-	/// TODO: Currently there is no way to set this.
+	/// It can be set using `OutStreamBuilder::name()`.
 	pub fn name(&self) -> String {
 		unsafe {
 			utf8_to_string((*self.userdata.outstream).name)
@@ -241,8 +439,12 @@ impl<'a> OutStream<'a> {
 
 /// `OutStreamWriter` is passed to the write callback and can be used to write to the stream.
 ///
-/// You start by calling `begin_write()` then you can write the samples. When the `OutStreamWriter``
-/// is dropped the write is committed. An error at that point is written to the console and ignored.
+/// You start by calling `begin_write()` then you can write the samples, and commit them with
+/// `end_write()`. If you don't, the write is committed when the `OutStreamWriter` is dropped,
+/// and an error at that point is passed to the stream's error callback.
+///
+/// Once committing a write has failed the writer can't be used any more: `begin_write()` and
+/// `end_write()` return the same error, which is also returned by `error()`.
 ///
 pub struct OutStreamWriter<'a> {
 	outstream: *mut raw::SoundIoOutStream,
@@ -250,17 +452,82 @@ pub struct OutStreamWriter<'a> {
 	frame_count_max: usize,
 
 	write_started: bool,
+	// The first error from committing a write. After that the writer can't be used.
+	error: Option<Error>,
 
-	// The memory area to write to - one for each channel. Populated after begin_write()
-	channel_areas: Vec<raw::SoundIoChannelArea>,
+	// The memory area to write to - one for each channel. Populated after begin_write(). This is
+	// a fixed size array rather than a `Vec` so that the audio thread never allocates.
+	channel_areas: [raw::SoundIoChannelArea; raw::SOUNDIO_MAX_CHANNELS],
 	// The actual frame count. Populated after begin_write()
 	frame_count: usize,
 
+	// If this is set the buffer is in memory rather than libsoundio's. See `OfflineOutStream` and
+	// `MockOutStream`.
+	memory: *mut MemoryOutBuffer,
+
 	// This cannot outlive the scope that it is spawned from (in the write callback).
 	phantom: PhantomData<&'a ()>,
 }
 
 impl<'a> OutStreamWriter<'a> {
+	// Create a writer for a call of the write callback.
+	fn new(outstream: *mut raw::SoundIoOutStream, frame_count_min: usize, frame_count_max: usize) -> OutStreamWriter<'a> {
+		OutStreamWriter {
+			outstream: outstream,
+			frame_count_min: frame_count_min,
+			frame_count_max: frame_count_max,
+			write_started: false,
+			error: None,
+			channel_areas: [raw::SoundIoChannelArea { ptr: ptr::null_mut(), step: 0 }; raw::SOUNDIO_MAX_CHANNELS],
+			frame_count: 0,
+			memory: ptr::null_mut(),
+			phantom: PhantomData,
+		}
+	}
+
+	// Create a writer that writes to `memory` instead of libsoundio's buffer. `outstream` is only
+	// used for the stream's parameters.
+	pub(crate) fn with_memory(outstream: *mut raw::SoundIoOutStream, memory: *mut MemoryOutBuffer, frame_count_min: usize, frame_count_max: usize) -> OutStreamWriter<'a> {
+		let mut writer = OutStreamWriter::new(outstream, frame_count_min, frame_count_max);
+		writer.memory = memory;
+		writer
+	}
+
+	// `soundio_outstream_begin_write()`, or its stand-in.
+	unsafe fn raw_begin_write(&mut self, areas: *mut *mut raw::SoundIoChannelArea, frame_count: *mut c_int) -> c_int {
+		if !self.memory.is_null() {
+			return (*self.memory).begin_write(areas, frame_count);
+		}
+		raw::soundio_outstream_begin_write(self.outstream, areas, frame_count)
+	}
+
+	// `soundio_outstream_end_write()`, or its stand-in.
+	unsafe fn raw_end_write(&mut self) -> c_int {
+		if !self.memory.is_null() {
+			return (*self.memory).end_write();
+		}
+		raw::soundio_outstream_end_write(self.outstream)
+	}
+
+	// `soundio_outstream_get_latency()`, or its stand-in.
+	unsafe fn raw_get_latency(&mut self, latency: *mut c_double) -> c_int {
+		if !self.memory.is_null() {
+			*latency = (*self.memory).latency();
+			return 0;
+		}
+		raw::soundio_outstream_get_latency(self.outstream, latency)
+	}
+
+	// Pass an error from the implicit commit in `drop()` on to the stream.
+	unsafe fn report_commit_error(&mut self, error: Error) {
+		if !self.memory.is_null() {
+			(*self.memory).commit_errors.push(error);
+			return;
+		}
+		let userdata = (*self.outstream).userdata as *mut OutStreamUserData;
+		(*userdata).report_commit_error(error);
+	}
+
 	/// Start a write. You can only call this once per callback otherwise it panics.
 	///
 	/// frame_count is the number of frames you want to write. It must be between
@@ -283,14 +550,19 @@ impl<'a> OutStreamWriter<'a> {
 	/// * `Error::IncompatibleDevice` - in rare cases it might just now
 	///   be discovered that the device uses non-byte-aligned access, in which
 	///   case this error code is returned.
+	/// * Any error from an earlier `end_write()`. See `error()`.
 	///
 	pub fn begin_write(&mut self, frame_count: usize) -> Result<usize> {
 		assert!(frame_count >= self.frame_count_min && frame_count <= self.frame_count_max, "frame_count out of range");
 
+		if let Some(e) = self.error {
+			return Err(e);
+		}
+
 		let mut areas: *mut raw::SoundIoChannelArea = ptr::null_mut();
 		let mut actual_frame_count: c_int = frame_count as _;
 
-		match unsafe { raw::soundio_outstream_begin_write(self.outstream, &mut areas as *mut _, &mut actual_frame_count as *mut _) } {
+		match unsafe { self.raw_begin_write(&mut areas as *mut _, &mut actual_frame_count as *mut _) } {
 			0 => {
 				self.write_started = true;
 				self.frame_count = actual_frame_count as _;
@@ -299,17 +571,15 @@ impl<'a> OutStreamWriter<'a> {
 					return Ok(0);
 				}
 				let cc = self.channel_count();
-				self.channel_areas = vec![raw::SoundIoChannelArea { ptr: ptr::null_mut(), step: 0 }; cc];
-				unsafe { self.channel_areas.copy_from_slice(slice::from_raw_parts::<raw::SoundIoChannelArea>(areas, cc)); }
+				unsafe { self.channel_areas[..cc].copy_from_slice(slice::from_raw_parts::<raw::SoundIoChannelArea>(areas, cc)); }
 				Ok(actual_frame_count as _)
 			},
 			e => Err(e.into()),
 		}
 	}
 
-	/// Commits the write that you began with `begin_write()`.
-	///
-	/// Errors are currently are just printed to the console and ignored.
+	/// Commits the write that you began with `begin_write()`. It does nothing if there isn't a
+	/// write to commit.
 	///
 	/// # Errors
 	///
@@ -317,18 +587,35 @@ impl<'a> OutStreamWriter<'a> {
 	/// * `Error::Underflow` - an underflow caused this call to fail. You might
 	///   also get an `underflow_callback()`, and you might not get
 	///   this error code when an underflow occurs. Unlike `Error::Streaming`,
-	///   the outstream is still in a valid state and streaming can continue.
-	pub fn end_write(&mut self) {
-		if self.write_started {
-			unsafe {
-				match raw::soundio_outstream_end_write(self.outstream) {
-					0 => {self.write_started = false;},
-					x => println!("Error writing outstream: {}", Error::from(x)),
-				}
-			}
+	///   the outstream is still in a valid state and streaming can continue
+	///   in the next call to the write callback.
+	/// * Any error from an earlier `end_write()`. See `error()`.
+	///
+	pub fn end_write(&mut self) -> Result<()> {
+		if let Some(e) = self.error {
+			return Err(e);
+		}
+		if !self.write_started {
+			return Ok(());
 		}
+		self.write_started = false;
+		match unsafe { self.raw_end_write() } {
+			0 => Ok(()),
+			x => {
+				let e = x.into();
+				self.error = Some(e);
+				Err(e)
+			},
+		}
+	}
+
+	/// The error from committing a write, if it failed. Once this is set the writer can't be used
+	/// any more.
+	pub fn error(&self) -> Option<Error> {
+		self.error
 	}
 	
+
 	/// Get the minimum frame count that you can call `begin_write()` with.
 	/// Retreive this value before calling `begin_write()` to ensure you read the correct number
 	/// of frames.
@@ -384,7 +671,7 @@ impl<'a> OutStreamWriter<'a> {
 	///
 	pub fn get_latency(&mut self) -> Result<f64> {
 		let mut x: c_double = 0.0;
-		match unsafe { raw::soundio_outstream_get_latency(self.outstream, &mut x as *mut c_double) } {
+		match unsafe { self.raw_get_latency(&mut x as *mut c_double) } {
 			0 => Ok(x),
 			e => Err(e.into()),
 		}
@@ -441,25 +728,133 @@ impl<'a> OutStreamWriter<'a> {
 		}
 	}
 
-	// TODO: To acheive speed *and* safety I can use iterators. That will be in a future API.
-}
+	/// Get a typed, mutable view of one channel's samples, which can be written in place without
+	/// the per-sample format conversion done by `set_sample()`. This panics if `channel` is out of
+	/// range or if you haven't called `begin_write()` yet.
+	///
+	/// # Errors
+	///
+	/// * `Error::IncompatibleFormat`
+	///   - `T` is not the stream's format, e.g. `f32` for a stream that isn't `native::Float32NE`.
+	///   - the channel's samples are not aligned for `T`.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// fn write_callback(stream: &mut soundio::OutStreamWriter) {
+	///     let frame_count_max = stream.frame_count_max();
+	///     stream.begin_write(frame_count_max).unwrap();
+	///     for c in 0..stream.channel_count() {
+	///         let mut channel = stream.channel_mut::<f32>(c).unwrap();
+	///         for f in 0..channel.len() {
+	///             channel[f] = 0.0;
+	///         }
+	///     }
+	/// }
+	/// ```
+	pub fn channel_mut<T: NativeSample>(&mut self, channel: usize) -> Result<ChannelMut<'_, T>> {
+		assert!(self.write_started);
 
-impl<'a> Drop for OutStreamWriter<'a> {
-	/// This will drop all of the frames from when you called `begin_write()`.
+		assert!(channel < self.channel_count(), "Channel out of range");
+
+		if Format::from(unsafe { (*self.outstream).format }) != T::native_format() {
+			return Err(Error::IncompatibleFormat);
+		}
+
+		let area = self.channel_areas[channel];
+		if self.frame_count == 0 {
+			return Ok(unsafe { ChannelMut::new(ptr::null_mut(), 0, 0) });
+		}
+		if !is_aligned_for::<T>(area.ptr as *const u8, area.step as usize) {
+			return Err(Error::IncompatibleFormat);
+		}
+		Ok(unsafe { ChannelMut::new(area.ptr as *mut u8, area.step as usize, self.frame_count) })
+	}
+
+	/// Write interleaved samples, e.g. `[l0, r0, l1, r1, ...]` for a stereo stream, converting them to
+	/// the stream's format. This panics if you haven't called `begin_write()` yet.
 	///
-	/// Errors are currently are just printed to the console and ignored.
+	/// It writes as many whole frames as are available in `samples`, up to `frame_count()`, and
+	/// returns the number of frames written.
 	///
-	/// # Errors
+	/// # Examples
 	///
-	/// * `Error::Streaming`
-	fn drop(&mut self) {
-		if self.write_started {
+	/// ```
+	/// fn write_callback(stream: &mut soundio::OutStreamWriter, audio: &[f32]) {
+	///     let frame_count_max = stream.frame_count_max();
+	///     stream.begin_write(frame_count_max).unwrap();
+	///     let written = stream.write_interleaved(audio);
+	///     // Do something with the remaining `audio[written * stream.channel_count()..]`.
+	/// }
+	/// ```
+	pub fn write_interleaved<T: Sample + Copy>(&mut self, samples: &[T]) -> usize {
+		assert!(self.write_started);
+
+		let channel_count = self.channel_count();
+		let frames = cmp::min(self.frame_count, samples.len() / channel_count);
+		if frames == 0 {
+			return 0;
+		}
+
+		let format = Format::from(unsafe { (*self.outstream).format });
+		for c in 0..channel_count {
+			let area = self.channel_areas[c];
 			unsafe {
-				match raw::soundio_outstream_end_write(self.outstream) {
-					0 => {},
-					x => println!("Error writing outstream: {}", Error::from(x)),
-				}
+				write_samples(format, area.ptr as *mut u8, area.step as usize,
+				              samples[c..].iter().step_by(channel_count).take(frames).cloned());
 			}
 		}
+		frames
+	}
+
+	/// Write planar samples, i.e. one slice per channel, converting them to the stream's format.
+	/// This panics if you haven't called `begin_write()` yet, or if the number of slices is not
+	/// `channel_count()`.
+	///
+	/// It writes as many frames as are available in the shortest slice, up to `frame_count()`, and
+	/// returns the number of frames written.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// fn write_callback(stream: &mut soundio::OutStreamWriter, audio: &[Vec<f32>]) {
+	///     let frame_count_max = stream.frame_count_max();
+	///     stream.begin_write(frame_count_max).unwrap();
+	///     stream.write_planar(audio);
+	/// }
+	/// ```
+	pub fn write_planar<T: Sample + Copy, S: AsRef<[T]>>(&mut self, channels: &[S]) -> usize {
+		assert!(self.write_started);
+
+		assert_eq!(channels.len(), self.channel_count(), "Wrong number of channels");
+		let frames = channels.iter().fold(self.frame_count, |frames, ch| cmp::min(frames, ch.as_ref().len()));
+		if frames == 0 {
+			return 0;
+		}
+
+		let format = Format::from(unsafe { (*self.outstream).format });
+		for (c, ch) in channels.iter().enumerate() {
+			let area = self.channel_areas[c];
+			unsafe {
+				write_samples(format, area.ptr as *mut u8, area.step as usize, ch.as_ref()[..frames].iter().cloned());
+			}
+		}
+		frames
+	}
+}
+
+impl<'a> Drop for OutStreamWriter<'a> {
+	/// This commits the write if you called `begin_write()` but not `end_write()`.
+	///
+	/// An error is passed to the stream's error callback. If there isn't one it is reported as
+	/// `StreamEvent::EndWriteFailed`; see `OutStream::poll_event()`.
+	fn drop(&mut self) {
+		// An earlier error has already been returned by `end_write()`.
+		if self.error.is_some() || !self.write_started {
+			return;
+		}
+		if let Err(e) = self.end_write() {
+			unsafe { self.report_commit_error(e); }
+		}
 	}
 }
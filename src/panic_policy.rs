@@ -0,0 +1,138 @@
+use std::any::Any;
+use std::panic;
+use std::process;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// `PanicPolicy` determines what happens when one of the callbacks passed to `Device::open_outstream()`
+/// or `Device::open_instream()` panics.
+///
+/// Unwinding from a callback into libsoundio's C code is undefined behaviour, so every callback
+/// is run inside `std::panic::catch_unwind()`. The panic payload is kept so that you can retrieve it
+/// on the control thread with `OutStream::take_panic()` or `InStream::take_panic()`.
+///
+/// It is set with `OutStream::set_panic_policy()` or `InStream::set_panic_policy()`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PanicPolicy {
+	/// Abort the process. This is the default.
+	Abort,
+	/// Stop calling the user callbacks. Output streams write silence from then on, and input
+	/// streams discard their input.
+	Silence,
+	/// The same as `PanicPolicy::Silence` except that the stream's error callback is also called
+	/// with `Error::Streaming`, because the stream can no longer be used normally.
+	ReportError,
+}
+
+impl Default for PanicPolicy {
+	fn default() -> PanicPolicy {
+		PanicPolicy::Abort
+	}
+}
+
+impl PanicPolicy {
+	// The policy is stored in an `AtomicUsize` so that the audio thread can read it.
+	fn to_usize(self) -> usize {
+		match self {
+			PanicPolicy::Abort => 0,
+			PanicPolicy::Silence => 1,
+			PanicPolicy::ReportError => 2,
+		}
+	}
+
+	fn from_usize(policy: usize) -> PanicPolicy {
+		match policy {
+			1 => PanicPolicy::Silence,
+			2 => PanicPolicy::ReportError,
+			_ => PanicPolicy::Abort,
+		}
+	}
+}
+
+// The panic state for a stream. It is shared between the control thread, which sets the policy
+// and takes the payload, and the audio thread which runs the callbacks.
+pub(crate) struct PanicState {
+	policy: AtomicUsize,
+	panicked: AtomicBool,
+	payload: Mutex<Option<Box<Any + Send>>>,
+}
+
+impl PanicState {
+	pub fn new() -> PanicState {
+		PanicState {
+			policy: AtomicUsize::new(PanicPolicy::default().to_usize()),
+			panicked: AtomicBool::new(false),
+			payload: Mutex::new(None),
+		}
+	}
+
+	pub fn policy(&self) -> PanicPolicy {
+		PanicPolicy::from_usize(self.policy.load(Ordering::Relaxed))
+	}
+
+	pub fn set_policy(&self, policy: PanicPolicy) {
+		self.policy.store(policy.to_usize(), Ordering::Relaxed);
+	}
+
+	/// Whether a callback has panicked. After that the callbacks are not called again.
+	pub fn panicked(&self) -> bool {
+		self.panicked.load(Ordering::Acquire)
+	}
+
+	/// Run `f`, catching any panic. If it panics the payload is stored (unless there is already one
+	/// that hasn't been taken) and, depending on the policy, the process is aborted. Returns `true`
+	/// if `f` panicked.
+	pub fn call<F: FnOnce()>(&self, f: F) -> bool {
+		match panic::catch_unwind(panic::AssertUnwindSafe(f)) {
+			Ok(()) => false,
+			Err(payload) => {
+				if self.policy() == PanicPolicy::Abort {
+					process::abort();
+				}
+				if let Ok(mut stored) = self.payload.lock() {
+					if stored.is_none() {
+						*stored = Some(payload);
+					}
+				}
+				self.panicked.store(true, Ordering::Release);
+				true
+			}
+		}
+	}
+
+	/// Take the panic payload, if there is one.
+	pub fn take(&self) -> Option<Box<Any + Send>> {
+		match self.payload.lock() {
+			Ok(mut payload) => payload.take(),
+			Err(_) => None,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn default_policy_is_abort() {
+		assert_eq!(PanicState::new().policy(), PanicPolicy::Abort);
+	}
+
+	#[test]
+	fn panic_is_caught_and_stored() {
+		let state = PanicState::new();
+		state.set_policy(PanicPolicy::Silence);
+
+		assert!(!state.call(|| {}));
+		assert!(!state.panicked());
+
+		assert!(state.call(|| panic!("first")));
+		assert!(state.call(|| panic!("second")));
+		assert!(state.panicked());
+
+		// Only the first payload is kept.
+		let payload = state.take().expect("No panic payload");
+		assert_eq!(payload.downcast_ref::<&str>(), Some(&"first"));
+		assert!(state.take().is_none());
+	}
+}
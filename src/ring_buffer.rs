@@ -0,0 +1,251 @@
+extern crate libsoundio_sys as raw;
+
+use std::ptr;
+use std::mem;
+use std::cmp::min;
+use std::sync::Arc;
+use std::marker::PhantomData;
+use std::os::raw::c_int;
+
+/// `RingBuffer` is a safe wrapper around libsoundio's lock-free single-producer, single-consumer
+/// ring buffer. It is useful for moving audio between the real-time read or write callback and
+/// another thread, because neither side ever blocks or allocates.
+///
+/// It is created using `Context::ring_buffer()` and then split into a `RingBufferProducer` and a
+/// `RingBufferConsumer` using `RingBuffer::split()`. Both halves are `Send` (if `T` is) so they can
+/// be moved to different threads.
+///
+/// The capacity is measured in elements of type `T`, not bytes. libsoundio may round the capacity
+/// up for alignment purposes, so use `RingBuffer::capacity()` to get the actual value.
+///
+/// # Examples
+///
+/// ```
+/// let ctx = soundio::Context::new();
+/// let (mut producer, mut consumer) = ctx.ring_buffer::<f32>(1024).split();
+///
+/// assert_eq!(producer.write(&[0.1, 0.2, 0.3]), 3);
+///
+/// let mut buffer = [0.0f32; 8];
+/// assert_eq!(consumer.read(&mut buffer), 3);
+/// assert_eq!(&buffer[0..3], &[0.1, 0.2, 0.3]);
+/// ```
+pub struct RingBuffer<T: Copy> {
+	inner: Arc<RingBufferInner>,
+	// `*mut T` makes the buffer invariant in `T`. If it were covariant a producer of
+	// `&'static` references could be shortened to write references that don't live that long,
+	// and the consumer would still read them as `&'static`.
+	phantom: PhantomData<*mut T>,
+}
+
+/// The write half of a `RingBuffer`. See `RingBuffer::split()`.
+pub struct RingBufferProducer<T: Copy> {
+	inner: Arc<RingBufferInner>,
+	phantom: PhantomData<*mut T>,
+}
+
+/// The read half of a `RingBuffer`. See `RingBuffer::split()`.
+pub struct RingBufferConsumer<T: Copy> {
+	inner: Arc<RingBufferInner>,
+	phantom: PhantomData<*mut T>,
+}
+
+// The raw ring buffer is shared between the producer and consumer, and is destroyed
+// when both have been dropped.
+struct RingBufferInner {
+	ring_buffer: *mut raw::SoundIoRingBuffer,
+}
+
+impl Drop for RingBufferInner {
+	fn drop(&mut self) {
+		unsafe {
+			raw::soundio_ring_buffer_destroy(self.ring_buffer);
+		}
+	}
+}
+
+// libsoundio's ring buffer uses atomics internally so that one thread can write while
+// another reads. The producer/consumer split ensures there is only ever one of each.
+unsafe impl Send for RingBufferInner {}
+unsafe impl Sync for RingBufferInner {}
+
+unsafe impl<T: Copy + Send> Send for RingBuffer<T> {}
+unsafe impl<T: Copy + Send> Send for RingBufferProducer<T> {}
+unsafe impl<T: Copy + Send> Send for RingBufferConsumer<T> {}
+// Only the counts can be read through a shared reference; reading and writing need `&mut self`.
+unsafe impl<T: Copy + Sync> Sync for RingBuffer<T> {}
+unsafe impl<T: Copy + Sync> Sync for RingBufferProducer<T> {}
+unsafe impl<T: Copy + Sync> Sync for RingBufferConsumer<T> {}
+
+impl<T: Copy> RingBuffer<T> {
+	/// Create a new ring buffer. Use `Context::ring_buffer()` rather than calling this directly.
+	pub(crate) fn new(soundio: *mut raw::SoundIo, capacity: usize) -> RingBuffer<T> {
+		assert!(capacity > 0, "Ring buffer capacity must be greater than 0");
+		assert!(mem::size_of::<T>() > 0, "Ring buffer elements cannot be zero-sized");
+
+		let ring_buffer = unsafe {
+			raw::soundio_ring_buffer_create(soundio, (capacity * mem::size_of::<T>()) as c_int)
+		};
+		if ring_buffer == ptr::null_mut() {
+			panic!("soundio_ring_buffer_create() failed (out of memory).");
+		}
+
+		RingBuffer {
+			inner: Arc::new(RingBufferInner {
+				ring_buffer: ring_buffer,
+			}),
+			phantom: PhantomData,
+		}
+	}
+
+	/// The actual capacity in elements, which may be more than was requested.
+	pub fn capacity(&self) -> usize {
+		self.inner.capacity::<T>()
+	}
+
+	/// Split the ring buffer into its write and read halves.
+	pub fn split(self) -> (RingBufferProducer<T>, RingBufferConsumer<T>) {
+		(
+			RingBufferProducer {
+				inner: self.inner.clone(),
+				phantom: PhantomData,
+			},
+			RingBufferConsumer {
+				inner: self.inner,
+				phantom: PhantomData,
+			},
+		)
+	}
+}
+
+impl RingBufferInner {
+	fn capacity<T>(&self) -> usize {
+		unsafe { raw::soundio_ring_buffer_capacity(self.ring_buffer) as usize / mem::size_of::<T>() }
+	}
+
+	fn fill_count<T>(&self) -> usize {
+		unsafe { raw::soundio_ring_buffer_fill_count(self.ring_buffer) as usize / mem::size_of::<T>() }
+	}
+
+	fn free_count<T>(&self) -> usize {
+		unsafe { raw::soundio_ring_buffer_free_count(self.ring_buffer) as usize / mem::size_of::<T>() }
+	}
+}
+
+impl<T: Copy> RingBufferProducer<T> {
+	/// Write as many elements from `data` as there is space for, and return the
+	/// number that were written. This never blocks.
+	pub fn write(&mut self, data: &[T]) -> usize {
+		let count = min(data.len(), self.free_count());
+		if count == 0 {
+			return 0;
+		}
+		let bytes = count * mem::size_of::<T>();
+		unsafe {
+			// libsoundio mirrors the buffer memory so the write pointer is always
+			// valid for up to `capacity` contiguous bytes. The write pointer may not be
+			// aligned for `T` so the data is copied bytewise.
+			let dst = raw::soundio_ring_buffer_write_ptr(self.inner.ring_buffer) as *mut u8;
+			ptr::copy_nonoverlapping(data.as_ptr() as *const u8, dst, bytes);
+			raw::soundio_ring_buffer_advance_write_ptr(self.inner.ring_buffer, bytes as c_int);
+		}
+		count
+	}
+
+	/// The number of elements that can currently be written without overwriting unread data.
+	pub fn free_count(&self) -> usize {
+		self.inner.free_count::<T>()
+	}
+
+	/// The number of elements that have been written but not yet read.
+	pub fn fill_count(&self) -> usize {
+		self.inner.fill_count::<T>()
+	}
+
+	/// The actual capacity in elements.
+	pub fn capacity(&self) -> usize {
+		self.inner.capacity::<T>()
+	}
+
+	/// Discard all unread data. libsoundio requires this to be called by the writer.
+	pub fn clear(&mut self) {
+		unsafe {
+			raw::soundio_ring_buffer_clear(self.inner.ring_buffer);
+		}
+	}
+}
+
+impl<T: Copy> RingBufferConsumer<T> {
+	/// Read as many elements into `data` as are available, and return the number
+	/// that were read. This never blocks.
+	pub fn read(&mut self, data: &mut [T]) -> usize {
+		let count = min(data.len(), self.fill_count());
+		if count == 0 {
+			return 0;
+		}
+		let bytes = count * mem::size_of::<T>();
+		unsafe {
+			let src = raw::soundio_ring_buffer_read_ptr(self.inner.ring_buffer) as *const u8;
+			ptr::copy_nonoverlapping(src, data.as_mut_ptr() as *mut u8, bytes);
+			raw::soundio_ring_buffer_advance_read_ptr(self.inner.ring_buffer, bytes as c_int);
+		}
+		count
+	}
+
+	/// Discard up to `count` elements without reading them, and return the number discarded.
+	pub fn skip(&mut self, count: usize) -> usize {
+		let count = min(count, self.fill_count());
+		unsafe {
+			raw::soundio_ring_buffer_advance_read_ptr(self.inner.ring_buffer, (count * mem::size_of::<T>()) as c_int);
+		}
+		count
+	}
+
+	/// The number of elements that are available to read.
+	pub fn fill_count(&self) -> usize {
+		self.inner.fill_count::<T>()
+	}
+
+	/// The number of elements that can currently be written by the producer.
+	pub fn free_count(&self) -> usize {
+		self.inner.free_count::<T>()
+	}
+
+	/// The actual capacity in elements.
+	pub fn capacity(&self) -> usize {
+		self.inner.capacity::<T>()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::super::context::*;
+
+	#[test]
+	fn write_then_read() {
+		let ctx = Context::new();
+		let (mut producer, mut consumer) = ctx.ring_buffer::<i32>(16).split();
+
+		assert_eq!(producer.write(&[1, 2, 3, 4]), 4);
+		assert_eq!(consumer.fill_count(), 4);
+
+		let mut out = [0; 3];
+		assert_eq!(consumer.read(&mut out), 3);
+		assert_eq!(out, [1, 2, 3]);
+		assert_eq!(consumer.read(&mut out), 1);
+		assert_eq!(out[0], 4);
+		assert_eq!(consumer.read(&mut out), 0);
+	}
+
+	#[test]
+	fn write_stops_when_full() {
+		let ctx = Context::new();
+		let (mut producer, consumer) = ctx.ring_buffer::<f32>(16).split();
+
+		let capacity = producer.capacity();
+		let data = vec![0.5f32; capacity + 10];
+		assert_eq!(producer.write(&data), capacity);
+		assert_eq!(producer.free_count(), 0);
+		assert_eq!(consumer.fill_count(), capacity);
+	}
+}
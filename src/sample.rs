@@ -4,6 +4,8 @@
 
 use std;
 
+use super::format::*;
+
 /// The Sample trait defines functions to convert between the various sample formats. The full range
 /// of the integer sample formats is always used, so `0u16.to_i8()` is -128. Converting between 
 /// signed and unsigned of the same size is lossless, as is increasing the bit depth.
@@ -138,9 +140,10 @@ macro_rules! impl_raw_methods_24 {
 			// TODO: This seems like a suboptimal implementation.
 			$ty_24(((u32::from_raw_le(ptr) << 8) as $ty_32) >> 8)
 		}
+		// The samples are in the low three bytes of a 32 bit word, which are the last three in
+		// big endian.
 		unsafe fn from_raw_be(ptr: *const u8) -> Self {
-			// TODO: This seems like a suboptimal implementation.
-			$ty_24(((u32::from_raw_le(ptr.offset(3)) << 8) as $ty_32) >> 8)
+			$ty_24(((u32::from_raw_be(ptr) << 8) as $ty_32) >> 8)
 		}
 		unsafe fn to_raw_le(v: Self, ptr: *mut u8) {
 			*ptr = (v.0 & 0xFF) as u8;
@@ -148,9 +151,9 @@ macro_rules! impl_raw_methods_24 {
 			*ptr.offset(2) = ((v.0 >> 16) & 0xFF) as u8;
 		}
 		unsafe fn to_raw_be(v: Self, ptr: *mut u8) {
-			*ptr = ((v.0 >> 16) & 0xFF) as u8;
-			*ptr.offset(1) = ((v.0 >> 8) & 0xFF) as u8;
-			*ptr.offset(2) = (v.0 & 0xFF) as u8;
+			*ptr.offset(1) = ((v.0 >> 16) & 0xFF) as u8;
+			*ptr.offset(2) = ((v.0 >> 8) & 0xFF) as u8;
+			*ptr.offset(3) = (v.0 & 0xFF) as u8;
 		}
 	}
 }
@@ -379,6 +382,117 @@ impl Sample for f64 {
 	impl_float_raw_methods!(u64);
 }
 
+/// `NativeSample` is implemented for sample types that can be read and written in place in a
+/// stream's buffer, without any conversion. That is the case when the stream's format is the
+/// native endian version of the type, and every bit pattern is a valid value of the type.
+///
+/// `u24` and `i24` are not included because the top byte of their 32-bit word is unspecified.
+///
+/// # Examples
+///
+/// ```
+/// use soundio::NativeSample;
+/// assert_eq!(f32::native_format(), soundio::native::Float32NE);
+/// assert_eq!(u8::native_format(), soundio::Format::U8);
+/// ```
+pub trait NativeSample: Sample + Copy {
+	/// The `Format` of this type in native endianness.
+	fn native_format() -> Format;
+}
+
+macro_rules! impl_native_sample {
+	($ty:ident, $format:expr) => {
+		impl NativeSample for $ty {
+			fn native_format() -> Format { $format }
+		}
+	}
+}
+
+impl_native_sample!(u8, Format::U8);
+impl_native_sample!(i8, Format::S8);
+impl_native_sample!(u16, native::U16NE);
+impl_native_sample!(i16, native::S16NE);
+impl_native_sample!(u32, native::U32NE);
+impl_native_sample!(i32, native::S32NE);
+impl_native_sample!(f32, native::Float32NE);
+impl_native_sample!(f64, native::Float64NE);
+
+// Write `samples` to a channel of a stream's buffer, starting at `ptr` with `step` bytes between
+// samples, converting them to `format`. The format is only matched once so that the conversion
+// is a tight loop. The caller must ensure the buffer is large enough.
+pub(crate) unsafe fn write_samples<T, I>(format: Format, ptr: *mut u8, step: usize, samples: I)
+	where T: Sample, I: Iterator<Item = T> {
+
+	macro_rules! write_loop {
+		($ty:ident, $to:ident, $raw:ident) => {{
+			let mut p = ptr;
+			for sample in samples {
+				$ty::$raw(T::$to(sample), p);
+				p = p.wrapping_offset(step as isize);
+			}
+		}}
+	}
+
+	match format {
+		Format::S8 => write_loop!(i8, to_i8, to_raw_le),
+		Format::U8 => write_loop!(u8, to_u8, to_raw_le),
+		Format::S16LE => write_loop!(i16, to_i16, to_raw_le),
+		Format::S16BE => write_loop!(i16, to_i16, to_raw_be),
+		Format::U16LE => write_loop!(u16, to_u16, to_raw_le),
+		Format::U16BE => write_loop!(u16, to_u16, to_raw_be),
+		Format::S24LE => write_loop!(i24, to_i24, to_raw_le),
+		Format::S24BE => write_loop!(i24, to_i24, to_raw_be),
+		Format::U24LE => write_loop!(u24, to_u24, to_raw_le),
+		Format::U24BE => write_loop!(u24, to_u24, to_raw_be),
+		Format::S32LE => write_loop!(i32, to_i32, to_raw_le),
+		Format::S32BE => write_loop!(i32, to_i32, to_raw_be),
+		Format::U32LE => write_loop!(u32, to_u32, to_raw_le),
+		Format::U32BE => write_loop!(u32, to_u32, to_raw_be),
+		Format::Float32LE => write_loop!(f32, to_f32, to_raw_le),
+		Format::Float32BE => write_loop!(f32, to_f32, to_raw_be),
+		Format::Float64LE => write_loop!(f64, to_f64, to_raw_le),
+		Format::Float64BE => write_loop!(f64, to_f64, to_raw_be),
+		Format::Invalid => panic!("Unknown format"),
+	}
+}
+
+// The inverse of `write_samples()`: read from a channel of a stream's buffer into `samples`,
+// converting them from `format`.
+pub(crate) unsafe fn read_samples<'s, T, I>(format: Format, ptr: *const u8, step: usize, samples: I)
+	where T: Sample + 's, I: Iterator<Item = &'s mut T> {
+
+	macro_rules! read_loop {
+		($ty:ident, $from:ident, $raw:ident) => {{
+			let mut p = ptr;
+			for sample in samples {
+				*sample = T::$from($ty::$raw(p));
+				p = p.wrapping_offset(step as isize);
+			}
+		}}
+	}
+
+	match format {
+		Format::S8 => read_loop!(i8, from_i8, from_raw_le),
+		Format::U8 => read_loop!(u8, from_u8, from_raw_le),
+		Format::S16LE => read_loop!(i16, from_i16, from_raw_le),
+		Format::S16BE => read_loop!(i16, from_i16, from_raw_be),
+		Format::U16LE => read_loop!(u16, from_u16, from_raw_le),
+		Format::U16BE => read_loop!(u16, from_u16, from_raw_be),
+		Format::S24LE => read_loop!(i24, from_i24, from_raw_le),
+		Format::S24BE => read_loop!(i24, from_i24, from_raw_be),
+		Format::U24LE => read_loop!(u24, from_u24, from_raw_le),
+		Format::U24BE => read_loop!(u24, from_u24, from_raw_be),
+		Format::S32LE => read_loop!(i32, from_i32, from_raw_le),
+		Format::S32BE => read_loop!(i32, from_i32, from_raw_be),
+		Format::U32LE => read_loop!(u32, from_u32, from_raw_le),
+		Format::U32BE => read_loop!(u32, from_u32, from_raw_be),
+		Format::Float32LE => read_loop!(f32, from_f32, from_raw_le),
+		Format::Float32BE => read_loop!(f32, from_f32, from_raw_be),
+		Format::Float64LE => read_loop!(f64, from_f64, from_raw_le),
+		Format::Float64BE => read_loop!(f64, from_f64, from_raw_be),
+		Format::Invalid => panic!("Unknown format"),
+	}
+}
 
 #[cfg(test)]
 mod tests {
@@ -442,6 +556,21 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn raw_24_bit_layout() {
+		let le = [0x03u8, 0x02, 0xFF, 0x00];
+		let be = [0x00u8, 0xFF, 0x02, 0x03];
+		unsafe {
+			assert_eq!(i24::from_raw_le(le.as_ptr()), i24(-0xFDFD));
+			assert_eq!(i24::from_raw_be(be.as_ptr()), i24(-0xFDFD));
+			assert_eq!(u24::from_raw_be(be.as_ptr()), u24(0xFF0203));
+
+			let mut raw = [0u8; 4];
+			i24::to_raw_be(i24(-0xFDFD), raw.as_mut_ptr());
+			assert_eq!(raw, be);
+		}
+	}
+
 	#[test]
 	fn out_of_range_float() {
 		assert_eq!(0, u8::from_f64(-1.0));
@@ -487,4 +616,22 @@ mod tests {
 			}
 		}
 	}
+
+	#[test]
+	fn write_then_read_samples() {
+		// Two interleaved S16BE channels.
+		let mut buffer = [0u8; 8];
+		let input = [0.0f32, 0.5, -0.5, -1.0];
+		unsafe {
+			write_samples(Format::S16BE, buffer.as_mut_ptr().offset(2), 4, input.iter().cloned().take(2));
+			write_samples(Format::S16BE, buffer.as_mut_ptr(), 4, input.iter().cloned().skip(2));
+		}
+		assert_eq!(buffer, [0xC0, 0x00, 0x00, 0x00, 0x80, 0x00, 0x40, 0x00]);
+
+		let mut output = [0i16; 2];
+		unsafe {
+			read_samples(Format::S16BE, buffer.as_ptr().offset(2), 4, output.iter_mut());
+		}
+		assert_eq!(output, [0, 0x4000]);
+	}
 }
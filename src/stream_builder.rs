@@ -0,0 +1,563 @@
+use super::error::*;
+use super::format::*;
+use super::layout::*;
+use super::device::*;
+use super::outstream::*;
+use super::instream::*;
+use super::supervised_stream::*;
+use super::following_stream::*;
+
+// The sample rate used if none is requested and the device doesn't have a current one.
+const DEFAULT_SAMPLE_RATE: i32 = 48000;
+
+/// The stream configuration negotiated by an `OutStreamBuilder` or `InStreamBuilder`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamConfig {
+	/// The sample rate in Hertz.
+	pub sample_rate: i32,
+	/// The sample format.
+	pub format: Format,
+	/// The channel layout.
+	pub layout: ChannelLayout,
+	/// The software latency in seconds. Once the stream has been opened this is the actual
+	/// latency, which may differ from what was requested.
+	pub software_latency: f64,
+}
+
+// The preferences shared by both builders.
+#[derive(Clone)]
+struct StreamPreferences {
+	formats: Vec<Format>,
+	sample_rate: Option<i32>,
+	layouts: Vec<ChannelLayout>,
+	software_latency: f64,
+}
+
+impl StreamPreferences {
+	fn new() -> StreamPreferences {
+		StreamPreferences {
+			formats: Vec::new(),
+			sample_rate: None,
+			layouts: Vec::new(),
+			software_latency: 0.0,
+		}
+	}
+
+	fn negotiate(&self, device: &Device) -> Result<StreamConfig> {
+		// Nothing is known about a device that couldn't be probed.
+		if let Some(e) = device.probe_error() {
+			return Err(e);
+		}
+
+		let format = if self.formats.is_empty() {
+			match device.current_format()? {
+				Format::Invalid => match device.formats()?.first() {
+					Some(&format) => format,
+					None => return Err(Error::IncompatibleDevice),
+				},
+				format => format,
+			}
+		} else {
+			match self.formats.iter().find(|&&format| device.supports_format(format)) {
+				Some(&format) => format,
+				None => return Err(Error::IncompatibleDevice),
+			}
+		};
+
+		let sample_rate = match self.sample_rate {
+			Some(sample_rate) => sample_rate,
+			None => match device.current_sample_rate()? {
+				0 => DEFAULT_SAMPLE_RATE,
+				sample_rate => sample_rate,
+			},
+		};
+		let sample_rate = device.nearest_sample_rate(sample_rate)?;
+
+		let layout = if self.layouts.is_empty() {
+			default_layout(device.current_layout()?, &device.layouts()?)?
+		} else {
+			match ChannelLayout::best_matching_channel_layout(&self.layouts, &device.layouts()?) {
+				Some(layout) => layout,
+				None => return Err(Error::IncompatibleDevice),
+			}
+		};
+
+		// A latency of 0 means the backend's default. Otherwise keep it within the device's
+		// limits, where they are known.
+		let mut software_latency = self.software_latency;
+		let limits = device.software_latency()?;
+		if software_latency > 0.0 {
+			if limits.min > 0.0 && software_latency < limits.min {
+				software_latency = limits.min;
+			}
+			if limits.max > 0.0 && software_latency > limits.max {
+				software_latency = limits.max;
+			}
+		}
+
+		Ok(StreamConfig {
+			sample_rate: sample_rate,
+			format: format,
+			layout: layout,
+			software_latency: software_latency,
+		})
+	}
+}
+
+// The layout to use when none are preferred. libsoundio reports an unknown current layout, which
+// raw and some ALSA devices have, as one with no channels. Then the first layout the device
+// supports is used, and as libsoundio only supports the layouts in that list there is nothing else
+// to try if it is empty.
+fn default_layout(current: ChannelLayout, layouts: &[ChannelLayout]) -> Result<ChannelLayout> {
+	if !current.channels.is_empty() {
+		return Ok(current);
+	}
+	match layouts.first() {
+		Some(layout) => Ok(layout.clone()),
+		None => Err(Error::IncompatibleDevice),
+	}
+}
+
+/// `OutStreamBuilder` opens an `OutStream` on a device, choosing the format, sample rate and channel
+/// layout from lists of preferences according to what the device supports. It is obtained using
+/// `Device::outstream_builder()`.
+///
+/// * Formats: the first preferred format that the device supports. If no formats are given the
+///   device's current format is used, or failing that the first one it supports.
+/// * Sample rate: the device's nearest supported sample rate to the requested one. If no sample
+///   rate is requested the device's current sample rate is used, or 48 kHz if it doesn't have one.
+/// * Layouts: the first preferred layout that the device supports, as found by
+///   `ChannelLayout::best_matching_channel_layout()`. If no layouts are given the device's current
+///   layout is used, or if it isn't known the first one the device supports.
+///
+/// # Examples
+///
+/// ```
+/// let mut ctx = soundio::Context::new();
+/// ctx.connect_backend(soundio::Backend::Dummy).expect("Couldn't connect to backend");
+/// ctx.flush_events();
+/// let dev = ctx.default_output_device().expect("No output device");
+///
+/// let (stream, config) = dev.outstream_builder()
+///     .formats(&[soundio::native::Float32NE, soundio::native::S16NE])
+///     .sample_rate(44100)
+///     .layouts(&[soundio::ChannelLayout::get_builtin(soundio::ChannelLayoutId::Stereo)])
+///     .software_latency(0.05)
+///     .open(|_: &mut soundio::OutStreamWriter| {})
+///     .expect("Couldn't open stream");
+///
+/// println!("Opened {:?} at {} Hz with layout {}", config.format, config.sample_rate, config.layout.name);
+/// ```
+pub struct OutStreamBuilder<'a> {
+	device: Device<'a>,
+	preferences: StreamPreferences,
+	options: StreamOptions,
+	underflow_callback: Option<Box<FnMut() + Send + 'a>>,
+	error_callback: Option<Box<FnMut(Error) + Send + 'a>>,
+}
+
+impl<'a> OutStreamBuilder<'a> {
+	// Use `Device::outstream_builder()` rather than calling this directly.
+	pub(crate) fn new(device: &Device<'a>) -> OutStreamBuilder<'a> {
+		OutStreamBuilder {
+			device: device.clone(),
+			preferences: StreamPreferences::new(),
+			options: StreamOptions::default(),
+			underflow_callback: None,
+			error_callback: None,
+		}
+	}
+
+	/// The formats to use, in order of preference.
+	pub fn formats(mut self, formats: &[Format]) -> OutStreamBuilder<'a> {
+		self.preferences.formats = formats.to_vec();
+		self
+	}
+
+	/// The sample rate to use, or the nearest one that the device supports.
+	pub fn sample_rate(mut self, sample_rate: i32) -> OutStreamBuilder<'a> {
+		self.preferences.sample_rate = Some(sample_rate);
+		self
+	}
+
+	/// The channel layouts to use, in order of preference.
+	pub fn layouts(mut self, layouts: &[ChannelLayout]) -> OutStreamBuilder<'a> {
+		self.preferences.layouts = layouts.to_vec();
+		self
+	}
+
+	/// The requested software latency in seconds. See `Device::open_outstream()`. It is clamped
+	/// to the range given by `Device::software_latency()` if that is known. The default is 0,
+	/// which uses the backend's default latency.
+	pub fn software_latency(mut self, latency: f64) -> OutStreamBuilder<'a> {
+		self.preferences.software_latency = latency;
+		self
+	}
+
+	/// The name of the stream. PulseAudio uses this for the stream name, JACK uses it for the
+	/// client name and WASAPI uses it for the session display name. It must not contain a colon.
+	///
+	/// If it is invalid `open()` returns `Error::Invalid`, or `Error::EncodingString` if it contains
+	/// a nul character.
+	pub fn name(mut self, name: &str) -> OutStreamBuilder<'a> {
+		self.options.name = Some(name.to_string());
+		self
+	}
+
+	/// Hint that this stream is nonterminal, i.e. its data originates from an input stream.
+	/// This is only used by JACK. The default is `false`.
+	pub fn non_terminal_hint(mut self, non_terminal_hint: bool) -> OutStreamBuilder<'a> {
+		self.options.non_terminal_hint = non_terminal_hint;
+		self
+	}
+
+	/// The initial volume of the stream, from 0.0 to 1.0. See `OutStream::set_volume()`.
+	///
+	/// It is only applied where the backend supports it. Other backends ignore it and play the
+	/// stream at full volume; `open()` doesn't fail because of it.
+	pub fn volume(mut self, volume: f64) -> OutStreamBuilder<'a> {
+		self.options.volume = Some(volume);
+		self
+	}
+
+	/// The callback that is called when the output skips. See `Device::open_outstream()`.
+	pub fn underflow_callback<UnderflowCB: 'a + FnMut() + Send>(mut self, callback: UnderflowCB) -> OutStreamBuilder<'a> {
+		self.underflow_callback = Some(Box::new(callback));
+		self
+	}
+
+	/// The callback that is called when there is a streaming error. See `Device::open_outstream()`.
+	pub fn error_callback<ErrorCB: 'a + FnMut(Error) + Send>(mut self, callback: ErrorCB) -> OutStreamBuilder<'a> {
+		self.error_callback = Some(Box::new(callback));
+		self
+	}
+
+	/// Work out the configuration that `open()` would use, without opening the stream.
+	///
+	/// # Errors
+	///
+	/// * `Error::IncompatibleDevice` - none of the preferred formats or layouts are supported.
+	/// * The device's probe error, if probing failed. See `Device::probe_error()`.
+	pub fn negotiate(&self) -> Result<StreamConfig> {
+		self.preferences.negotiate(&self.device)
+	}
+
+	/// Negotiate the configuration and open the stream. It returns the stream and the configuration
+	/// that was used.
+	///
+	/// # Errors
+	///
+	/// The errors from `negotiate()` and `Device::open_outstream()`.
+	pub fn open<WriteCB>(self, write_callback: WriteCB) -> Result<(OutStream<'a>, StreamConfig)>
+		where WriteCB: 'a + FnMut(&mut OutStreamWriter) + Send {
+
+		let mut config = self.negotiate()?;
+
+		let stream = self.device.open_outstream_with_options(
+			config.sample_rate,
+			config.format,
+			config.layout.clone(),
+			config.software_latency,
+			write_callback,
+			self.underflow_callback,
+			self.error_callback,
+			self.options,
+		)?;
+
+		config.software_latency = stream.software_latency();
+		Ok((stream, config))
+	}
+
+	/// Negotiate the configuration and open a `SupervisedOutStream`, which reopens the stream with
+	/// the same configuration and callbacks if it fails. It returns the stream and the configuration
+	/// that was used.
+	///
+	/// # Errors
+	///
+	/// The same as `open()`.
+	pub fn open_supervised<WriteCB>(self, write_callback: WriteCB) -> Result<(SupervisedOutStream<'a>, StreamConfig)>
+		where WriteCB: 'a + FnMut(&mut OutStreamWriter) + Send {
+
+		let config = self.negotiate()?;
+
+		let stream = SupervisedOutStream::open(
+			&self.device,
+			config,
+			self.options,
+			Box::new(write_callback),
+			self.underflow_callback,
+			self.error_callback,
+		)?;
+
+		let config = stream.config().clone();
+		Ok((stream, config))
+	}
+
+	/// Negotiate the configuration and open a `FollowingOutStream` on the builder's device, which
+	/// moves to the default output device whenever it changes. The builder's device should
+	/// normally be the current default, from `Context::default_output_device()`.
+	///
+	/// `callback` fills a buffer of interleaved samples in the negotiated layout. `crossfade` is
+	/// the length in seconds of the fade out and fade in when the stream moves, or 0 to move
+	/// straight away. It returns the stream and the configuration that was used.
+	///
+	/// # Errors
+	///
+	/// The same as `open()`.
+	pub fn open_following_default<CB>(self, crossfade: f64, callback: CB) -> Result<(FollowingOutStream<'a>, StreamConfig)>
+		where CB: 'a + FnMut(&mut [f32]) + Send {
+
+		let config = self.negotiate()?;
+
+		let stream = FollowingOutStream::open(
+			&self.device,
+			config,
+			self.options,
+			crossfade,
+			Box::new(callback),
+			self.underflow_callback,
+			self.error_callback,
+		)?;
+
+		let config = stream.config().clone();
+		Ok((stream, config))
+	}
+}
+
+/// `InStreamBuilder` opens an `InStream` on a device, choosing the format, sample rate and channel
+/// layout from lists of preferences according to what the device supports. It is obtained using
+/// `Device::instream_builder()`. See `OutStreamBuilder` for how the configuration is chosen.
+///
+/// # Examples
+///
+/// ```
+/// let mut ctx = soundio::Context::new();
+/// ctx.connect_backend(soundio::Backend::Dummy).expect("Couldn't connect to backend");
+/// ctx.flush_events();
+/// let dev = ctx.default_input_device().expect("No input device");
+///
+/// let (stream, config) = dev.instream_builder()
+///     .formats(&[soundio::native::S16NE])
+///     .sample_rate(44100)
+///     .open(|_: &mut soundio::InStreamReader| {})
+///     .expect("Couldn't open stream");
+/// ```
+pub struct InStreamBuilder<'a> {
+	device: Device<'a>,
+	preferences: StreamPreferences,
+	options: StreamOptions,
+	overflow_callback: Option<Box<FnMut() + Send + 'a>>,
+	error_callback: Option<Box<FnMut(Error) + Send + 'a>>,
+}
+
+impl<'a> InStreamBuilder<'a> {
+	// Use `Device::instream_builder()` rather than calling this directly.
+	pub(crate) fn new(device: &Device<'a>) -> InStreamBuilder<'a> {
+		InStreamBuilder {
+			device: device.clone(),
+			preferences: StreamPreferences::new(),
+			options: StreamOptions::default(),
+			overflow_callback: None,
+			error_callback: None,
+		}
+	}
+
+	/// The formats to use, in order of preference.
+	pub fn formats(mut self, formats: &[Format]) -> InStreamBuilder<'a> {
+		self.preferences.formats = formats.to_vec();
+		self
+	}
+
+	/// The sample rate to use, or the nearest one that the device supports.
+	pub fn sample_rate(mut self, sample_rate: i32) -> InStreamBuilder<'a> {
+		self.preferences.sample_rate = Some(sample_rate);
+		self
+	}
+
+	/// The channel layouts to use, in order of preference.
+	pub fn layouts(mut self, layouts: &[ChannelLayout]) -> InStreamBuilder<'a> {
+		self.preferences.layouts = layouts.to_vec();
+		self
+	}
+
+	/// The requested software latency in seconds. See `OutStreamBuilder::software_latency()`.
+	pub fn software_latency(mut self, latency: f64) -> InStreamBuilder<'a> {
+		self.preferences.software_latency = latency;
+		self
+	}
+
+	/// The name of the stream. PulseAudio uses this for the stream name, JACK uses it for the
+	/// client name and WASAPI uses it for the session display name. It must not contain a colon.
+	///
+	/// If it is invalid `open()` returns `Error::Invalid`, or `Error::EncodingString` if it contains
+	/// a nul character.
+	pub fn name(mut self, name: &str) -> InStreamBuilder<'a> {
+		self.options.name = Some(name.to_string());
+		self
+	}
+
+	/// Hint that this stream is nonterminal, i.e. its data is passed on to another stream.
+	/// This is only used by JACK. The default is `false`.
+	pub fn non_terminal_hint(mut self, non_terminal_hint: bool) -> InStreamBuilder<'a> {
+		self.options.non_terminal_hint = non_terminal_hint;
+		self
+	}
+
+	/// The callback that is called when input is lost. See `Device::open_instream()`.
+	pub fn overflow_callback<OverflowCB: 'a + FnMut() + Send>(mut self, callback: OverflowCB) -> InStreamBuilder<'a> {
+		self.overflow_callback = Some(Box::new(callback));
+		self
+	}
+
+	/// The callback that is called when there is a streaming error. See `Device::open_instream()`.
+	pub fn error_callback<ErrorCB: 'a + FnMut(Error) + Send>(mut self, callback: ErrorCB) -> InStreamBuilder<'a> {
+		self.error_callback = Some(Box::new(callback));
+		self
+	}
+
+	/// Work out the configuration that `open()` would use, without opening the stream.
+	///
+	/// # Errors
+	///
+	/// * `Error::IncompatibleDevice` - none of the preferred formats or layouts are supported.
+	/// * The device's probe error, if probing failed. See `Device::probe_error()`.
+	pub fn negotiate(&self) -> Result<StreamConfig> {
+		self.preferences.negotiate(&self.device)
+	}
+
+	/// Negotiate the configuration and open the stream. It returns the stream and the configuration
+	/// that was used.
+	///
+	/// # Errors
+	///
+	/// The errors from `negotiate()` and `Device::open_instream()`.
+	pub fn open<ReadCB>(self, read_callback: ReadCB) -> Result<(InStream<'a>, StreamConfig)>
+		where ReadCB: 'a + FnMut(&mut InStreamReader) + Send {
+
+		let mut config = self.negotiate()?;
+
+		let stream = self.device.open_instream_with_options(
+			config.sample_rate,
+			config.format,
+			config.layout.clone(),
+			config.software_latency,
+			read_callback,
+			self.overflow_callback,
+			self.error_callback,
+			self.options,
+		)?;
+
+		config.software_latency = stream.software_latency();
+		Ok((stream, config))
+	}
+
+	/// Negotiate the configuration and open a `SupervisedInStream`, which reopens the stream with
+	/// the same configuration and callbacks if it fails. It returns the stream and the configuration
+	/// that was used.
+	///
+	/// # Errors
+	///
+	/// The same as `open()`.
+	pub fn open_supervised<ReadCB>(self, read_callback: ReadCB) -> Result<(SupervisedInStream<'a>, StreamConfig)>
+		where ReadCB: 'a + FnMut(&mut InStreamReader) + Send {
+
+		let config = self.negotiate()?;
+
+		let stream = SupervisedInStream::open(
+			&self.device,
+			config,
+			self.options,
+			Box::new(read_callback),
+			self.overflow_callback,
+			self.error_callback,
+		)?;
+
+		let config = stream.config().clone();
+		Ok((stream, config))
+	}
+
+	/// Negotiate the configuration and open a `FollowingInStream` on the builder's device, which
+	/// moves to the default input device whenever it changes. See
+	/// `OutStreamBuilder::open_following_default()`.
+	///
+	/// `callback` is passed the recorded interleaved samples in the negotiated layout.
+	pub fn open_following_default<CB>(self, crossfade: f64, callback: CB) -> Result<(FollowingInStream<'a>, StreamConfig)>
+		where CB: 'a + FnMut(&[f32]) + Send {
+
+		let config = self.negotiate()?;
+
+		let stream = FollowingInStream::open(
+			&self.device,
+			config,
+			self.options,
+			crossfade,
+			Box::new(callback),
+			self.overflow_callback,
+			self.error_callback,
+		)?;
+
+		let config = stream.config().clone();
+		Ok((stream, config))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::context::*;
+	use super::super::backend::*;
+
+	#[test]
+	fn negotiate_dummy_outstream() {
+		let mut ctx = Context::new();
+		ctx.connect_backend(Backend::Dummy).expect("Couldn't connect to backend");
+		ctx.flush_events();
+
+		let dev = ctx.default_output_device().expect("No output device");
+		let stereo = ChannelLayout::get_builtin(ChannelLayoutId::Stereo);
+
+		let builder = dev.outstream_builder()
+			.formats(&[Format::Invalid, Format::S16LE])
+			.sample_rate(44100)
+			.layouts(&[stereo.clone()])
+			.name("soundio-rs test");
+
+		let config = builder.negotiate().expect("Couldn't negotiate");
+		assert_eq!(config.format, Format::S16LE);
+		assert_eq!(config.sample_rate, 44100);
+		assert_eq!(config.layout, stereo);
+
+		let (stream, opened) = builder.open(|_: &mut OutStreamWriter| {}).expect("Couldn't open stream");
+		assert_eq!(opened.format, config.format);
+		assert_eq!(stream.name(), "soundio-rs test");
+
+		match dev.outstream_builder().name("a:b").open(|_: &mut OutStreamWriter| {}) {
+			Err(Error::Invalid) => {},
+			Err(e) => panic!("Expected Error::Invalid, got {:?}", e),
+			Ok(_) => panic!("Expected Error::Invalid"),
+		}
+
+		let unsupported = dev.outstream_builder().formats(&[Format::Invalid]).negotiate();
+		match unsupported {
+			Err(Error::IncompatibleDevice) => {},
+			x => panic!("Expected Error::IncompatibleDevice, got {:?}", x),
+		}
+	}
+
+	#[test]
+	fn unknown_current_layout() {
+		let mono = ChannelLayout::get_builtin(ChannelLayoutId::Mono);
+		let stereo = ChannelLayout::get_builtin(ChannelLayoutId::Stereo);
+		let unknown = ChannelLayout {
+			name: String::new(),
+			channels: Vec::new(),
+		};
+
+		assert_eq!(default_layout(mono.clone(), &[stereo.clone()]).unwrap(), mono);
+		assert_eq!(default_layout(unknown.clone(), &[stereo.clone(), mono.clone()]).unwrap(), stereo);
+		match default_layout(unknown, &[]) {
+			Err(Error::IncompatibleDevice) => {},
+			x => panic!("Expected Error::IncompatibleDevice, got {:?}", x),
+		}
+	}
+}
@@ -0,0 +1,166 @@
+use super::error::*;
+use super::context::*;
+use super::ring_buffer::*;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "async")]
+use std::sync::atomic::AtomicBool;
+
+// The number of events that can be waiting in a stream's queue. If it is full further
+// events are dropped and counted.
+const EVENT_QUEUE_CAPACITY: usize = 64;
+
+/// `StreamEvent` is a diagnostic event from an `OutStream` or `InStream` that would otherwise be
+/// lost, for example an underflow when you didn't provide an underflow callback.
+///
+/// Events are generated on the audio thread, so they are passed through a lock-free queue and
+/// can be retrieved on any other thread using `OutStream::poll_event()` or
+/// `InStream::poll_event()`. If the `log` feature is enabled they are also logged as they are
+/// retrieved, and any that are left when the stream is dropped are logged then. soundio-rs never
+/// prints them. With the `async` feature they can also be received from `OutStream::events()` or
+/// `InStream::events()`.
+#[derive(Debug, Copy, Clone)]
+pub enum StreamEvent {
+	/// The output stream underflowed and there was no underflow callback.
+	Underflow,
+	/// The input stream overflowed and there was no overflow callback.
+	Overflow,
+	/// The stream reported an error and there was no error callback.
+	Error(Error),
+	/// The implicit commit when an `OutStreamWriter` was dropped failed, and there was no error
+	/// callback to pass it to.
+	EndWriteFailed(Error),
+	/// The implicit commit when an `InStreamReader` was dropped failed, and there was no error
+	/// callback to pass it to.
+	EndReadFailed(Error),
+}
+
+// The audio thread end of a stream's event queue.
+pub(crate) struct EventSender {
+	producer: RingBufferProducer<StreamEvent>,
+	dropped: Arc<AtomicUsize>,
+	// Set when an event is sent, for a `StreamEvents` to wake its task. The executor's waker isn't
+	// called on the audio thread because it might lock or allocate.
+	#[cfg(feature = "async")]
+	pending: Arc<AtomicBool>,
+}
+
+// The control thread end of a stream's event queue.
+pub(crate) struct EventReceiver {
+	consumer: RingBufferConsumer<StreamEvent>,
+	dropped: Arc<AtomicUsize>,
+	#[cfg(feature = "async")]
+	pending: Arc<AtomicBool>,
+}
+
+// Create the event queue for a new stream.
+pub(crate) fn event_queue(context: &ContextInner) -> (EventSender, EventReceiver) {
+	let (producer, consumer) = context.ring_buffer::<StreamEvent>(EVENT_QUEUE_CAPACITY).split();
+	let dropped = Arc::new(AtomicUsize::new(0));
+	#[cfg(feature = "async")]
+	let pending = Arc::new(AtomicBool::new(false));
+	(
+		EventSender {
+			producer: producer,
+			dropped: dropped.clone(),
+			#[cfg(feature = "async")]
+			pending: pending.clone(),
+		},
+		EventReceiver {
+			consumer: consumer,
+			dropped: dropped,
+			#[cfg(feature = "async")]
+			pending: pending,
+		},
+	)
+}
+
+impl EventSender {
+	// Queue an event. This never blocks or allocates.
+	pub fn send(&mut self, event: StreamEvent) {
+		if self.producer.write(&[event]) == 0 {
+			self.dropped.fetch_add(1, Ordering::Relaxed);
+		}
+		#[cfg(feature = "async")]
+		self.pending.store(true, Ordering::Release);
+	}
+}
+
+impl EventReceiver {
+	pub fn poll(&mut self) -> Option<StreamEvent> {
+		let mut event = [StreamEvent::Underflow];
+		if self.consumer.read(&mut event) == 0 {
+			return None;
+		}
+		log_event(&event[0]);
+		Some(event[0])
+	}
+
+	pub fn dropped(&self) -> usize {
+		self.dropped.load(Ordering::Relaxed)
+	}
+
+	// The flag that is set when an event is sent. A `StreamEvents` clears it and wakes its task.
+	#[cfg(feature = "async")]
+	pub fn pending(&self) -> Arc<AtomicBool> {
+		self.pending.clone()
+	}
+}
+
+impl Drop for EventReceiver {
+	fn drop(&mut self) {
+		if cfg!(feature = "log") {
+			while self.poll().is_some() {}
+		}
+	}
+}
+
+#[cfg(feature = "log")]
+fn log_event(event: &StreamEvent) {
+	match *event {
+		StreamEvent::Underflow => warn!("soundio: output stream underflow"),
+		StreamEvent::Overflow => warn!("soundio: input stream overflow"),
+		StreamEvent::Error(e) => error!("soundio: stream error: {}", e),
+		StreamEvent::EndWriteFailed(e) => error!("soundio: error writing outstream: {}", e),
+		StreamEvent::EndReadFailed(e) => error!("soundio: error reading instream: {}", e),
+	}
+}
+
+#[cfg(not(feature = "log"))]
+fn log_event(_: &StreamEvent) {
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn overflow_drops_events() {
+		let ctx = Context::new();
+		let (mut sender, mut receiver) = event_queue(&ctx.inner);
+		// The ring buffer may be rounded up from `EVENT_QUEUE_CAPACITY`.
+		let capacity = receiver.consumer.capacity();
+
+		for i in 0..capacity + 3 {
+			sender.send(StreamEvent::Error(if i % 2 == 0 { Error::Streaming } else { Error::Underflow }));
+		}
+		assert_eq!(receiver.dropped(), 3);
+
+		for i in 0..capacity {
+			match receiver.poll() {
+				Some(StreamEvent::Error(e)) => assert_eq!(e, if i % 2 == 0 { Error::Streaming } else { Error::Underflow }),
+				other => panic!("Unexpected event {:?}", other),
+			}
+		}
+		assert!(receiver.poll().is_none());
+
+		// There is room again once the queue has been emptied.
+		sender.send(StreamEvent::Overflow);
+		match receiver.poll() {
+			Some(StreamEvent::Overflow) => {},
+			other => panic!("Unexpected event {:?}", other),
+		}
+		assert_eq!(receiver.dropped(), 3);
+	}
+}
@@ -0,0 +1,606 @@
+use super::error::*;
+use super::types::*;
+use super::backend::*;
+use super::context::*;
+use super::device::*;
+use super::outstream::*;
+use super::instream::*;
+use super::panic_policy::*;
+use super::stream_builder::*;
+use super::util::*;
+
+use std::collections::VecDeque;
+use std::os::raw::c_int;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicIsize, Ordering};
+
+/// `RecoveryEvent` describes something a `SupervisedOutStream` or `SupervisedInStream` did to
+/// keep the stream running. They are retrieved using `poll_event()` on the supervised stream.
+#[derive(Debug, Clone)]
+pub enum RecoveryEvent {
+	/// The stream passed this error to its error callback, so it is being reopened. This is
+	/// always `Error::Streaming`; other errors, such as `Error::Underflow`, are only passed to your
+	/// error callback.
+	StreamFailed(Error),
+	/// The backend disconnected with this error, so the context is being reconnected.
+	BackendDisconnected(Error),
+	/// The context was reconnected to this backend.
+	Reconnected(Backend),
+	/// The stream was reopened and restarted on the device with this id. `fallback` is `true` if
+	/// the original device couldn't be found, so the default device was used instead.
+	Recovered {
+		device_id: String,
+		fallback: bool,
+	},
+	/// Recovery failed with this error. It is tried again the next time the stream is
+	/// supervised.
+	RecoveryFailed(Error),
+}
+
+// The state shared by both kinds of supervised stream.
+struct Supervisor<'a> {
+	context: Arc<ContextInner<'a>>,
+	// The backend to reconnect to.
+	backend: Backend,
+	// The device to reopen, identified by `Device::id()` and `Device::is_raw()`.
+	aim: DeviceAim,
+	device_id: String,
+	is_raw: bool,
+	config: StreamConfig,
+	options: StreamOptions,
+	panic_policy: PanicPolicy,
+	// The error passed to the current stream's error callback that it must be reopened for, or 0.
+	error: Arc<AtomicIsize>,
+	// Whether the backend disconnected and hasn't been reconnected yet.
+	disconnected: bool,
+	started: bool,
+	paused: bool,
+	events: VecDeque<RecoveryEvent>,
+}
+
+impl<'a> Supervisor<'a> {
+	fn new(device: &Device<'a>, config: StreamConfig, options: StreamOptions) -> Supervisor<'a> {
+		device.context.add_supervised_stream();
+		Supervisor {
+			context: device.context.clone(),
+			backend: device.context.current_backend(),
+			aim: device.aim(),
+			device_id: device.id(),
+			is_raw: device.is_raw(),
+			config: config,
+			options: options,
+			panic_policy: PanicPolicy::default(),
+			error: Arc::new(AtomicIsize::new(0)),
+			disconnected: false,
+			started: false,
+			paused: false,
+			events: VecDeque::new(),
+		}
+	}
+
+	// Check whether the stream needs to be reopened, recording why. `open` is whether there is
+	// a stream at the moment.
+	fn needs_recovery(&mut self, ctx: &Context<'a>, open: bool) -> Result<bool> {
+		if !Arc::ptr_eq(&ctx.inner, &self.context) {
+			return Err(Error::Invalid);
+		}
+		if let Some(e) = self.context.take_backend_disconnect() {
+			self.disconnected = true;
+			self.events.push_back(RecoveryEvent::BackendDisconnected(e));
+		}
+		let failed = match self.error.swap(0, Ordering::AcqRel) {
+			0 => false,
+			x => {
+				self.events.push_back(RecoveryEvent::StreamFailed((x as c_int).into()));
+				true
+			},
+		};
+		Ok(self.disconnected || failed || !open)
+	}
+
+	// Reconnect the context if necessary and find the device to reopen the stream on. The
+	// stream must have been dropped first.
+	fn find_device(&mut self, ctx: &mut Context<'a>) -> Result<(Device<'a>, bool)> {
+		if self.disconnected {
+			ctx.disconnect();
+			ctx.connect_backend(self.backend)?;
+			ctx.flush_events();
+			self.disconnected = false;
+			self.events.push_back(RecoveryEvent::Reconnected(self.backend));
+		}
+
+		let count = match self.aim {
+			DeviceAim::Output => ctx.output_device_count(),
+			DeviceAim::Input => ctx.input_device_count(),
+		};
+		for i in 0..count {
+			let device = match self.aim {
+				DeviceAim::Output => ctx.output_device(i),
+				DeviceAim::Input => ctx.input_device(i),
+			};
+			// Devices with probe errors are skipped.
+			if let Ok(device) = device {
+				if device.probe_error().is_none() && device.id() == self.device_id && device.is_raw() == self.is_raw {
+					return Ok((device, false));
+				}
+			}
+		}
+
+		let device = match self.aim {
+			DeviceAim::Output => ctx.default_output_device()?,
+			DeviceAim::Input => ctx.default_input_device()?,
+		};
+		Ok((device, true))
+	}
+
+	// The error callback for the stream. It passes every error to the user's callback. Only
+	// `Error::Streaming` means the stream must be reopened; other errors, such as a failed commit
+	// reporting `Error::Underflow`, leave it usable. That error is recorded, and
+	// `Context::wait_events()` is woken up so that the stream is reopened promptly. Backend
+	// disconnects are reported through the context instead.
+	fn error_callback(&self, user_callback: Option<SendPtr<FnMut(Error) + Send + 'a>>) -> impl FnMut(Error) + Send + 'a {
+		let error = self.error.clone();
+		let context = self.context.clone();
+		move |err: Error| {
+			let fatal = err == Error::Streaming;
+			if fatal {
+				error.store(c_int::from(err) as isize, Ordering::Release);
+			}
+			if let Some(cb) = user_callback {
+				unsafe { (*cb.0)(err); }
+			}
+			if fatal {
+				context.wakeup();
+			}
+		}
+	}
+
+	// Recover `stream` if it has failed, using `open_on` to open the new stream.
+	fn supervise<S, F>(&mut self, ctx: &mut Context<'a>, stream: &mut Option<S>, mut open_on: F) -> Result<()>
+		where S: StreamControl, F: FnMut(&Supervisor<'a>, &Device<'a>) -> Result<S> {
+
+		// A callback that has panicked can't be trusted with a new stream.
+		if stream.as_ref().map_or(false, |s| s.has_panicked()) {
+			return Ok(());
+		}
+		let open = stream.is_some();
+		if !self.needs_recovery(ctx, open)? {
+			return Ok(());
+		}
+
+		// The old stream must be destroyed before the context is reconnected.
+		*stream = None;
+
+		let (device, fallback) = match self.find_device(ctx) {
+			Ok(x) => x,
+			Err(e) => return Err(self.failed(e)),
+		};
+		let mut new_stream = match open_on(self, &device) {
+			Ok(stream) => stream,
+			Err(e) => return Err(self.failed(e)),
+		};
+		if self.started {
+			if let Err(e) = new_stream.start() {
+				return Err(self.failed(e));
+			}
+			if self.paused {
+				let _ = new_stream.pause(true);
+			}
+		}
+		*stream = Some(new_stream);
+		self.recovered(&device, fallback);
+		Ok(())
+	}
+
+	fn start<S: StreamControl>(&mut self, stream: &mut Option<S>) -> Result<()> {
+		match *stream {
+			Some(ref mut stream) => stream.start()?,
+			None => return Err(Error::Streaming),
+		}
+		self.started = true;
+		Ok(())
+	}
+
+	fn pause<S: StreamControl>(&mut self, stream: &mut Option<S>, pause: bool) -> Result<()> {
+		match *stream {
+			Some(ref mut stream) => stream.pause(pause)?,
+			None => return Err(Error::Streaming),
+		}
+		self.paused = pause;
+		Ok(())
+	}
+
+	fn set_panic_policy<S: StreamControl>(&mut self, stream: &mut Option<S>, policy: PanicPolicy) {
+		self.panic_policy = policy;
+		if let Some(ref mut stream) = *stream {
+			stream.set_panic_policy(policy);
+		}
+	}
+
+	fn recovered(&mut self, device: &Device<'a>, fallback: bool) {
+		self.events.push_back(RecoveryEvent::Recovered {
+			device_id: device.id(),
+			fallback: fallback,
+		});
+	}
+
+	fn failed(&mut self, error: Error) -> Error {
+		self.events.push_back(RecoveryEvent::RecoveryFailed(error));
+		error
+	}
+}
+
+impl<'a> Drop for Supervisor<'a> {
+	fn drop(&mut self) {
+		self.context.remove_supervised_stream();
+	}
+}
+
+/// `SupervisedOutStream` is an output stream that recovers from errors that would otherwise
+/// require you to destroy and recreate the stream, i.e. `Error::Streaming` passed to the error
+/// callback, and the backend disconnecting. Other errors passed to the error callback, such as
+/// `Error::Underflow` from a failed commit, are passed on to yours without reopening the stream.
+///
+/// It is obtained using `OutStreamBuilder::open_supervised()`, and keeps your callbacks and the
+/// negotiated `StreamConfig`. When `supervise()` finds that the stream has failed it drops it,
+/// reconnects the `Context` if the backend disconnected, then opens a new stream with the same
+/// configuration on the device with the same `Device::id()`, or the default output device if that
+/// has gone. If the stream had been started it is started again. Each step is reported as a
+/// `RecoveryEvent`.
+///
+/// While a supervised stream is using a `Context`, the default backend disconnect callback
+/// doesn't panic. Any other streams on the context must be dropped before it is reconnected, so
+/// a context with more than one stream can only recover from `Error::Streaming`, not a backend
+/// disconnect.
+///
+/// If one of your callbacks panics the stream is not recovered.
+///
+/// # Examples
+///
+/// ```no_run
+/// let mut ctx = soundio::Context::new();
+/// ctx.connect().unwrap();
+/// ctx.flush_events();
+/// let dev = ctx.default_output_device().unwrap();
+///
+/// let (mut stream, _) = dev.outstream_builder()
+///     .open_supervised(|_: &mut soundio::OutStreamWriter| {})
+///     .unwrap();
+/// stream.start().unwrap();
+///
+/// loop {
+///     ctx.wait_events();
+///     if let Err(e) = stream.supervise(&mut ctx) {
+///         println!("Couldn't recover the stream yet: {}", e);
+///     }
+///     while let Some(event) = stream.poll_event() {
+///         println!("{:?}", event);
+///     }
+/// }
+/// ```
+pub struct SupervisedOutStream<'a> {
+	// This must be dropped before the callbacks, which it points to.
+	stream: Option<OutStream<'a>>,
+	supervisor: Supervisor<'a>,
+	// The user's callbacks. They are boxed so that each stream can be given a pointer to them.
+	write_callback: Box<FnMut(&mut OutStreamWriter) + Send + 'a>,
+	underflow_callback: Option<Box<FnMut() + Send + 'a>>,
+	error_callback: Option<Box<FnMut(Error) + Send + 'a>>,
+}
+
+impl<'a> SupervisedOutStream<'a> {
+	// Open the stream. Use `OutStreamBuilder::open_supervised()` rather than calling this directly.
+	pub(crate) fn open(
+				device: &Device<'a>,
+				config: StreamConfig,
+				options: StreamOptions,
+				mut write_callback: Box<FnMut(&mut OutStreamWriter) + Send + 'a>,
+				mut underflow_callback: Option<Box<FnMut() + Send + 'a>>,
+				mut error_callback: Option<Box<FnMut(Error) + Send + 'a>>,
+				) -> Result<SupervisedOutStream<'a>> {
+
+		let mut supervisor = Supervisor::new(device, config, options);
+		let stream = open_outstream_on(&supervisor, device, &mut write_callback, &mut underflow_callback, &mut error_callback)?;
+		supervisor.config.software_latency = stream.software_latency();
+
+		Ok(SupervisedOutStream {
+			stream: Some(stream),
+			supervisor: supervisor,
+			write_callback: write_callback,
+			underflow_callback: underflow_callback,
+			error_callback: error_callback,
+		})
+	}
+
+	/// Check whether the stream has failed, and if it has, try to recover it as described above.
+	/// This should be called regularly on the thread that owns `ctx`, e.g. after each call to
+	/// `Context::wait_events()`. The stream's error callback wakes up `wait_events()`.
+	///
+	/// `ctx` must be the `Context` that the stream was opened from.
+	///
+	/// # Errors
+	///
+	/// * `Error::Invalid` - `ctx` is a different context.
+	/// * Any error from reconnecting, finding the device, or opening and starting the new stream.
+	///   The stream is left closed and `supervise()` tries again next time.
+	pub fn supervise(&mut self, ctx: &mut Context<'a>) -> Result<()> {
+		let write_callback = &mut self.write_callback;
+		let underflow_callback = &mut self.underflow_callback;
+		let error_callback = &mut self.error_callback;
+		self.supervisor.supervise(ctx, &mut self.stream, |supervisor, device| {
+			open_outstream_on(supervisor, device, write_callback, underflow_callback, error_callback)
+		})
+	}
+
+	/// Take the next `RecoveryEvent`, or `None` if there aren't any.
+	pub fn poll_event(&mut self) -> Option<RecoveryEvent> {
+		self.supervisor.events.pop_front()
+	}
+
+	/// Start the stream. It is started again whenever it is recovered. See `OutStream::start()`.
+	///
+	/// # Errors
+	///
+	/// * `Error::Streaming` - the stream is closed because recovery failed.
+	/// * Any error from `OutStream::start()`.
+	pub fn start(&mut self) -> Result<()> {
+		self.supervisor.start(&mut self.stream)
+	}
+
+	/// Pause or unpause the stream. The state is kept when it is recovered. See `OutStream::pause()`.
+	///
+	/// # Errors
+	///
+	/// * `Error::Streaming` - the stream is closed because recovery failed.
+	/// * Any error from `OutStream::pause()`.
+	pub fn pause(&mut self, pause: bool) -> Result<()> {
+		self.supervisor.pause(&mut self.stream, pause)
+	}
+
+	/// Set the `PanicPolicy` of this stream and the streams it is recovered as.
+	pub fn set_panic_policy(&mut self, policy: PanicPolicy) {
+		self.supervisor.set_panic_policy(&mut self.stream, policy)
+	}
+
+	/// The configuration used to open the stream.
+	pub fn config(&self) -> &StreamConfig {
+		&self.supervisor.config
+	}
+
+	/// The current stream, or `None` if it is closed because recovery failed.
+	pub fn stream(&self) -> Option<&OutStream<'a>> {
+		self.stream.as_ref()
+	}
+
+	/// The current stream, or `None` if it is closed because recovery failed. Changes made to it,
+	/// such as the volume, are not kept when it is recovered.
+	pub fn stream_mut(&mut self) -> Option<&mut OutStream<'a>> {
+		self.stream.as_mut()
+	}
+}
+
+// Open an output stream for a `SupervisedOutStream` on `device` with the user's callbacks.
+fn open_outstream_on<'a>(
+			supervisor: &Supervisor<'a>,
+			device: &Device<'a>,
+			write_callback: &mut Box<FnMut(&mut OutStreamWriter) + Send + 'a>,
+			underflow_callback: &mut Option<Box<FnMut() + Send + 'a>>,
+			error_callback: &mut Option<Box<FnMut(Error) + Send + 'a>>,
+			) -> Result<OutStream<'a>> {
+
+	let write_callback: SendPtr<FnMut(&mut OutStreamWriter) + Send + 'a> = SendPtr(&mut **write_callback);
+	let underflow_callback = underflow_callback.as_mut().map(|cb| SendPtr(&mut **cb as *mut (FnMut() + Send + 'a)));
+	let error_callback = error_callback.as_mut().map(|cb| SendPtr(&mut **cb as *mut (FnMut(Error) + Send + 'a)));
+
+	// Only one stream exists at a time, so the callbacks are never called concurrently.
+	let mut stream = device.open_outstream_with_options(
+		supervisor.config.sample_rate,
+		supervisor.config.format,
+		supervisor.config.layout.clone(),
+		supervisor.config.software_latency,
+		move |writer: &mut OutStreamWriter| unsafe { (*write_callback.0)(writer) },
+		underflow_callback.map(|cb| move || unsafe { (*cb.0)() }),
+		Some(supervisor.error_callback(error_callback)),
+		supervisor.options.clone(),
+	)?;
+	stream.set_panic_policy(supervisor.panic_policy);
+	Ok(stream)
+}
+
+/// `SupervisedInStream` is an input stream that recovers from errors that would otherwise
+/// require you to destroy and recreate the stream. It is obtained using
+/// `InStreamBuilder::open_supervised()` and works the same way as `SupervisedOutStream`.
+pub struct SupervisedInStream<'a> {
+	// This must be dropped before the callbacks, which it points to.
+	stream: Option<InStream<'a>>,
+	supervisor: Supervisor<'a>,
+	// The user's callbacks. They are boxed so that each stream can be given a pointer to them.
+	read_callback: Box<FnMut(&mut InStreamReader) + Send + 'a>,
+	overflow_callback: Option<Box<FnMut() + Send + 'a>>,
+	error_callback: Option<Box<FnMut(Error) + Send + 'a>>,
+}
+
+impl<'a> SupervisedInStream<'a> {
+	// Open the stream. Use `InStreamBuilder::open_supervised()` rather than calling this directly.
+	pub(crate) fn open(
+				device: &Device<'a>,
+				config: StreamConfig,
+				options: StreamOptions,
+				mut read_callback: Box<FnMut(&mut InStreamReader) + Send + 'a>,
+				mut overflow_callback: Option<Box<FnMut() + Send + 'a>>,
+				mut error_callback: Option<Box<FnMut(Error) + Send + 'a>>,
+				) -> Result<SupervisedInStream<'a>> {
+
+		let mut supervisor = Supervisor::new(device, config, options);
+		let stream = open_instream_on(&supervisor, device, &mut read_callback, &mut overflow_callback, &mut error_callback)?;
+		supervisor.config.software_latency = stream.software_latency();
+
+		Ok(SupervisedInStream {
+			stream: Some(stream),
+			supervisor: supervisor,
+			read_callback: read_callback,
+			overflow_callback: overflow_callback,
+			error_callback: error_callback,
+		})
+	}
+
+	/// Check whether the stream has failed, and if it has, try to recover it. See
+	/// `SupervisedOutStream::supervise()`.
+	pub fn supervise(&mut self, ctx: &mut Context<'a>) -> Result<()> {
+		let read_callback = &mut self.read_callback;
+		let overflow_callback = &mut self.overflow_callback;
+		let error_callback = &mut self.error_callback;
+		self.supervisor.supervise(ctx, &mut self.stream, |supervisor, device| {
+			open_instream_on(supervisor, device, read_callback, overflow_callback, error_callback)
+		})
+	}
+
+	/// Take the next `RecoveryEvent`, or `None` if there aren't any.
+	pub fn poll_event(&mut self) -> Option<RecoveryEvent> {
+		self.supervisor.events.pop_front()
+	}
+
+	/// Start the stream. It is started again whenever it is recovered. See `InStream::start()`.
+	///
+	/// # Errors
+	///
+	/// * `Error::Streaming` - the stream is closed because recovery failed.
+	/// * Any error from `InStream::start()`.
+	pub fn start(&mut self) -> Result<()> {
+		self.supervisor.start(&mut self.stream)
+	}
+
+	/// Pause or unpause the stream. The state is kept when it is recovered. See `InStream::pause()`.
+	///
+	/// # Errors
+	///
+	/// * `Error::Streaming` - the stream is closed because recovery failed.
+	/// * Any error from `InStream::pause()`.
+	pub fn pause(&mut self, pause: bool) -> Result<()> {
+		self.supervisor.pause(&mut self.stream, pause)
+	}
+
+	/// Set the `PanicPolicy` of this stream and the streams it is recovered as.
+	pub fn set_panic_policy(&mut self, policy: PanicPolicy) {
+		self.supervisor.set_panic_policy(&mut self.stream, policy)
+	}
+
+	/// The configuration used to open the stream.
+	pub fn config(&self) -> &StreamConfig {
+		&self.supervisor.config
+	}
+
+	/// The current stream, or `None` if it is closed because recovery failed.
+	pub fn stream(&self) -> Option<&InStream<'a>> {
+		self.stream.as_ref()
+	}
+
+	/// The current stream, or `None` if it is closed because recovery failed.
+	pub fn stream_mut(&mut self) -> Option<&mut InStream<'a>> {
+		self.stream.as_mut()
+	}
+}
+
+// Open an input stream for a `SupervisedInStream` on `device` with the user's callbacks.
+fn open_instream_on<'a>(
+			supervisor: &Supervisor<'a>,
+			device: &Device<'a>,
+			read_callback: &mut Box<FnMut(&mut InStreamReader) + Send + 'a>,
+			overflow_callback: &mut Option<Box<FnMut() + Send + 'a>>,
+			error_callback: &mut Option<Box<FnMut(Error) + Send + 'a>>,
+			) -> Result<InStream<'a>> {
+
+	let read_callback: SendPtr<FnMut(&mut InStreamReader) + Send + 'a> = SendPtr(&mut **read_callback);
+	let overflow_callback = overflow_callback.as_mut().map(|cb| SendPtr(&mut **cb as *mut (FnMut() + Send + 'a)));
+	let error_callback = error_callback.as_mut().map(|cb| SendPtr(&mut **cb as *mut (FnMut(Error) + Send + 'a)));
+
+	// Only one stream exists at a time, so the callbacks are never called concurrently.
+	let mut stream = device.open_instream_with_options(
+		supervisor.config.sample_rate,
+		supervisor.config.format,
+		supervisor.config.layout.clone(),
+		supervisor.config.software_latency,
+		move |reader: &mut InStreamReader| unsafe { (*read_callback.0)(reader) },
+		overflow_callback.map(|cb| move || unsafe { (*cb.0)() }),
+		Some(supervisor.error_callback(error_callback)),
+		supervisor.options.clone(),
+	)?;
+	stream.set_panic_policy(supervisor.panic_policy);
+	Ok(stream)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::format::*;
+	use super::super::layout::*;
+
+	#[test]
+	fn recover_failed_stream() {
+		let mut ctx = Context::new();
+		ctx.connect_backend(Backend::Dummy).expect("Couldn't connect to backend");
+		ctx.flush_events();
+
+		let dev = ctx.default_output_device().expect("No output device");
+		let (mut stream, config) = dev.outstream_builder()
+			.formats(&[Format::Float32LE])
+			.layouts(&[ChannelLayout::get_default(2)])
+			.open_supervised(|_: &mut OutStreamWriter| {})
+			.expect("Couldn't open stream");
+		stream.start().expect("Couldn't start stream");
+
+		// Nothing has gone wrong yet.
+		stream.supervise(&mut ctx).expect("Couldn't supervise");
+		assert!(stream.poll_event().is_none());
+
+		// Simulate the error callback.
+		stream.supervisor.error.store(c_int::from(Error::Streaming) as isize, Ordering::Release);
+		stream.supervise(&mut ctx).expect("Couldn't recover");
+		match stream.poll_event() {
+			Some(RecoveryEvent::StreamFailed(Error::Streaming)) => {},
+			x => panic!("Expected StreamFailed, got {:?}", x),
+		}
+		match stream.poll_event() {
+			Some(RecoveryEvent::Recovered { ref device_id, fallback: false }) if *device_id == dev.id() => {},
+			x => panic!("Expected Recovered, got {:?}", x),
+		}
+		assert!(stream.poll_event().is_none());
+		stream.supervise(&mut ctx).expect("Couldn't supervise");
+		assert!(stream.poll_event().is_none());
+		assert_eq!(stream.config(), &config);
+		assert!(stream.stream().is_some());
+
+		match stream.supervise(&mut Context::new()) {
+			Err(Error::Invalid) => {},
+			x => panic!("Expected Error::Invalid, got {:?}", x),
+		}
+	}
+
+	#[test]
+	fn underflow_does_not_reopen() {
+		let mut ctx = Context::new();
+		ctx.connect_backend(Backend::Dummy).expect("Couldn't connect to backend");
+		ctx.flush_events();
+
+		let dev = ctx.default_output_device().expect("No output device");
+		let (mut stream, _) = dev.outstream_builder()
+			.formats(&[Format::Float32LE])
+			.layouts(&[ChannelLayout::get_default(2)])
+			.open_supervised(|_: &mut OutStreamWriter| {})
+			.expect("Couldn't open stream");
+
+		let mut errors = Vec::new();
+		{
+			let mut user_callback = |err: Error| errors.push(err);
+			let user_callback: &mut (FnMut(Error) + Send) = &mut user_callback;
+			let mut error_callback = stream.supervisor.error_callback(Some(SendPtr(user_callback as *mut _)));
+			error_callback(Error::Underflow);
+		}
+		assert_eq!(errors, vec![Error::Underflow]);
+
+		let before = stream.stream().map(|s| s as *const OutStream);
+		stream.supervise(&mut ctx).expect("Couldn't supervise");
+		assert!(stream.poll_event().is_none());
+		assert_eq!(stream.stream().map(|s| s as *const OutStream), before);
+	}
+}
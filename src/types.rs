@@ -4,7 +4,8 @@ extern crate libsoundio_sys as raw;
 /// all devices are either input or output. If a physical device supports
 /// both it is exposed as two devices with the same id, but with different
 /// aims returned by `Device::aim()`.
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum DeviceAim {
 	/// Capture / recording
 	Input, 
@@ -32,7 +33,8 @@ impl From<DeviceAim> for raw::SoundIoDeviceAim {
 
 /// Devices report their supported sample rates as ranges. For non-range sample
 /// rates `min` and `max` are the same.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SampleRateRange {
 	pub min: i32,
 	pub max: i32,
@@ -58,7 +60,8 @@ impl From<SampleRateRange> for raw::SoundIoSampleRateRange {
 
 /// This is used for reporting software latency, that is the latency not including
 /// latency due to hardware. It is returned by `Device::software_latency()`.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SoftwareLatency {
 	pub min: f64,
 	pub max: f64,
@@ -1,3 +1,8 @@
+use super::error::*;
+use super::outstream::*;
+use super::instream::*;
+use super::panic_policy::*;
+
 use std::ffi::CStr;
 use std::ptr;
 use std::os::raw::c_char;
@@ -43,4 +48,64 @@ pub fn utf8_to_string(s: *const c_char) -> String {
 	let c_str: &CStr = unsafe { CStr::from_ptr(s) };
 
 	c_str.to_str().unwrap_or("").to_string()
-}
\ No newline at end of file
+}
+
+// A raw pointer that can be moved to another thread. Streams that reopen themselves own the
+// user's callbacks and give each new stream closures that point to them. The callbacks are `Send`
+// and only one stream calls them at a time.
+pub(crate) struct SendPtr<T: ?Sized>(pub *mut T);
+
+unsafe impl<T: ?Sized + Send> Send for SendPtr<T> {}
+
+impl<T: ?Sized> Clone for SendPtr<T> {
+	fn clone(&self) -> SendPtr<T> {
+		SendPtr(self.0)
+	}
+}
+
+impl<T: ?Sized> Copy for SendPtr<T> {}
+
+// The controls of `OutStream` and `InStream` that streams which reopen themselves apply again to
+// each new stream.
+pub(crate) trait StreamControl {
+	fn start(&mut self) -> Result<()>;
+	fn pause(&mut self, pause: bool) -> Result<()>;
+	fn set_panic_policy(&mut self, policy: PanicPolicy);
+	fn has_panicked(&self) -> bool;
+}
+
+impl<'a> StreamControl for OutStream<'a> {
+	fn start(&mut self) -> Result<()> {
+		OutStream::start(self)
+	}
+
+	fn pause(&mut self, pause: bool) -> Result<()> {
+		OutStream::pause(self, pause)
+	}
+
+	fn set_panic_policy(&mut self, policy: PanicPolicy) {
+		OutStream::set_panic_policy(self, policy)
+	}
+
+	fn has_panicked(&self) -> bool {
+		OutStream::has_panicked(self)
+	}
+}
+
+impl<'a> StreamControl for InStream<'a> {
+	fn start(&mut self) -> Result<()> {
+		InStream::start(self)
+	}
+
+	fn pause(&mut self, pause: bool) -> Result<()> {
+		InStream::pause(self, pause)
+	}
+
+	fn set_panic_policy(&mut self, policy: PanicPolicy) {
+		InStream::set_panic_policy(self, policy)
+	}
+
+	fn has_panicked(&self) -> bool {
+		InStream::has_panicked(self)
+	}
+}
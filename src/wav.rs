@@ -0,0 +1,197 @@
+use super::format::*;
+use super::layout::*;
+use super::channels::*;
+use super::sample::*;
+
+use std::io;
+use std::io::Write;
+
+// Writing WAV files for `OfflineOutStream::render_wav()`. WAV files can only hold little endian
+// samples, which are unsigned if they are 8 bit and signed otherwise, so samples in other formats
+// are converted to the WAV format of the same size. This doesn't lose anything. The files use
+// `WAVE_FORMAT_EXTENSIBLE` so that the channel layout can be saved.
+
+const WAVE_FORMAT_PCM: u8 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u8 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+// The size of the `fmt ` chunk's data for `WAVE_FORMAT_EXTENSIBLE`.
+const FMT_SIZE: u32 = 40;
+
+// The format that samples in `format` are written to a WAV file in.
+fn wav_format(format: Format) -> Format {
+	match format {
+		Format::S8 | Format::U8 => Format::U8,
+		Format::S16LE | Format::S16BE | Format::U16LE | Format::U16BE => Format::S16LE,
+		Format::S24LE | Format::S24BE | Format::U24LE | Format::U24BE => Format::S24LE,
+		Format::S32LE | Format::S32BE | Format::U32LE | Format::U32BE => Format::S32LE,
+		Format::Float32LE | Format::Float32BE => Format::Float32LE,
+		Format::Float64LE | Format::Float64BE => Format::Float64LE,
+		Format::Invalid => panic!("Unknown format"),
+	}
+}
+
+// The number of bytes a sample in `format` takes in a WAV file. 24 bit samples are packed into 3
+// bytes, rather than the 4 libsoundio uses.
+fn wav_bytes_per_sample(format: Format) -> usize {
+	match wav_format(format) {
+		Format::S24LE => 3,
+		x => x.bytes_per_sample(),
+	}
+}
+
+// The `dwChannelMask` for `layout`. WAV files can only say which speakers the channels are for if
+// they are standard speakers in the standard order, so otherwise this is 0, which means the
+// speakers aren't specified.
+fn channel_mask(layout: &ChannelLayout) -> u32 {
+	let mut mask = 0u32;
+	for &channel in &layout.channels {
+		let bit = match channel {
+			ChannelId::FrontLeft => 0x1,
+			ChannelId::FrontRight => 0x2,
+			ChannelId::FrontCenter => 0x4,
+			ChannelId::Lfe => 0x8,
+			ChannelId::BackLeft => 0x10,
+			ChannelId::BackRight => 0x20,
+			ChannelId::FrontLeftCenter => 0x40,
+			ChannelId::FrontRightCenter => 0x80,
+			ChannelId::BackCenter => 0x100,
+			ChannelId::SideLeft => 0x200,
+			ChannelId::SideRight => 0x400,
+			ChannelId::TopCenter => 0x800,
+			ChannelId::TopFrontLeft => 0x1000,
+			ChannelId::TopFrontCenter => 0x2000,
+			ChannelId::TopFrontRight => 0x4000,
+			ChannelId::TopBackLeft => 0x8000,
+			ChannelId::TopBackCenter => 0x10000,
+			ChannelId::TopBackRight => 0x20000,
+			_ => return 0,
+		};
+		// The bits must be in increasing order.
+		if bit <= mask {
+			return 0;
+		}
+		mask |= bit;
+	}
+	mask
+}
+
+// Write the header of a WAV file holding `frame_count` frames of `format` samples. The data of
+// the `data` chunk must follow, then `write_wav_padding()`.
+pub(crate) fn write_wav_header<W: Write>(writer: &mut W, format: Format, layout: &ChannelLayout, sample_rate: i32, frame_count: usize) -> io::Result<()> {
+	let channel_count = layout.channels.len();
+	let bytes_per_sample = wav_bytes_per_sample(format);
+	let block_align = bytes_per_sample * channel_count;
+	let data_size = (frame_count as u64) * (block_align as u64);
+	// RIFF sizes are 32 bit, and the data must be padded to an even size.
+	let riff_size = 4 + (8 + FMT_SIZE as u64) + 8 + data_size + data_size % 2;
+	if riff_size > u32::max_value() as u64 {
+		return Err(io::Error::new(io::ErrorKind::InvalidInput, "too many frames for a WAV file"));
+	}
+	let bits = (bytes_per_sample * 8) as u16;
+	let sub_format = match wav_format(format) {
+		Format::Float32LE | Format::Float64LE => WAVE_FORMAT_IEEE_FLOAT,
+		_ => WAVE_FORMAT_PCM,
+	};
+
+	let mut header = Vec::with_capacity(68);
+	header.extend_from_slice(b"RIFF");
+	header.extend_from_slice(&(riff_size as u32).to_le_bytes());
+	header.extend_from_slice(b"WAVE");
+	header.extend_from_slice(b"fmt ");
+	header.extend_from_slice(&FMT_SIZE.to_le_bytes());
+	header.extend_from_slice(&WAVE_FORMAT_EXTENSIBLE.to_le_bytes());
+	header.extend_from_slice(&(channel_count as u16).to_le_bytes());
+	header.extend_from_slice(&(sample_rate as u32).to_le_bytes());
+	header.extend_from_slice(&((sample_rate as u32) * (block_align as u32)).to_le_bytes());
+	header.extend_from_slice(&(block_align as u16).to_le_bytes());
+	header.extend_from_slice(&bits.to_le_bytes());
+	// The size of the extension, then the valid bits per sample, which is all of them.
+	header.extend_from_slice(&22u16.to_le_bytes());
+	header.extend_from_slice(&bits.to_le_bytes());
+	header.extend_from_slice(&channel_mask(layout).to_le_bytes());
+	// The sub format GUID is the format code followed by a fixed suffix.
+	header.extend_from_slice(&[sub_format, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71]);
+	header.extend_from_slice(b"data");
+	header.extend_from_slice(&(data_size as u32).to_le_bytes());
+	writer.write_all(&header)
+}
+
+// Write the byte that pads the `data` chunk to an even size, if it is needed.
+pub(crate) fn write_wav_padding<W: Write>(writer: &mut W, format: Format, channel_count: usize, frame_count: usize) -> io::Result<()> {
+	if (frame_count * wav_bytes_per_sample(format) * channel_count) % 2 != 0 {
+		writer.write_all(&[0])?;
+	}
+	Ok(())
+}
+
+// Convert interleaved `format` samples in `data` to their WAV format, and append them to `out`.
+pub(crate) fn convert_to_wav(format: Format, data: &[u8], out: &mut Vec<u8>) {
+	match wav_format(format) {
+		Format::U8 => convert::<u8>(format, data, out),
+		Format::S16LE => convert::<i16>(format, data, out),
+		Format::S24LE => convert::<i24>(format, data, out),
+		Format::S32LE => convert::<i32>(format, data, out),
+		Format::Float32LE => convert::<f32>(format, data, out),
+		Format::Float64LE => convert::<f64>(format, data, out),
+		_ => unreachable!(),
+	}
+}
+
+fn convert<T: Sample + Copy>(format: Format, data: &[u8], out: &mut Vec<u8>) {
+	let to = wav_format(format);
+	let from_step = format.bytes_per_sample();
+	let to_step = to.bytes_per_sample();
+	let sample_count = data.len() / from_step;
+
+	let mut samples: Vec<T> = (0..sample_count).map(|_| T::from_f32(0.0)).collect();
+	let mut converted = vec![0u8; sample_count * to_step];
+	unsafe {
+		read_samples(format, data.as_ptr(), from_step, samples.iter_mut());
+		write_samples(to, converted.as_mut_ptr(), to_step, samples.iter().cloned());
+	}
+
+	// 24 bit samples are in the low 3 bytes of each 4.
+	let packed_step = wav_bytes_per_sample(format);
+	for sample in converted.chunks(to_step) {
+		out.extend_from_slice(&sample[..packed_step]);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn channel_masks() {
+		assert_eq!(channel_mask(&ChannelLayout::get_builtin(ChannelLayoutId::Stereo)), 0x3);
+		assert_eq!(channel_mask(&ChannelLayout::get_builtin(ChannelLayoutId::Quad)), 0x33);
+		assert_eq!(channel_mask(&ChannelLayout {
+			name: String::new(),
+			channels: vec![ChannelId::FrontRight, ChannelId::FrontLeft],
+		}), 0);
+	}
+
+	#[test]
+	fn convert_samples() {
+		let mut out = Vec::new();
+		convert_to_wav(Format::S16BE, &[0x12, 0x34, 0xFF, 0xFE], &mut out);
+		assert_eq!(out, vec![0x34, 0x12, 0xFE, 0xFF]);
+
+		out.clear();
+		convert_to_wav(Format::S8, &[0x00, 0x7F], &mut out);
+		assert_eq!(out, vec![0x80, 0xFF]);
+
+		out.clear();
+		convert_to_wav(Format::S24LE, &[0x01, 0x02, 0x03, 0x00], &mut out);
+		assert_eq!(out, vec![0x01, 0x02, 0x03]);
+
+		out.clear();
+		convert_to_wav(Format::S24BE, &[0x00, 0x01, 0x02, 0x03, 0x00, 0xFF, 0xFF, 0xFE], &mut out);
+		assert_eq!(out, vec![0x03, 0x02, 0x01, 0xFE, 0xFF, 0xFF]);
+
+		out.clear();
+		convert_to_wav(Format::U24BE, &[0x00, 0x80, 0x00, 0x01, 0x00, 0x7F, 0xFF, 0xFF], &mut out);
+		assert_eq!(out, vec![0x01, 0x00, 0x00, 0xFF, 0xFF, 0xFF]);
+	}
+}
@@ -0,0 +1,13 @@
+extern crate soundio;
+
+use std::rc::Rc;
+
+fn main() {
+	// The context can be moved to another thread, and its callbacks with it.
+	let counter = Rc::new(0);
+	let _ctx = soundio::Context::new_with_callbacks(
+		None::<fn(soundio::Error)>,
+		Some(move || { let _ = &counter; }),
+		None::<fn()>,
+	);
+}
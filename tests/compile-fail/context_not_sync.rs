@@ -0,0 +1,8 @@
+extern crate soundio;
+
+fn assert_sync<T: Sync>() {}
+
+fn main() {
+	// A `Context` can only be used from one thread at a time.
+	assert_sync::<soundio::Context>();
+}
@@ -0,0 +1,8 @@
+extern crate soundio;
+
+fn main() {
+	// Calls that change the context's state need `&mut`.
+	let ctx = soundio::Context::new();
+	ctx.flush_events();
+	ctx.wait_events();
+}
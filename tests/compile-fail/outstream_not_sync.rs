@@ -0,0 +1,8 @@
+extern crate soundio;
+
+fn assert_sync<T: Sync>() {}
+
+fn main() {
+	// Streams can be moved to another thread, but not shared.
+	assert_sync::<soundio::OutStream>();
+}
@@ -0,0 +1,15 @@
+extern crate soundio;
+
+fn main() {
+	let ctx = soundio::Context::new();
+	let (producer, mut consumer) = ctx.ring_buffer::<&'static i32>(1).split();
+	{
+		// The producer can't be shortened to write a reference that the consumer would read as
+		// `&'static`.
+		let x = 5;
+		let mut producer: soundio::RingBufferProducer<&i32> = producer;
+		producer.write(&[&x]);
+	}
+	let mut out = [&0];
+	consumer.read(&mut out);
+}
@@ -0,0 +1,22 @@
+extern crate soundio;
+
+use std::rc::Rc;
+
+fn main() {
+	let mut ctx = soundio::Context::new();
+	ctx.connect_backend(soundio::Backend::Dummy).unwrap();
+	ctx.flush_events();
+	let device = ctx.default_output_device().unwrap();
+
+	// The write callback is called on the audio thread.
+	let counter = Rc::new(0);
+	let _stream = device.open_outstream(
+		44100,
+		soundio::Format::Float32LE,
+		soundio::ChannelLayout::get_builtin(soundio::ChannelLayoutId::Stereo),
+		0.1,
+		move |_: &mut soundio::OutStreamWriter| { let _ = &counter; },
+		None::<fn()>,
+		None::<fn(soundio::Error)>,
+	);
+}
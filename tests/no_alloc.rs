@@ -0,0 +1,135 @@
+//! Check that the real-time read and write paths never allocate. This uses a global allocator
+//! that counts allocations, so it is kept in its own test binary.
+
+extern crate soundio;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+struct CountingAllocator;
+
+// Allocations are counted per thread, because the streams' callbacks run on different threads at
+// the same time.
+thread_local!(static ALLOCATIONS: Cell<usize> = Cell::new(0));
+
+fn allocations() -> usize {
+	ALLOCATIONS.with(|allocations| allocations.get())
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		// This fails while the thread is being torn down, when nothing is being counted.
+		let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+		System.alloc(layout)
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		System.dealloc(ptr, layout)
+	}
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+// The number of times each callback ran, and the number of allocations made inside it.
+static WRITE_CALLBACKS: AtomicUsize = AtomicUsize::new(0);
+static WRITE_ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static READ_CALLBACKS: AtomicUsize = AtomicUsize::new(0);
+static READ_ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+fn write_callback(stream: &mut soundio::OutStreamWriter) {
+	let before = allocations();
+
+	let mut frames_left = stream.frame_count_max();
+	while frames_left > 0 {
+		let frame_count = match stream.begin_write(frames_left) {
+			Ok(0) | Err(_) => break,
+			Ok(x) => x,
+		};
+		for f in 0..frame_count {
+			for c in 0..stream.channel_count() {
+				stream.set_sample(c, f, 0.0f32);
+			}
+		}
+		if stream.end_write().is_err() {
+			break;
+		}
+		frames_left -= frame_count;
+	}
+
+	WRITE_ALLOCATIONS.fetch_add(allocations() - before, Ordering::SeqCst);
+	WRITE_CALLBACKS.fetch_add(1, Ordering::SeqCst);
+}
+
+fn read_callback(stream: &mut soundio::InStreamReader) {
+	let before = allocations();
+
+	let mut frames_left = stream.frame_count_max();
+	let mut total = 0.0f32;
+	while frames_left > 0 {
+		let frame_count = match stream.begin_read(frames_left) {
+			Ok(0) | Err(_) => break,
+			Ok(x) => x,
+		};
+		for f in 0..frame_count {
+			for c in 0..stream.channel_count() {
+				total += stream.sample::<f32>(c, f);
+			}
+		}
+		if stream.end_read().is_err() {
+			break;
+		}
+		frames_left -= frame_count;
+	}
+	assert!(total.is_finite());
+
+	READ_ALLOCATIONS.fetch_add(allocations() - before, Ordering::SeqCst);
+	READ_CALLBACKS.fetch_add(1, Ordering::SeqCst);
+}
+
+#[test]
+fn read_and_write_do_not_allocate() {
+	let mut ctx = soundio::Context::new();
+	ctx.connect_backend(soundio::Backend::Dummy).expect("Couldn't connect to backend");
+	ctx.flush_events();
+
+	let layout = soundio::ChannelLayout::get_default(2);
+
+	let output = ctx.default_output_device().expect("No output device");
+	let mut outstream = output.open_outstream(
+		48000,
+		soundio::Format::Float32LE,
+		layout.clone(),
+		0.01,
+		write_callback,
+		None::<fn()>,
+		None::<fn(soundio::Error)>,
+	).expect("Couldn't open output stream");
+
+	let input = ctx.default_input_device().expect("No input device");
+	let mut instream = input.open_instream(
+		48000,
+		soundio::Format::Float32LE,
+		layout,
+		0.01,
+		read_callback,
+		None::<fn()>,
+		None::<fn(soundio::Error)>,
+	).expect("Couldn't open input stream");
+
+	outstream.start().expect("Couldn't start output stream");
+	instream.start().expect("Couldn't start input stream");
+
+	thread::sleep(Duration::from_millis(200));
+
+	drop(instream);
+	drop(outstream);
+
+	assert!(WRITE_CALLBACKS.load(Ordering::SeqCst) > 0, "The write callback wasn't called");
+	assert!(READ_CALLBACKS.load(Ordering::SeqCst) > 0, "The read callback wasn't called");
+	assert_eq!(WRITE_ALLOCATIONS.load(Ordering::SeqCst), 0, "The write callback allocated");
+	assert_eq!(READ_ALLOCATIONS.load(Ordering::SeqCst), 0, "The read callback allocated");
+}
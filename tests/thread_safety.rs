@@ -0,0 +1,36 @@
+//! Check which types can be sent to and shared between threads. The code that mustn't compile is
+//! in `tests/compile-fail`, with the expected errors.
+
+extern crate soundio;
+extern crate trybuild;
+
+fn assert_send<T: Send>() {}
+fn assert_sync<T: Sync>() {}
+
+#[test]
+fn send_and_sync() {
+	assert_send::<soundio::Context>();
+	assert_send::<soundio::ContextWaker>();
+	assert_sync::<soundio::ContextWaker>();
+	assert_send::<soundio::Device>();
+	assert_sync::<soundio::Device>();
+
+	assert_send::<soundio::OutStream>();
+	assert_send::<soundio::InStream>();
+	assert_send::<soundio::BlockingOutStream<f32>>();
+	assert_send::<soundio::BlockingInStream<f32>>();
+	assert_send::<soundio::SupervisedOutStream>();
+	assert_send::<soundio::SupervisedInStream>();
+	assert_send::<soundio::FollowingOutStream>();
+	assert_send::<soundio::FollowingInStream>();
+
+	assert_send::<soundio::DeviceWatcher>();
+	assert_send::<soundio::EventLoop>();
+	assert_sync::<soundio::EventLoop>();
+}
+
+#[test]
+fn compile_fail() {
+	let t = trybuild::TestCases::new();
+	t.compile_fail("tests/compile-fail/*.rs");
+}